@@ -0,0 +1,29 @@
+#!/usr/bin/env python3
+# a tiny bot for the json protocol: heads for the apple and avoids whatever it would run into
+import json
+import sys
+
+moves = {}
+width = height = 0
+
+for line in sys.stdin:
+    message = json.loads(line)
+    if message["type"] == "start":
+        moves = message["moves"]
+        width, height = message["width"], message["height"]
+        continue
+
+    head = message["snake"][0]
+    apple = message["apple"]
+    blocked = {tuple(cell) for cell in message["snake"][:-1] + message["obstacles"]}
+
+    def step(direction):
+        dx, dy = moves[direction]
+        return ((head[0] + dx) % width, (head[1] + dy) % height)
+
+    def distance(cell):
+        return abs(cell[0] - apple[0]) + abs(cell[1] - apple[1])
+
+    safe = [direction for direction in moves if step(direction) not in blocked]
+    best = min(safe, key=lambda direction: distance(step(direction)), default=None)
+    print(json.dumps({"direction": best}), flush=True)
@@ -0,0 +1,28 @@
+// plans the shortest way to the apple around the body and the obstacles
+use super::grid::Grid;
+use crate::controller::{Controller, GameView};
+use crate::model::Direction;
+
+#[derive(Default)]
+pub struct AStarController;
+
+impl Controller for AStarController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        let grid = Grid::from_view(view);
+        let head = view.snake.body[0].get().position;
+        let heading = view.snake.head_direction();
+
+        match grid.find_path(head, heading, view.apple.position) {
+            Some(path) => path.first().copied(),
+            None => roomiest_move(&grid, head, heading),
+        }
+    }
+}
+
+// without a way to the apple, stay alive by going where there is the most space
+pub fn roomiest_move(grid: &Grid, head: (u32, u32), heading: Direction) -> Option<Direction> {
+    grid.safe_moves(head, heading)
+        .into_iter()
+        .max_by_key(|(_, position)| grid.reachable_area(*position))
+        .map(|(direction, _)| direction)
+}
@@ -0,0 +1,18 @@
+// heads straight for the apple, only looking one move ahead
+use super::grid::Grid;
+use crate::controller::{Controller, GameView};
+use crate::model::Direction;
+
+#[derive(Default)]
+pub struct GreedyController;
+
+impl Controller for GreedyController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        let grid = Grid::from_view(view);
+        let head = view.snake.body[0].get().position;
+        grid.safe_moves(head, view.snake.head_direction())
+            .into_iter()
+            .min_by_key(|(_, position)| grid.distance(*position, view.apple.position))
+            .map(|(direction, _)| direction)
+    }
+}
@@ -0,0 +1,271 @@
+// the field as the bots see it: for every cell, how many moves until it is free to enter
+use crate::controller::GameView;
+use crate::model::{portal_exit, Direction, Edge, Edges, Field, Portal, Snake};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+// obstacles never go away
+const BLOCKED: u32 = u32::MAX;
+
+pub struct Grid {
+    pub width: u32,
+    pub height: u32,
+    // without a border the snake comes out on the other side, on every edge
+    pub wraps: bool,
+    has_border: bool,
+    edges: Edges,
+    portals: Vec<Portal>,
+    free_after: Vec<u32>,
+}
+
+impl Grid {
+    pub fn from_view(view: &GameView) -> Self {
+        let positions = |snake: &Snake| -> Vec<(u32, u32)> {
+            snake
+                .body
+                .iter()
+                .map(|body_part| body_part.get().position)
+                .collect()
+        };
+        let mut grid = Self::from_parts(view.field, &positions(view.snake));
+        // the other snakes move out of the way the same as our own body
+        for other in &view.others {
+            grid.add_body(&positions(other));
+        }
+        // their heads could move next to them on the same tick, so those cells aren't safe
+        // on the first move
+        for other in &view.others {
+            let head = other.body[0].get().position;
+            for direction in [
+                Direction::UP,
+                Direction::DOWN,
+                Direction::LEFT,
+                Direction::RIGHT,
+            ] {
+                if let Some(next) = grid.neighbor(head, direction) {
+                    let cell = grid.index(next);
+                    if grid.free_after[cell] != BLOCKED {
+                        grid.free_after[cell] = grid.free_after[cell].max(2);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    // the body goes from the head to the tail
+    pub fn from_parts(field: &Field, body: &[(u32, u32)]) -> Self {
+        let (width, height) = field.size;
+        let mut grid = Self {
+            width,
+            height,
+            wraps: !field.has_border() && field.edges == Edges::TORUS,
+            has_border: field.has_border(),
+            edges: field.edges,
+            portals: field.portals.clone(),
+            free_after: vec![0; (width * height) as usize],
+        };
+        if let Some(obstacles) = &field.obstacles {
+            for obstacle_part in obstacles {
+                let index = grid.index(*obstacle_part);
+                grid.free_after[index] = BLOCKED;
+            }
+        }
+        grid.add_body(body);
+        grid
+    }
+
+    // the tail leaves its cell on the next move, the part before it one move later and so on
+    pub fn add_body(&mut self, body: &[(u32, u32)]) {
+        for (index, position) in body.iter().enumerate() {
+            let cell = self.index(*position);
+            if self.free_after[cell] != BLOCKED {
+                self.free_after[cell] = self.free_after[cell].max((body.len() - index) as u32);
+            }
+        }
+    }
+
+    pub fn index(&self, position: (u32, u32)) -> usize {
+        (position.1 * self.width + position.0) as usize
+    }
+
+    // true when the snake can be on this cell after the given number of moves
+    pub fn is_free_after(&self, position: (u32, u32), moves: u32) -> bool {
+        let free_after = self.free_after[self.index(position)];
+        free_after != BLOCKED && free_after <= moves
+    }
+
+    // where the head ends up after one move, bouncing off an edge isn't planned with so those
+    // count as walls
+    pub fn neighbor(&self, position: (u32, u32), direction: Direction) -> Option<(u32, u32)> {
+        match self
+            .edges
+            .crossed(position, direction, (self.width, self.height))
+        {
+            // a border is made of obstacles, there is nothing to go around to
+            Some(Edge::Wrap) if self.has_border => return None,
+            Some(Edge::Wall | Edge::Bounce) => return None,
+            Some(Edge::Wrap) | None => {}
+        }
+        let next = direction.step(position, self.width, self.height);
+        Some(portal_exit(&self.portals, next).unwrap_or(next))
+    }
+
+    // the number of moves between two cells if nothing was in the way, portals aside
+    pub fn distance(&self, from: (u32, u32), to: (u32, u32)) -> u32 {
+        let axis = |a: u32, b: u32, size: u32, wraps: bool| {
+            let straight = a.abs_diff(b);
+            if wraps && !self.has_border {
+                straight.min(size - straight)
+            } else {
+                straight
+            }
+        };
+        axis(from.0, to.0, self.width, self.edges.wraps_horizontally())
+            + axis(from.1, to.1, self.height, self.edges.wraps_vertically())
+    }
+
+    // for every portal end, the fewest moves from where it lets out to the goal if nothing was in
+    // the way, going through other portals too
+    fn portal_bounds(&self, goal: (u32, u32)) -> Vec<((u32, u32), u32)> {
+        let ends: Vec<((u32, u32), (u32, u32))> = self
+            .portals
+            .iter()
+            .flat_map(|(first, second)| [(*first, *second), (*second, *first)])
+            .collect();
+        let mut bounds: Vec<u32> = ends
+            .iter()
+            .map(|(_, exit)| self.distance(*exit, goal))
+            .collect();
+        // every round lets the ways use one more portal
+        for _ in 0..ends.len() {
+            for (index, (_, exit)) in ends.iter().enumerate() {
+                for (other, (entrance, _)) in ends.iter().enumerate() {
+                    let through = self.distance(*exit, *entrance) + bounds[other];
+                    bounds[index] = bounds[index].min(through);
+                }
+            }
+        }
+        ends.iter()
+            .map(|(entrance, _)| *entrance)
+            .zip(bounds)
+            .collect()
+    }
+
+    // never more moves than the way to the goal really takes, so a* still finds the shortest one
+    fn estimate(
+        &self,
+        position: (u32, u32),
+        goal: (u32, u32),
+        portal_bounds: &[((u32, u32), u32)],
+    ) -> u32 {
+        portal_bounds
+            .iter()
+            .map(|(entrance, bound)| self.distance(position, *entrance) + bound)
+            .fold(self.distance(position, goal), u32::min)
+    }
+
+    // a* from start to goal where every cell has to be free by the time the snake gets there,
+    // the first move can't be the opposite of the current heading
+    pub fn find_path(
+        &self,
+        start: (u32, u32),
+        heading: Direction,
+        goal: (u32, u32),
+    ) -> Option<Vec<Direction>> {
+        let cells = (self.width * self.height) as usize;
+        let mut cost = vec![u32::MAX; cells];
+        let mut came_from: Vec<Option<(usize, Direction)>> = vec![None; cells];
+        let mut open = BinaryHeap::new();
+
+        let portal_bounds = self.portal_bounds(goal);
+        cost[self.index(start)] = 0;
+        open.push(Reverse((
+            self.estimate(start, goal, &portal_bounds),
+            0,
+            start,
+        )));
+
+        while let Some(Reverse((_, moves, position))) = open.pop() {
+            if position == goal && moves > 0 {
+                return Some(self.walk_back(&came_from, goal));
+            }
+            if moves > cost[self.index(position)] {
+                continue;
+            }
+            for direction in Direction::ALL {
+                if position == start && direction == heading.opposite() {
+                    continue;
+                }
+                let Some(next) = self.neighbor(position, direction) else {
+                    continue;
+                };
+                let next_moves = moves + 1;
+                let next_index = self.index(next);
+                if !self.is_free_after(next, next_moves) || next_moves >= cost[next_index] {
+                    continue;
+                }
+                cost[next_index] = next_moves;
+                came_from[next_index] = Some((self.index(position), direction));
+                open.push(Reverse((
+                    next_moves + self.estimate(next, goal, &portal_bounds),
+                    next_moves,
+                    next,
+                )));
+            }
+        }
+        None
+    }
+
+    // the start is the only cell nobody came from
+    fn walk_back(
+        &self,
+        came_from: &[Option<(usize, Direction)>],
+        goal: (u32, u32),
+    ) -> Vec<Direction> {
+        let mut path = vec![];
+        let mut index = self.index(goal);
+        while let Some((previous, direction)) = came_from[index] {
+            path.push(direction);
+            index = previous;
+        }
+        path.reverse();
+        path
+    }
+
+    // how many cells can be reached from here, used to avoid walking into dead ends
+    pub fn reachable_area(&self, start: (u32, u32)) -> usize {
+        let mut seen = vec![false; (self.width * self.height) as usize];
+        let mut queue = VecDeque::from([(start, 0)]);
+        seen[self.index(start)] = true;
+        let mut area = 0;
+
+        while let Some((position, moves)) = queue.pop_front() {
+            area += 1;
+            for direction in Direction::ALL {
+                let Some(next) = self.neighbor(position, direction) else {
+                    continue;
+                };
+                let next_index = self.index(next);
+                if !seen[next_index] && self.is_free_after(next, moves + 1) {
+                    seen[next_index] = true;
+                    queue.push_back((next, moves + 1));
+                }
+            }
+        }
+        area
+    }
+
+    // the moves that don't kill the snake right away
+    pub fn safe_moves(&self, head: (u32, u32), heading: Direction) -> Vec<(Direction, (u32, u32))> {
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| *direction != heading.opposite())
+            .filter_map(|direction| {
+                self.neighbor(head, direction)
+                    .map(|position| (direction, position))
+            })
+            .filter(|(_, position)| self.is_free_after(*position, 1))
+            .collect()
+    }
+}
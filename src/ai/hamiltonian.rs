@@ -0,0 +1,504 @@
+// an autopilot that can't lose: it walks a cycle through every free cell of the field, and takes
+// shortcuts towards the apple while the snake is short enough for them to be safe
+use super::grid::Grid;
+use crate::controller::{Controller, GameView};
+use crate::model::{Direction, Field};
+
+// how many cells the search may try before giving up on boards it can't build a cycle for
+const SEARCH_BUDGET: u64 = 5_000_000;
+// the room kept between the head and the tail when shortcutting, one apple grows the snake by one
+const SHORTCUT_MARGIN: u32 = 4;
+
+const NOT_ON_CYCLE: u32 = u32::MAX;
+
+pub struct Cycle {
+    grid: Grid,
+    // the cells in the order they are visited
+    pub cells: Vec<(u32, u32)>,
+    // for every cell of the field its place in the cycle
+    order: Vec<u32>,
+}
+
+impl Cycle {
+    // builds a cycle through every cell without an obstacle, the error tells why there is none
+    pub fn find(field: &Field) -> Result<Self, String> {
+        let (width, height) = field.size;
+        if !field.portals.is_empty() {
+            return Err("it can't plan a way through portals".to_string());
+        }
+        if field.changes() {
+            return Err("it can't plan around obstacles that move or show up".to_string());
+        }
+        // without a snake on it the grid only knows the obstacles
+        let grid = Grid::from_parts(field, &[]);
+        let free: Vec<bool> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|position| grid.is_free_after(position, 0))
+            .collect();
+        let free_count = free.iter().filter(|cell| **cell).count();
+        if free_count < 4 {
+            return Err("there are less than 4 free cells".to_string());
+        }
+
+        let cells = match plain_rectangle(&free, width, height) {
+            Some((origin, size)) => {
+                rectangle_cycle(origin, size, grid.wraps && size == (width, height))?
+            }
+            None => {
+                let graph = FreeGraph {
+                    grid: &grid,
+                    free: &free,
+                };
+                graph.check_possible(free_count)?;
+                graph.search(free_count)?
+            }
+        };
+
+        let mut order = vec![NOT_ON_CYCLE; (width * height) as usize];
+        for (index, cell) in cells.iter().enumerate() {
+            order[(cell.1 * width + cell.0) as usize] = index as u32;
+        }
+        Ok(Self { grid, cells, order })
+    }
+
+    pub fn len(&self) -> u32 {
+        self.cells.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    fn order_of(&self, position: (u32, u32)) -> u32 {
+        self.order[self.grid.index(position)]
+    }
+
+    // how many steps along the cycle it takes to get from one cell to the other
+    fn distance(&self, from: (u32, u32), to: (u32, u32)) -> u32 {
+        (self.order_of(to) + self.len() - self.order_of(from)) % self.len()
+    }
+
+    fn reverse(&mut self) {
+        self.cells.reverse();
+        for (index, cell) in self.cells.iter().enumerate() {
+            self.order[self.grid.index(*cell)] = index as u32;
+        }
+    }
+
+    fn direction_between(&self, from: (u32, u32), to: (u32, u32)) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .find(|direction| self.grid.neighbor(from, *direction) == Some(to))
+    }
+}
+
+// the origin and size of the free area when it is a full rectangle, which is the case for every
+// board without random obstacles
+fn plain_rectangle(free: &[bool], width: u32, height: u32) -> Option<((u32, u32), (u32, u32))> {
+    let free_cells: Vec<(u32, u32)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|(x, y)| free[(y * width + x) as usize])
+        .collect();
+    let min_x = free_cells.iter().map(|cell| cell.0).min()?;
+    let max_x = free_cells.iter().map(|cell| cell.0).max()?;
+    let min_y = free_cells.iter().map(|cell| cell.1).min()?;
+    let max_y = free_cells.iter().map(|cell| cell.1).max()?;
+    let size = (max_x - min_x + 1, max_y - min_y + 1);
+    if (size.0 * size.1) as usize != free_cells.len() {
+        return None;
+    }
+    Some(((min_x, min_y), size))
+}
+
+fn rectangle_cycle(
+    origin: (u32, u32),
+    size: (u32, u32),
+    is_torus: bool,
+) -> Result<Vec<(u32, u32)>, String> {
+    let (width, height) = size;
+    let at = |x: u32, y: u32| (origin.0 + x, origin.1 + y);
+    let transpose = |cells: Vec<(u32, u32)>| {
+        cells
+            .into_iter()
+            .map(|(x, y)| at(y, x))
+            .collect::<Vec<(u32, u32)>>()
+    };
+
+    if width < 2 || height < 2 {
+        return Err(format!("the free area is only {}x{}", width, height));
+    }
+    if height % 2 == 0 {
+        return Ok(comb(width, height)
+            .into_iter()
+            .map(|(x, y)| at(x, y))
+            .collect());
+    }
+    if width % 2 == 0 {
+        return Ok(transpose(comb(height, width)));
+    }
+    if !is_torus {
+        return Err(format!(
+            "the free area is {}x{}, a grid with two odd sides has no hamiltonian cycle \
+             (try an even size or no border)",
+            width, height
+        ));
+    }
+    if height >= width {
+        Ok(staircase(width, height))
+    } else {
+        Ok(transpose(staircase(height, width)))
+    }
+}
+
+// goes right along the first row, snakes back through the other rows leaving the first column
+// free and comes back up through it, the height has to be even
+fn comb(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut cells: Vec<(u32, u32)> = (0..width).map(|x| (x, 0)).collect();
+    for y in 1..height {
+        if y % 2 == 1 {
+            cells.extend((1..width).rev().map(|x| (x, y)));
+        } else {
+            cells.extend((1..width).map(|x| (x, y)));
+        }
+    }
+    cells.extend((1..height).rev().map(|y| (0, y)));
+    cells
+}
+
+// for a field that wraps on both sides with two odd sides, the comb can't work so every row is
+// walked completely (wrapping around), stepping down from wherever it ended. walking a row to
+// the right moves the start one column to the left and the other way around, so enough rows
+// going each way bring the last row back above the first start (needs height >= width)
+fn staircase(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let rows_to_the_right = (height + width) / 2;
+    let mut cells = vec![];
+    let mut start = 0;
+    for y in 0..height {
+        if y < rows_to_the_right {
+            cells.extend((0..width).map(|step| ((start + step) % width, y)));
+            start = (start + width - 1) % width;
+        } else {
+            cells.extend((0..width).map(|step| ((start + width - step) % width, y)));
+            start = (start + 1) % width;
+        }
+    }
+    cells
+}
+
+// the free cells by their index in the grid
+struct FreeGraph<'a> {
+    grid: &'a Grid,
+    free: &'a [bool],
+}
+
+impl FreeGraph<'_> {
+    fn position(&self, cell: usize) -> (u32, u32) {
+        (cell as u32 % self.grid.width, cell as u32 / self.grid.width)
+    }
+
+    fn neighbors(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let position = self.position(cell);
+        Direction::ALL.into_iter().filter_map(move |direction| {
+            let index = self.grid.index(self.grid.neighbor(position, direction)?);
+            self.free[index].then_some(index)
+        })
+    }
+
+    // the quick reasons a cycle can't exist
+    fn check_possible(&self, free_count: usize) -> Result<(), String> {
+        for cell in (0..self.free.len()).filter(|cell| self.free[*cell]) {
+            if self.neighbors(cell).count() < 2 {
+                let (x, y) = self.position(cell);
+                return Err(format!("the cell ({}, {}) is a dead end", x, y));
+            }
+        }
+
+        let first = (0..self.free.len())
+            .find(|cell| self.free[*cell])
+            .expect("there are free cells");
+        let mut seen = vec![false; self.free.len()];
+        let mut stack = vec![first];
+        seen[first] = true;
+        let mut reached = 0;
+        while let Some(cell) = stack.pop() {
+            reached += 1;
+            for next in self.neighbors(cell) {
+                if !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        if reached != free_count {
+            return Err("the obstacles split the field in parts".to_string());
+        }
+
+        // a cycle on a checkerboard alternates colors, so both colors need as many free cells,
+        // wrapping around an odd side breaks the checkerboard so this only holds otherwise
+        let (width, height) = (self.grid.width, self.grid.height);
+        let is_checkerboard = !self.grid.wraps || (width % 2 == 0 && height % 2 == 0);
+        if is_checkerboard {
+            let dark = (0..self.free.len())
+                .filter(|cell| self.free[*cell])
+                .filter(|cell| {
+                    let (x, y) = self.position(*cell);
+                    (x + y) % 2 == 0
+                })
+                .count();
+            let light = free_count - dark;
+            if dark != light {
+                return Err(format!(
+                    "{} free cells are dark and {} light on the checkerboard, a cycle needs \
+                     as many of both",
+                    dark, light
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // depth first search that always tries the cell with the fewest ways out first and backs
+    // off as soon as a cell next to the path would be left with less than two ways in and out
+    fn search(&self, free_count: usize) -> Result<Vec<(u32, u32)>, String> {
+        let start = (0..self.free.len())
+            .filter(|cell| self.free[*cell])
+            .min_by_key(|cell| self.neighbors(*cell).count())
+            .expect("there are free cells");
+        let mut visited = vec![false; self.free.len()];
+        let mut path = vec![start];
+        visited[start] = true;
+        // the cells still to try for every cell on the path
+        let mut choices: Vec<Vec<usize>> = vec![self.ordered_choices(start, start, &visited)];
+        let mut budget = SEARCH_BUDGET;
+
+        while let Some(options) = choices.last_mut() {
+            let head = *path.last().expect("the path is never empty here");
+            if path.len() == free_count {
+                if self.neighbors(head).any(|cell| cell == start) {
+                    return Ok(path.into_iter().map(|cell| self.position(cell)).collect());
+                }
+                options.clear();
+            }
+
+            match options.pop() {
+                Some(next) => {
+                    budget = budget.checked_sub(1).ok_or(
+                        "no hamiltonian cycle was found before the search gave up".to_string(),
+                    )?;
+                    visited[next] = true;
+                    path.push(next);
+                    if self.strands_a_cell(head, next, start, &visited) {
+                        visited[next] = false;
+                        path.pop();
+                        continue;
+                    }
+                    let next_options = self.ordered_choices(next, start, &visited);
+                    choices.push(next_options);
+                }
+                None => {
+                    choices.pop();
+                    if let Some(cell) = path.pop() {
+                        visited[cell] = false;
+                    }
+                }
+            }
+        }
+        Err("the obstacles leave no hamiltonian cycle".to_string())
+    }
+
+    // the unvisited neighbors, the one with the fewest ways out last since it is popped first
+    fn ordered_choices(&self, cell: usize, start: usize, visited: &[bool]) -> Vec<usize> {
+        let mut options: Vec<usize> = self
+            .neighbors(cell)
+            .filter(|next| !visited[*next])
+            .collect();
+        options.sort_by_key(|next| std::cmp::Reverse(self.ways_out(*next, start, visited)));
+        options
+    }
+
+    fn ways_out(&self, cell: usize, start: usize, visited: &[bool]) -> usize {
+        self.neighbors(cell)
+            .filter(|next| !visited[*next] || *next == start)
+            .count()
+    }
+
+    // after stepping from head to next, an unvisited cell next to the old head lost a way in
+    fn strands_a_cell(&self, head: usize, next: usize, start: usize, visited: &[bool]) -> bool {
+        for cell in self.neighbors(head) {
+            if visited[cell] {
+                continue;
+            }
+            let ways = self
+                .neighbors(cell)
+                .filter(|other| !visited[*other] || *other == start || *other == next)
+                .count();
+            if ways < 2 {
+                return true;
+            }
+        }
+        // the start has to keep a way back in
+        !self
+            .neighbors(start)
+            .any(|cell| !visited[cell] || cell == next)
+    }
+}
+
+#[derive(Default)]
+pub struct HamiltonianController {
+    cycle: Option<Cycle>,
+    checked_direction: bool,
+}
+
+impl HamiltonianController {
+    pub fn for_field(field: &Field) -> Result<Self, String> {
+        Ok(Self {
+            cycle: Some(Cycle::find(field)?),
+            checked_direction: false,
+        })
+    }
+}
+
+impl Controller for HamiltonianController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        let cycle = self.cycle.as_mut()?;
+        let body: Vec<(u32, u32)> = view
+            .snake
+            .body
+            .iter()
+            .map(|body_part| body_part.get().position)
+            .collect();
+        let head = body[0];
+        let tail = body[body.len() - 1];
+
+        // the cycle can go either way, pick the one that doesn't start by turning back
+        let heading = view.snake.head_direction();
+        if !self.checked_direction {
+            let next = cycle.cells[((cycle.order_of(head) + 1) % cycle.len()) as usize];
+            if cycle.direction_between(head, next) == Some(heading.opposite()) {
+                cycle.reverse();
+            }
+            self.checked_direction = true;
+        }
+        let next_on_cycle = cycle.cells[((cycle.order_of(head) + 1) % cycle.len()) as usize];
+        let follow = cycle.direction_between(head, next_on_cycle);
+
+        // the body has to lie on the stretch of the cycle between the tail and the head,
+        // everything ahead of the head up to the tail is then known to be free
+        let stretch = cycle.distance(tail, head);
+        let body_on_stretch = body
+            .iter()
+            .all(|part| cycle.distance(tail, *part) <= stretch);
+        if !body_on_stretch || body.len() as u32 * 2 > cycle.len() {
+            return follow;
+        }
+
+        let room = cycle.distance(head, tail).saturating_sub(SHORTCUT_MARGIN);
+        let to_apple = cycle.distance(head, view.apple.position);
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| *direction != heading.opposite())
+            .filter_map(|direction| {
+                let next = cycle.grid.neighbor(head, direction)?;
+                if cycle.order_of(next) == NOT_ON_CYCLE {
+                    return None;
+                }
+                let ahead = cycle.distance(head, next);
+                (ahead > 0 && ahead < room && ahead <= to_apple).then_some((direction, ahead))
+            })
+            .max_by_key(|(_, ahead)| *ahead)
+            .map(|(direction, _)| direction)
+            .or(follow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Edge, Edges};
+
+    // every free cell once and every cell next to the one before it, the last one next to the first
+    fn assert_visits_every_cell_once(field: &Field, cells: &[(u32, u32)]) {
+        let grid = Grid::from_parts(field, &[]);
+        let (width, height) = field.size;
+        let mut visits = vec![0; (width * height) as usize];
+        for cell in cells {
+            visits[grid.index(*cell)] += 1;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let expected = if grid.is_free_after((x, y), 0) { 1 } else { 0 };
+                assert_eq!(visits[grid.index((x, y))], expected, "cell ({}, {})", x, y);
+            }
+        }
+        for (index, cell) in cells.iter().enumerate() {
+            let next = cells[(index + 1) % cells.len()];
+            let is_adjacent = Direction::ALL
+                .into_iter()
+                .any(|direction| grid.neighbor(*cell, direction) == Some(next));
+            assert!(is_adjacent, "{:?} isn't next to {:?}", cell, next);
+        }
+    }
+
+    fn field(width: u32, height: u32, has_border: bool, walls: &[(u32, u32)]) -> Field {
+        let mut field = Field::from((width, height), has_border, false, 0);
+        if !walls.is_empty() {
+            let mut obstacles = field.obstacles.take().unwrap_or_default();
+            obstacles.extend_from_slice(walls);
+            field.obstacles = Some(obstacles);
+        }
+        field
+    }
+
+    #[test]
+    fn rectangles_with_an_even_side_are_covered() {
+        for (width, height) in [(2, 2), (6, 4), (8, 8), (5, 4), (4, 5), (7, 10), (10, 7)] {
+            // walls all around so wrapping doesn't make cells next to each other
+            let mut board = field(width, height, false, &[]);
+            board.edges = Edges::all(Edge::Wall);
+            let cells = rectangle_cycle((0, 0), (width, height), false).unwrap();
+            assert_visits_every_cell_once(&board, &cells);
+        }
+    }
+
+    #[test]
+    fn odd_rectangles_need_to_wrap() {
+        for (width, height) in [(3, 3), (5, 7), (9, 5)] {
+            assert!(rectangle_cycle((0, 0), (width, height), false).is_err());
+            let board = field(width, height, false, &[]);
+            let cells = rectangle_cycle((0, 0), (width, height), true).unwrap();
+            assert_visits_every_cell_once(&board, &cells);
+        }
+    }
+
+    #[test]
+    fn borders_leave_the_inside() {
+        for (width, height) in [(10, 10), (9, 10), (10, 9), (22, 12)] {
+            let board = field(width, height, true, &[]);
+            let cycle = Cycle::find(&board).unwrap();
+            assert_visits_every_cell_once(&board, &cycle.cells);
+        }
+        assert!(Cycle::find(&field(9, 9, true, &[])).is_err());
+    }
+
+    #[test]
+    fn walls_are_searched_around() {
+        // a corner of the inside cut off, as many dark cells as light ones are left
+        let board = field(8, 8, true, &[(1, 1), (2, 1)]);
+        let cycle = Cycle::find(&board).unwrap();
+        assert_visits_every_cell_once(&board, &cycle.cells);
+
+        let board = field(10, 8, false, &[(4, 3), (5, 3), (4, 4), (5, 4)]);
+        let cycle = Cycle::find(&board).unwrap();
+        assert_visits_every_cell_once(&board, &cycle.cells);
+    }
+
+    #[test]
+    fn an_odd_free_area_is_unplayable() {
+        for walls in [vec![(3, 3)], vec![(1, 1), (2, 1), (3, 1)], vec![(4, 5)]] {
+            let board = field(8, 8, true, &walls);
+            assert!(HamiltonianController::for_field(&board).is_err());
+        }
+        let board = field(10, 10, false, &[(5, 5)]);
+        assert!(HamiltonianController::for_field(&board).is_err());
+    }
+}
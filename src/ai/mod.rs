@@ -0,0 +1,26 @@
+// bots that play the game on their own, pick one with by_name
+pub mod astar;
+pub mod greedy;
+pub mod grid;
+pub mod hamiltonian;
+pub mod safe;
+pub use astar::AStarController;
+pub use greedy::GreedyController;
+pub use hamiltonian::HamiltonianController;
+pub use safe::SafeController;
+
+use crate::controller::Controller;
+use crate::model::Field;
+
+pub const NAMES: [&str; 4] = ["greedy", "astar", "safe", "hamiltonian"];
+
+// some bots plan the whole field ahead and fail when it can't be played their way
+pub fn by_name(name: &str, field: &Field) -> Result<Box<dyn Controller>, String> {
+    match name {
+        "greedy" => Ok(Box::new(GreedyController)),
+        "astar" => Ok(Box::new(AStarController)),
+        "safe" => Ok(Box::<SafeController>::default()),
+        "hamiltonian" => Ok(Box::new(HamiltonianController::for_field(field)?)),
+        _ => Err(format!("There is no bot called {}", name)),
+    }
+}
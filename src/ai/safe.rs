@@ -0,0 +1,106 @@
+// only goes for the apple when it can still reach its own tail afterwards, otherwise it follows
+// the tail around until the way to the apple opens up
+use super::astar::roomiest_move;
+use super::grid::Grid;
+use crate::controller::{Controller, GameView};
+use crate::model::{Direction, Field};
+use std::collections::{HashSet, VecDeque};
+
+#[derive(Default)]
+pub struct SafeController {
+    // where the head was and how long the snake was while chasing its tail, being there again
+    // means it goes round in circles
+    chased: HashSet<((u32, u32), usize)>,
+}
+
+impl Controller for SafeController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        let grid = Grid::from_view(view);
+        let body: Vec<(u32, u32)> = view
+            .snake
+            .body
+            .iter()
+            .map(|body_part| body_part.get().position)
+            .collect();
+        let head = body[0];
+        let heading = view.snake.head_direction();
+
+        if let Some(path) = grid.find_path(head, heading, view.apple.position) {
+            if can_reach_tail_after(view.field, &grid, &body, heading, &path) {
+                self.chased.clear();
+                return path.first().copied();
+            }
+        }
+
+        // the shortest way to the apple never gets safe from the circle the tail leads around,
+        // so try going there another way and otherwise break out of the circle
+        if !self.chased.insert((head, body.len())) {
+            if let Some(direction) = detour_to_apple(view, &grid, &body, heading) {
+                return Some(direction);
+            }
+            return roomiest_move(&grid, head, heading);
+        }
+
+        // chasing the tail keeps the most room open
+        let tail = body[body.len() - 1];
+        if let Some(path) = grid.find_path(head, heading, tail) {
+            return path.first().copied();
+        }
+        roomiest_move(&grid, head, heading)
+    }
+}
+
+// the first move of the shortest safe way to the apple that starts with a move the shortest way
+// doesn't take
+fn detour_to_apple(
+    view: &GameView,
+    grid: &Grid,
+    body: &[(u32, u32)],
+    heading: Direction,
+) -> Option<Direction> {
+    grid.safe_moves(body[0], heading)
+        .into_iter()
+        .filter(|(_, next)| *next != view.apple.position)
+        .filter_map(|(direction, next)| {
+            let moved: Vec<(u32, u32)> = [next]
+                .into_iter()
+                .chain(body[..body.len() - 1].iter().copied())
+                .collect();
+            let rest = Grid::from_parts(view.field, &moved).find_path(
+                next,
+                direction,
+                view.apple.position,
+            )?;
+            let path: Vec<Direction> = [direction].into_iter().chain(rest).collect();
+            can_reach_tail_after(view.field, grid, body, heading, &path).then_some(path)
+        })
+        .min_by_key(|path| path.len())
+        .map(|path| path[0])
+}
+
+// moves a copy of the snake along the path and checks it isn't trapped once it has eaten
+fn can_reach_tail_after(
+    field: &Field,
+    grid: &Grid,
+    body: &[(u32, u32)],
+    mut heading: Direction,
+    path: &[Direction],
+) -> bool {
+    let mut virtual_body: VecDeque<(u32, u32)> = body.iter().copied().collect();
+    for (index, direction) in path.iter().enumerate() {
+        let Some(head) = grid.neighbor(virtual_body[0], *direction) else {
+            return false;
+        };
+        virtual_body.push_front(head);
+        // the snake grows on the last move since that's where the apple is
+        if index + 1 < path.len() {
+            virtual_body.pop_back();
+        }
+        heading = *direction;
+    }
+
+    let virtual_body: Vec<(u32, u32)> = virtual_body.into_iter().collect();
+    let after = Grid::from_parts(field, &virtual_body);
+    let tail = virtual_body[virtual_body.len() - 1];
+    after.find_path(virtual_body[0], heading, tail).is_some()
+}
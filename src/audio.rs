@@ -0,0 +1,214 @@
+// sound effects and background music, everything is synthesized so there are no files to ship
+// when there is no audio device the game just plays silently
+use sdl2::mixer::{self, Channel, Chunk, AUDIO_S16SYS};
+use sdl2::{AudioSubsystem, Sdl};
+use snake_game_sdl::*;
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: i32 = 44_100;
+// the first channel is kept for the music so effects never cut it off
+const MUSIC_CHANNEL: Channel = Channel(0);
+const EFFECT_CHANNELS: i32 = 8;
+// the notes of the music loop, one note lasts two ticks so faster games get faster music
+const MUSIC_NOTES: [f32; 8] = [110.0, 110.0, 164.8, 110.0, 130.8, 110.0, 164.8, 146.8];
+const MAX_NOTE_MS: u32 = 400;
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Square,
+    Triangle,
+    Noise,
+}
+
+pub struct Volume {
+    // percentages
+    pub effects: u32,
+    pub music: u32,
+    pub muted: bool,
+}
+
+struct Sounds {
+    eat: Chunk,
+    turn: Chunk,
+    collision: Chunk,
+    win: Chunk,
+    pause: Chunk,
+    resume: Chunk,
+    power_up: Chunk,
+    poison: Chunk,
+    // moving through the level select
+    navigate: Chunk,
+    music: Chunk,
+}
+
+pub struct Audio {
+    // none when no device could be opened
+    sounds: Option<Sounds>,
+    // sdl shuts the audio down when this is dropped
+    _subsystem: Option<AudioSubsystem>,
+    pub volume: Volume,
+}
+
+impl Audio {
+    pub fn open(sdl_context: &Sdl, volume: Volume, tickspeed: u32) -> Self {
+        let mut audio = Self {
+            sounds: None,
+            _subsystem: None,
+            volume,
+        };
+        match audio.open_device(sdl_context, tickspeed) {
+            Ok(()) => audio.apply_volume(),
+            Err(e) => eprintln!("No audio available, playing without sound: {}", e),
+        }
+        audio
+    }
+
+    fn open_device(&mut self, sdl_context: &Sdl, tickspeed: u32) -> Result<(), String> {
+        self._subsystem = Some(sdl_context.audio()?);
+        mixer::open_audio(SAMPLE_RATE, AUDIO_S16SYS, mixer::DEFAULT_CHANNELS, 1024)?;
+        // the mixer may pick another rate or channel count than we asked for
+        let (sample_rate, _, channels) = mixer::query_spec()?;
+        mixer::allocate_channels(EFFECT_CHANNELS + 1);
+        mixer::reserve_channels(1);
+
+        let tone = |notes: &[(f32, u32)], wave: Wave| {
+            Chunk::from_raw_buffer(synthesize(notes, wave, sample_rate, channels))
+        };
+        let note_ms = (tickspeed * 2).min(MAX_NOTE_MS);
+        let music_notes: Vec<(f32, u32)> =
+            MUSIC_NOTES.iter().map(|note| (*note, note_ms)).collect();
+
+        self.sounds = Some(Sounds {
+            eat: tone(&[(660.0, 50), (990.0, 70)], Wave::Square)?,
+            turn: tone(&[(220.0, 25)], Wave::Triangle)?,
+            collision: tone(&[(160.0, 120), (80.0, 300)], Wave::Noise)?,
+            win: tone(
+                &[(523.3, 110), (659.3, 110), (784.0, 110), (1046.5, 300)],
+                Wave::Square,
+            )?,
+            pause: tone(&[(440.0, 80), (330.0, 120)], Wave::Triangle)?,
+            resume: tone(&[(330.0, 80), (440.0, 120)], Wave::Triangle)?,
+            power_up: tone(&[(523.3, 40), (784.0, 40), (1046.5, 60)], Wave::Square)?,
+            poison: tone(&[(220.0, 60), (185.0, 60), (147.0, 120)], Wave::Square)?,
+            navigate: tone(&[(880.0, 30)], Wave::Triangle)?,
+            music: tone(&music_notes, Wave::Triangle)?,
+        });
+        Ok(())
+    }
+
+    pub fn play_events(&self, events: &[GameEvent]) {
+        let Some(sounds) = &self.sounds else {
+            return;
+        };
+        for event in events {
+            let chunk = match event {
+                GameEvent::AppleEaten => &sounds.eat,
+                GameEvent::Turned => &sounds.turn,
+                GameEvent::Collided | GameEvent::Disqualified => &sounds.collision,
+                GameEvent::Won => &sounds.win,
+                GameEvent::Paused => &sounds.pause,
+                GameEvent::Resumed => &sounds.resume,
+                GameEvent::PoweredUp => &sounds.power_up,
+                GameEvent::Poisoned => &sounds.poison,
+            };
+            // every effect channel being busy just means this one is skipped
+            let _ = Channel::all().play(chunk, 0);
+        }
+    }
+
+    // a menu isn't part of a game so it has no event for it
+    pub fn play_navigate(&self) {
+        if let Some(sounds) = &self.sounds {
+            let _ = Channel::all().play(&sounds.navigate, 0);
+        }
+    }
+
+    // the music only plays while the snake is moving
+    pub fn sync_music(&self, state: GameState) {
+        let Some(sounds) = &self.sounds else {
+            return;
+        };
+        // paused channels still count as playing
+        let playing = MUSIC_CHANNEL.is_playing();
+        match state {
+            GameState::Running if MUSIC_CHANNEL.is_paused() => MUSIC_CHANNEL.resume(),
+            GameState::Running if !playing => {
+                let _ = MUSIC_CHANNEL.play(&sounds.music, -1);
+            }
+            GameState::Paused if playing => MUSIC_CHANNEL.pause(),
+            GameState::GameOver if playing => MUSIC_CHANNEL.halt(),
+            _ => {}
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.volume.muted = !self.volume.muted;
+        self.apply_volume();
+    }
+
+    pub fn change_volume(&mut self, difference: i32) {
+        self.volume.effects = (self.volume.effects as i32 + difference).clamp(0, 100) as u32;
+        self.volume.music = (self.volume.music as i32 + difference).clamp(0, 100) as u32;
+        self.apply_volume();
+    }
+
+    fn apply_volume(&mut self) {
+        if self.sounds.is_none() {
+            return;
+        }
+        let to_mixer = |percentage: u32| {
+            if self.volume.muted {
+                0
+            } else {
+                percentage as i32 * mixer::MAX_VOLUME / 100
+            }
+        };
+        Channel::all().set_volume(to_mixer(self.volume.effects));
+        MUSIC_CHANNEL.set_volume(to_mixer(self.volume.music));
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        if self.sounds.take().is_some() {
+            mixer::close_audio();
+        }
+    }
+}
+
+// plays the notes one after the other, each one fading out so they don't click
+fn synthesize(notes: &[(f32, u32)], wave: Wave, sample_rate: i32, channels: i32) -> Box<[i16]> {
+    let mut samples = vec![];
+    // a tiny xorshift is plenty for noise
+    let mut noise_state: u32 = 0x1234_5678;
+
+    for (frequency, duration_ms) in notes {
+        let length = (sample_rate as u32 * duration_ms / 1000) as usize;
+        for index in 0..length {
+            let time = index as f32 / sample_rate as f32;
+            let phase = (time * frequency).fract();
+            let value = match wave {
+                Wave::Square => {
+                    if phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Wave::Triangle => (phase * 2.0 * PI).sin().asin() * 2.0 / PI,
+                Wave::Noise => {
+                    noise_state ^= noise_state << 13;
+                    noise_state ^= noise_state >> 17;
+                    noise_state ^= noise_state << 5;
+                    noise_state as f32 / u32::MAX as f32 * 2.0 - 1.0
+                }
+            };
+            let envelope = 1.0 - index as f32 / length as f32;
+            let sample = (value * envelope * 0.3 * i16::MAX as f32) as i16;
+            for _ in 0..channels {
+                samples.push(sample);
+            }
+        }
+    }
+    samples.into_boxed_slice()
+}
@@ -0,0 +1,298 @@
+// runs lots of games with a bot and no window and sums up how it did, used to compare bot changes
+use clap::{command, Arg};
+use snake_game_sdl::bot::ExternalController;
+use snake_game_sdl::sim::{self, Ending, Outcome, Player};
+use snake_game_sdl::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+struct Config {
+    width: u32,
+    height: u32,
+    border: bool,
+    obstacles: u32,
+}
+
+struct Summary {
+    config: Config,
+    outcomes: Vec<Outcome>,
+}
+
+impl Summary {
+    fn win_rate(&self) -> f64 {
+        self.count(Ending::Won) as f64 / self.outcomes.len() as f64 * 100.0
+    }
+
+    fn count(&self, ending: Ending) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.ending == ending)
+            .count()
+    }
+
+    // the games that were played, a board the bot couldn't play has no score to speak of and
+    // only shows up in its own column
+    fn played(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.ending != Ending::Unplayable)
+    }
+
+    // none when no game could be played
+    fn mean_score(&self) -> Option<f64> {
+        let scores: Vec<f64> = self.played().map(|outcome| outcome.score as f64).collect();
+        (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+
+    fn mean_ticks(&self) -> Option<f64> {
+        let ticks: Vec<f64> = self.played().map(|outcome| outcome.ticks as f64).collect();
+        (!ticks.is_empty()).then(|| ticks.iter().sum::<f64>() / ticks.len() as f64)
+    }
+
+    // the nearest rank percentile
+    fn score_percentile(&self, percent: usize) -> Option<usize> {
+        let mut scores: Vec<usize> = self.played().map(|outcome| outcome.score).collect();
+        scores.sort_unstable();
+        let rank = (percent * scores.len()).div_ceil(100).max(1);
+        scores.get(rank - 1).copied()
+    }
+}
+
+const PERCENTILES: [usize; 3] = [10, 50, 90];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matched_args = command!()
+        .name("snake-sim")
+        .about("Plays lots of snake games with a bot without a window and reports how it did")
+        .arg(
+            Arg::new("ai")
+                .long("ai")
+                .help("Sets the bot that plays.")
+                .required_unless_present("bot")
+                .value_parser(ai::NAMES),
+        )
+        .arg(
+            Arg::new("bot")
+                .long("bot")
+                .help("Lets an outside program play, one process per game, see the bot protocol.")
+                .conflicts_with("ai"),
+        )
+        .arg(
+            Arg::new("bot-time-limit")
+                .long("bot-time-limit")
+                .help("Sets the milliseconds a bot has to answer before it gets disqualified.")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u32).range(1..=60000)),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .help("Sets the number of games played on every board setup.")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Sets the seed of the first game, the others count up from it.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("size")
+                .long("size")
+                .help("Sets the board sizes to play on as WIDTHxHEIGHT, separated by commas.")
+                .default_value("21x21")
+                .value_delimiter(',')
+                .value_parser(sim::parse_size),
+        )
+        .arg(
+            Arg::new("border")
+                .long("border")
+                .help("Sets whether the boards have a border, both can be given as true,false.")
+                .default_value("false")
+                .value_delimiter(',')
+                .value_parser(clap::value_parser!(bool)),
+        )
+        .arg(
+            Arg::new("obstacles")
+                .long("obstacles")
+                .help("Sets the numbers of obstacles to play with, separated by commas.")
+                .default_value("0")
+                .value_delimiter(',')
+                .value_parser(clap::value_parser!(u32).range(0..=100)),
+        )
+        .arg(
+            Arg::new("max-ticks")
+                .long("max-ticks")
+                .help("Ends games that take longer than this many ticks, 0 lets them run forever.")
+                .default_value("100000")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("Sets the number of games played at the same time, defaults to the number of cores.")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Sets how the results are printed.")
+                .default_value("table")
+                .value_parser(["table", "csv"]),
+        )
+        .get_matches();
+
+    let player = match matched_args.get_one::<String>("bot") {
+        Some(command) => {
+            let time_limit = *matched_args.get_one::<u32>("bot-time-limit").unwrap();
+            // a typo in the command should fail once here and not show up as lost games
+            ExternalController::spawn(command, Duration::ZERO)?;
+            Player::Bot(command.clone(), Duration::from_millis(time_limit as u64))
+        }
+        None => Player::Ai(matched_args.get_one::<String>("ai").unwrap().clone()),
+    };
+    let games = *matched_args.get_one::<u64>("games").unwrap();
+    let first_seed = *matched_args.get_one::<u64>("seed").unwrap();
+    let max_ticks = *matched_args.get_one::<u64>("max-ticks").unwrap();
+    let threads = matched_args
+        .get_one::<u32>("threads")
+        .map(|threads| *threads as usize)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |cores| cores.get()));
+
+    let mut configs = vec![];
+    for (width, height) in matched_args.get_many::<(u32, u32)>("size").unwrap() {
+        for border in matched_args.get_many::<bool>("border").unwrap() {
+            for obstacles in matched_args.get_many::<u32>("obstacles").unwrap() {
+                configs.push(Config {
+                    width: *width,
+                    height: *height,
+                    border: *border,
+                    obstacles: *obstacles,
+                });
+            }
+        }
+    }
+
+    // every game is a job, the threads take the next one until none are left
+    let jobs: Vec<(usize, u64)> = (0..configs.len())
+        .flat_map(|config| (0..games).map(move |game| (config, first_seed + game)))
+        .collect();
+    let next_job = AtomicUsize::new(0);
+    let results: Mutex<HashMap<usize, Vec<Outcome>>> = Mutex::new(HashMap::new());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(jobs.len()) {
+            scope.spawn(|| {
+                while let Some((config_index, seed)) =
+                    jobs.get(next_job.fetch_add(1, Ordering::Relaxed))
+                {
+                    let outcome = play_one(&configs[*config_index], *seed, &player, max_ticks);
+                    results
+                        .lock()
+                        .unwrap()
+                        .entry(*config_index)
+                        .or_default()
+                        .push(outcome);
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    let summaries: Vec<Summary> = configs
+        .iter()
+        .enumerate()
+        .map(|(index, config)| Summary {
+            config: *config,
+            outcomes: results.remove(&index).unwrap_or_default(),
+        })
+        .collect();
+
+    if matched_args.get_one::<String>("format").unwrap() == "csv" {
+        print_csv(player.name(), &summaries);
+    } else {
+        print_table(player.name(), &summaries);
+    }
+    Ok(())
+}
+
+fn play_one(config: &Config, seed: u64, player: &Player, max_ticks: u64) -> Outcome {
+    let mut game = SnakeGame::from_seed(
+        config.width,
+        config.height,
+        config.border,
+        (config.obstacles > 0, config.obstacles),
+        seed,
+    );
+    match player.controller(&game.field) {
+        Ok(mut controller) => sim::play(&mut game, controller.as_mut(), max_ticks),
+        Err(_) => sim::unplayable(),
+    }
+}
+
+fn header() -> Vec<String> {
+    let mut columns: Vec<String> = ["ai", "size", "border", "obstacles", "games", "win %"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+    columns.push("mean score".to_string());
+    columns.extend(
+        PERCENTILES
+            .iter()
+            .map(|percent| format!("p{} score", percent)),
+    );
+    columns.push("mean ticks".to_string());
+    // winning already has its own column
+    columns.extend(Ending::ALL[1..].iter().map(|ending| ending.to_string()));
+    columns
+}
+
+fn row(ai_name: &str, summary: &Summary) -> Vec<String> {
+    let config = &summary.config;
+    let mut columns = vec![
+        ai_name.to_string(),
+        format!("{}x{}", config.width, config.height),
+        config.border.to_string(),
+        config.obstacles.to_string(),
+        summary.outcomes.len().to_string(),
+        format!("{:.1}", summary.win_rate()),
+        summary
+            .mean_score()
+            .map_or("-".to_string(), |mean| format!("{:.1}", mean)),
+    ];
+    columns.extend(PERCENTILES.iter().map(|percent| {
+        summary
+            .score_percentile(*percent)
+            .map_or("-".to_string(), |score| score.to_string())
+    }));
+    columns.push(
+        summary
+            .mean_ticks()
+            .map_or("-".to_string(), |mean| format!("{:.0}", mean)),
+    );
+    columns.extend(
+        Ending::ALL[1..]
+            .iter()
+            .map(|ending| summary.count(*ending).to_string()),
+    );
+    columns
+}
+
+fn print_csv(ai_name: &str, summaries: &[Summary]) {
+    println!("{}", sim::csv_line(&header()));
+    for summary in summaries {
+        println!("{}", sim::csv_line(&row(ai_name, summary)));
+    }
+}
+
+fn print_table(ai_name: &str, summaries: &[Summary]) {
+    let mut lines = vec![header()];
+    lines.extend(summaries.iter().map(|summary| row(ai_name, summary)));
+    print!("{}", sim::format_table(&lines));
+}
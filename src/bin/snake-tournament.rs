@@ -0,0 +1,440 @@
+// lets bots play against each other in a round robin or swiss tournament and ranks them
+use clap::{command, Arg, ArgAction};
+use snake_game_sdl::bot::ExternalController;
+use snake_game_sdl::net::Broadcaster;
+use snake_game_sdl::replay::Replay;
+use snake_game_sdl::sim::{self, Player};
+use snake_game_sdl::tournament::{self, Arena, GameResult, Mode, PlayedGame, Standing};
+use snake_game_sdl::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+struct Entrant {
+    name: String,
+    player: Player,
+}
+
+// how the games of a tournament are played, the same for every match
+struct Rules {
+    mode: Mode,
+    arena: Arena,
+    games: u64,
+    first_seed: u64,
+    threads: usize,
+}
+
+// a bot from the command line, given as name=command
+fn parse_bot(value: &str) -> Result<(String, String), String> {
+    let (name, command) = value
+        .split_once('=')
+        .ok_or(format!("\"{}\" is not a bot like name=command", value))?;
+    if name.is_empty() || command.trim().is_empty() {
+        return Err(format!("\"{}\" needs both a name and a command", value));
+    }
+    Ok((name.to_string(), command.to_string()))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matched_args = command!()
+        .name("snake-tournament")
+        .about("Lets snake bots play against each other and ranks them with elo and trueskill")
+        .arg(
+            Arg::new("ais")
+                .long("ais")
+                .help("Sets the built in bots that take part, separated by commas.")
+                .default_value("greedy,astar,safe,hamiltonian")
+                .value_delimiter(',')
+                .value_parser(ai::NAMES),
+        )
+        .arg(
+            Arg::new("bot")
+                .long("bot")
+                .help("Adds an outside bot as name=command, can be given more than once.")
+                .action(ArgAction::Append)
+                .value_parser(parse_bot),
+        )
+        .arg(
+            Arg::new("bot-time-limit")
+                .long("bot-time-limit")
+                .help("Sets the milliseconds a bot has to answer before it gets disqualified.")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u32).range(1..=60000)),
+        )
+        .arg(
+            Arg::new("mode")
+                .long("mode")
+                .help("Sets how a game is played: arena puts both bots on one board, solo lets each play the same board alone.")
+                .default_value("arena")
+                .value_parser(["arena", "solo"]),
+        )
+        .arg(
+            Arg::new("bracket")
+                .long("bracket")
+                .help("Sets who plays whom: everyone against everyone or swiss rounds between bots with the same points.")
+                .default_value("round-robin")
+                .value_parser(["round-robin", "swiss"]),
+        )
+        .arg(
+            Arg::new("rounds")
+                .long("rounds")
+                .help("Sets the number of swiss rounds, defaults to enough to find a clear winner.")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .help("Sets the number of games every match has, each on its own seed.")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Sets the seed of the first game of every match, the others count up from it.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("size")
+                .long("size")
+                .help("Sets the board size as WIDTHxHEIGHT.")
+                .default_value("21x21")
+                .value_parser(sim::parse_size),
+        )
+        .arg(
+            Arg::new("border")
+                .long("border")
+                .help("Sets whether the board has a border.")
+                .default_value("false")
+                .value_parser(clap::value_parser!(bool)),
+        )
+        .arg(
+            Arg::new("obstacles")
+                .long("obstacles")
+                .help("Sets the number of obstacles.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=100)),
+        )
+        .arg(
+            Arg::new("max-ticks")
+                .long("max-ticks")
+                .help("Ends games that take longer than this many ticks, the longer snake wins those.")
+                .default_value("10000")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("Sets the number of games played at the same time, defaults to the number of cores.")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .help("Sets the directory the leaderboard and the replays of every game are written to.")
+                .default_value("tournament")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("broadcast")
+                .long("broadcast")
+                .help("Streams every game to spectators on this port (snake_game_sdl --watch), one game at a time.")
+                .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+            Arg::new("tickspeed")
+                .long("tickspeed")
+                .help("Sets the milliseconds between ticks while broadcasting so the games can be followed.")
+                .default_value("50")
+                .value_parser(clap::value_parser!(u32).range(0..=1000)),
+        )
+        .get_matches();
+
+    let time_limit =
+        Duration::from_millis(*matched_args.get_one::<u32>("bot-time-limit").unwrap() as u64);
+    let mut entrants: Vec<Entrant> = matched_args
+        .get_many::<String>("ais")
+        .unwrap()
+        .map(|name| Entrant {
+            name: name.clone(),
+            player: Player::Ai(name.clone()),
+        })
+        .collect();
+    for (name, command) in matched_args
+        .get_many::<(String, String)>("bot")
+        .into_iter()
+        .flatten()
+    {
+        // a typo in the command should fail once here and not show up as lost games
+        ExternalController::spawn(command, Duration::ZERO)?;
+        entrants.push(Entrant {
+            name: name.clone(),
+            player: Player::Bot(command.clone(), time_limit),
+        });
+    }
+    for (index, entrant) in entrants.iter().enumerate() {
+        if entrants[..index]
+            .iter()
+            .any(|other| other.name == entrant.name)
+        {
+            return Err(format!("\"{}\" takes part twice", entrant.name).into());
+        }
+    }
+    if entrants.len() < 2 {
+        return Err("A tournament needs at least two bots".into());
+    }
+
+    let (width, height) = *matched_args.get_one::<(u32, u32)>("size").unwrap();
+    let rules = Rules {
+        mode: match matched_args.get_one::<String>("mode").unwrap().as_str() {
+            "solo" => Mode::Solo,
+            _ => Mode::Arena,
+        },
+        arena: Arena {
+            width,
+            height,
+            border: *matched_args.get_one::<bool>("border").unwrap(),
+            obstacles: *matched_args.get_one::<u32>("obstacles").unwrap(),
+            max_ticks: *matched_args.get_one::<u64>("max-ticks").unwrap(),
+        },
+        games: *matched_args.get_one::<u64>("games").unwrap(),
+        first_seed: *matched_args.get_one::<u64>("seed").unwrap(),
+        threads: matched_args
+            .get_one::<u32>("threads")
+            .map(|threads| *threads as usize)
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |cores| cores.get())),
+    };
+    let mut broadcast = match matched_args.get_one::<u16>("broadcast") {
+        Some(port) => {
+            let tick =
+                Duration::from_millis(*matched_args.get_one::<u32>("tickspeed").unwrap() as u64);
+            println!(
+                "Broadcasting on port {}, watch with --watch <this computer>:{}",
+                port, port
+            );
+            Some((Broadcaster::bind(*port)?, tick))
+        }
+        None => None,
+    };
+
+    let out = matched_args.get_one::<PathBuf>("out").unwrap();
+    let replays = out.join("replays");
+    fs::create_dir_all(&replays)
+        .map_err(|e| format!("Failed to create {}: {}", replays.display(), e))?;
+
+    let mut standings = vec![Standing::default(); entrants.len()];
+    let swiss = matched_args.get_one::<String>("bracket").unwrap() == "swiss";
+    let rounds = match (swiss, matched_args.get_one::<u32>("rounds")) {
+        (true, Some(rounds)) => *rounds as usize,
+        // enough rounds that only one can win them all
+        (true, None) => (entrants.len() as f64).log2().ceil() as usize,
+        (false, _) => entrants.len() - 1 + entrants.len() % 2,
+    };
+    let round_robin = tournament::round_robin(entrants.len());
+
+    for round in 0..rounds {
+        let (pairs, bye) = match swiss {
+            true => tournament::swiss_round(&standings),
+            false => (round_robin.get(round).cloned().unwrap_or_default(), None),
+        };
+        println!("Round {}", round + 1);
+        if let Some(bye) = bye {
+            // a bye counts as a won match
+            standings[bye].points += 1.0;
+            standings[bye].had_bye = true;
+            println!("  {} sits this round out", entrants[bye].name);
+        }
+        let results = play_round(&entrants, &pairs, &rules, broadcast.as_mut());
+        for ((first, second), games) in pairs.iter().zip(results) {
+            let mut points = (0.0, 0.0);
+            for (index, played) in games.iter().enumerate() {
+                let seed = rules.first_seed + index as u64;
+                save_replays(
+                    &replays,
+                    round,
+                    (&entrants[*first], &entrants[*second]),
+                    seed,
+                    played,
+                );
+                record(&mut standings, (*first, *second), played.result);
+                points.0 += played.result.score();
+                points.1 += 1.0 - played.result.score();
+            }
+            // winning more games wins the match
+            let match_points = if points.0 > points.1 {
+                1.0
+            } else if points.0 < points.1 {
+                0.0
+            } else {
+                0.5
+            };
+            standings[*first].points += match_points;
+            standings[*second].points += 1.0 - match_points;
+            standings[*first].opponents.push(*second);
+            standings[*second].opponents.push(*first);
+            println!(
+                "  {} {} - {} {}",
+                entrants[*first].name, points.0, points.1, entrants[*second].name
+            );
+        }
+    }
+
+    let leaderboard = leaderboard(&entrants, &standings);
+    println!();
+    print!("{}", sim::format_table(&leaderboard));
+    let path = out.join("leaderboard.csv");
+    let csv: Vec<String> = leaderboard.iter().map(|line| sim::csv_line(line)).collect();
+    fs::write(&path, csv.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("\nThe leaderboard and the replays are in {}", out.display());
+    Ok(())
+}
+
+// every game of every pairing, in the order of the pairings and then the seeds. the games are
+// spread over the threads unless they are broadcast, those are played one at a time
+fn play_round(
+    entrants: &[Entrant],
+    pairs: &[(usize, usize)],
+    rules: &Rules,
+    broadcast: Option<&mut (Broadcaster, Duration)>,
+) -> Vec<Vec<PlayedGame>> {
+    let jobs: Vec<(usize, u64)> = (0..pairs.len())
+        .flat_map(|pair| (0..rules.games).map(move |game| (pair, rules.first_seed + game)))
+        .collect();
+    let play = |(pair, seed): (usize, u64), on_tick: &mut dyn FnMut(&SnakeGame)| {
+        let (first, second) = pairs[pair];
+        tournament::play_game(
+            rules.mode,
+            &rules.arena,
+            (&entrants[first].player, &entrants[second].player),
+            seed,
+            on_tick,
+        )
+    };
+
+    let mut played: Vec<Option<PlayedGame>> = (0..jobs.len()).map(|_| None).collect();
+    if let Some((broadcaster, tick)) = broadcast {
+        for (index, job) in jobs.iter().enumerate() {
+            played[index] = Some(play(*job, &mut |game| {
+                broadcaster.publish(game);
+                thread::sleep(*tick);
+            }));
+        }
+    } else {
+        let next_job = AtomicUsize::new(0);
+        let results = Mutex::new(&mut played);
+        thread::scope(|scope| {
+            for _ in 0..rules.threads.min(jobs.len()) {
+                scope.spawn(|| {
+                    let mut index = next_job.fetch_add(1, Ordering::Relaxed);
+                    while let Some(job) = jobs.get(index) {
+                        let game = play(*job, &mut |_| {});
+                        results.lock().unwrap()[index] = Some(game);
+                        index = next_job.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+    }
+
+    let mut played = played.into_iter().map(|game| game.unwrap());
+    pairs
+        .iter()
+        .map(|_| played.by_ref().take(rules.games as usize).collect())
+        .collect()
+}
+
+fn record(standings: &mut [Standing], (first, second): (usize, usize), result: GameResult) {
+    let (mut first_rating, mut second_rating) = (standings[first].rating, standings[second].rating);
+    tournament::rate(&mut first_rating, &mut second_rating, result);
+    standings[first].rating = first_rating;
+    standings[second].rating = second_rating;
+    let (winner, loser) = match result {
+        GameResult::FirstWon => (first, second),
+        GameResult::SecondWon => (second, first),
+        GameResult::Draw => {
+            standings[first].draws += 1;
+            standings[second].draws += 1;
+            return;
+        }
+    };
+    standings[winner].wins += 1;
+    standings[loser].losses += 1;
+}
+
+// names made safe for file names, outside bots can be called anything
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn save_replays(
+    directory: &Path,
+    round: usize,
+    (first, second): (&Entrant, &Entrant),
+    seed: u64,
+    played: &PlayedGame,
+) {
+    let base = format!(
+        "round-{}-{}-vs-{}-seed-{}",
+        round + 1,
+        file_name(&first.name),
+        file_name(&second.name),
+        seed
+    );
+    // the solo games are one per bot
+    let names: Vec<String> = match played.games.len() {
+        1 => vec![base],
+        _ => [first, second]
+            .iter()
+            .map(|entrant| format!("{}-{}", base, file_name(&entrant.name)))
+            .collect(),
+    };
+    for (game, name) in played.games.iter().zip(names) {
+        let path = directory.join(format!("{}.replay", name));
+        if let Err(e) = Replay::from_game(game).save(&path) {
+            eprintln!("Failed to save the replay: {}", e);
+        }
+    }
+}
+
+fn leaderboard(entrants: &[Entrant], standings: &[Standing]) -> Vec<Vec<String>> {
+    let mut lines = vec![[
+        "rank",
+        "bot",
+        "games",
+        "wins",
+        "draws",
+        "losses",
+        "points",
+        "elo",
+        "trueskill",
+        "mu",
+        "sigma",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect()];
+    for (rank, entrant) in tournament::ranking(standings).iter().enumerate() {
+        let standing = &standings[*entrant];
+        lines.push(vec![
+            (rank + 1).to_string(),
+            entrants[*entrant].name.clone(),
+            standing.games().to_string(),
+            standing.wins.to_string(),
+            standing.draws.to_string(),
+            standing.losses.to_string(),
+            standing.points.to_string(),
+            format!("{:.0}", standing.rating.elo),
+            format!("{:.1}", standing.rating.conservative()),
+            format!("{:.1}", standing.rating.mu),
+            format!("{:.2}", standing.rating.sigma),
+        ]);
+    }
+    lines
+}
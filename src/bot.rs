@@ -0,0 +1,201 @@
+// bots written in any language, running as their own process and talking json over stdin/stdout
+//
+// every message is one line of json. the game sends once at the start:
+//   {"type":"start","width":21,"height":21,"border":false,
+//    "edges":{"top":"wall","right":"wrap","bottom":"wall","left":"wrap"},"portals":[[[3,4],[17,9]]],
+//    "time_limit_ms":100,"moves":{"UP":[0,-1],"DOWN":[0,1],"LEFT":[1,0],"RIGHT":[-1,0]}}
+// edges are wrap, wall (running off the field crashes) or bounce (the snake turns along it) and a
+// head moving onto one end of a portal comes out of the other. then every tick while the game runs:
+//   {"type":"tick","tick":12,"snake":[[7,10],[6,10]],"heading":"LEFT","apple":[14,10],
+//    "apples":[[14,10,"normal"],[3,9,"poison"]],"obstacles":[[3,4]],"others":[[[7,5],[6,5]]]}
+// the snakes go from the head to the tail, others holds the snakes of the other players. apple is
+// the closest one worth eating and apples all of them with their kind: normal, golden, poison or
+// rotten. obstacles are the ones blocking right now, patrols and closed gates included, they can
+// be somewhere else on the next tick. the bot has to answer every tick within the time limit with {"direction":"UP"} or
+// {"direction":null} to keep going straight, anything else or no answer in time disqualifies it
+use crate::controller::{Controller, GameView};
+use crate::model::{Direction, Snake};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// starting an interpreter can take a while so the first answer gets extra time
+const STARTUP_TIME: Duration = Duration::from_secs(2);
+
+pub struct ExternalController {
+    child: Child,
+    stdin: ChildStdin,
+    // the lines the bot printed, read on another thread so waiting for them can time out
+    replies: Receiver<String>,
+    time_limit: Duration,
+    started: bool,
+    disqualified: Option<String>,
+}
+
+impl ExternalController {
+    // the command is split on whitespace, the first part is the program
+    pub fn spawn(command: &str, time_limit: Duration) -> Result<Self, String> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or("The bot command is empty")?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // whatever the bot prints to stderr shows up in our terminal to help debugging it
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("Failed to start the bot \"{}\": {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("The bot has no stdin")?;
+        let stdout = child.stdout.take().ok_or("The bot has no stdout")?;
+
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            replies,
+            time_limit,
+            started: false,
+            disqualified: None,
+        })
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        writeln!(self.stdin, "{}", message)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("the bot stopped listening: {}", e))
+    }
+
+    fn receive(&mut self, time_limit: Duration) -> Result<Option<Direction>, String> {
+        let line = self.replies.recv_timeout(time_limit).map_err(|e| match e {
+            RecvTimeoutError::Timeout => format!(
+                "the bot took longer than {} ms to answer",
+                time_limit.as_millis()
+            ),
+            RecvTimeoutError::Disconnected => "the bot quit".to_string(),
+        })?;
+        let reply: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("the bot answered \"{}\" which is no json: {}", line, e))?;
+        match reply.get("direction") {
+            Some(Value::Null) => Ok(None),
+            Some(Value::String(direction)) => direction.parse().map(Some),
+            _ => Err(format!(
+                "the bot answered \"{}\" without a direction or null",
+                line
+            )),
+        }
+    }
+
+    fn play_tick(&mut self, view: &GameView) -> Result<Option<Direction>, String> {
+        let mut time_limit = self.time_limit;
+        if !self.started {
+            self.started = true;
+            time_limit += STARTUP_TIME;
+            let portals: Vec<[[u32; 2]; 2]> = view
+                .field
+                .portals
+                .iter()
+                .map(|(first, second)| [[first.0, first.1], [second.0, second.1]])
+                .collect();
+            self.send(&json!({
+                "type": "start",
+                "width": view.field.size.0,
+                "height": view.field.size.1,
+                "border": view.field.has_border(),
+                "edges": {
+                    "top": view.field.edges.top.to_string(),
+                    "right": view.field.edges.right.to_string(),
+                    "bottom": view.field.edges.bottom.to_string(),
+                    "left": view.field.edges.left.to_string(),
+                },
+                "portals": portals,
+                "time_limit_ms": self.time_limit.as_millis() as u64,
+                "moves": {
+                    "UP": [0, -1],
+                    "DOWN": [0, 1],
+                    "LEFT": [1, 0],
+                    "RIGHT": [-1, 0],
+                },
+            }))?;
+        }
+
+        // answers to ticks that already timed out would be taken for this tick otherwise
+        while self.replies.try_recv().is_ok() {}
+
+        let positions = |snake: &Snake| -> Vec<[u32; 2]> {
+            snake
+                .body
+                .iter()
+                .map(|body_part| {
+                    let position = body_part.get().position;
+                    [position.0, position.1]
+                })
+                .collect()
+        };
+        let others: Vec<Vec<[u32; 2]>> = view.others.iter().map(|other| positions(other)).collect();
+        let obstacles: Vec<[u32; 2]> = view
+            .field
+            .obstacles
+            .iter()
+            .flatten()
+            .map(|obstacle_part| [obstacle_part.0, obstacle_part.1])
+            .collect();
+        let apples: Vec<Value> = view
+            .apples
+            .iter()
+            .map(|apple| json!([apple.position.0, apple.position.1, apple.kind.name()]))
+            .collect();
+        self.send(&json!({
+            "type": "tick",
+            "tick": view.ticks,
+            "snake": positions(view.snake),
+            "heading": view.snake.head_direction().to_string(),
+            "apple": [view.apple.position.0, view.apple.position.1],
+            "apples": apples,
+            "obstacles": obstacles,
+            "others": others,
+        }))?;
+        self.receive(time_limit)
+    }
+}
+
+impl Controller for ExternalController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        // only running games are sent so pausing doesn't count against the time limit
+        if self.disqualified.is_some() || view.state != crate::GameState::Running {
+            return None;
+        }
+        match self.play_tick(view) {
+            Ok(direction) => direction,
+            Err(reason) => {
+                self.disqualified = Some(reason);
+                let _ = self.child.kill();
+                None
+            }
+        }
+    }
+
+    fn disqualified(&self) -> Option<&str> {
+        self.disqualified.as_deref()
+    }
+}
+
+impl Drop for ExternalController {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,108 @@
+// a camera that follows the snake head when the field is bigger than what fits on screen
+// everything here is in grid cells, the frontends decide how big a cell is
+
+pub struct Camera {
+    // the top left cell that is currently visible
+    pub origin: (u32, u32),
+    // how many cells are visible on each axis
+    pub view: (u32, u32),
+    field: (u32, u32),
+    // the percentage of the view (centered) where the head can move without scrolling
+    dead_zone: u32,
+}
+
+impl Camera {
+    pub fn from(field: (u32, u32), view: (u32, u32), dead_zone: u32) -> Self {
+        Self {
+            origin: (0, 0),
+            view: (view.0.min(field.0), view.1.min(field.1)),
+            field,
+            dead_zone: dead_zone.min(100),
+        }
+    }
+
+    pub fn field(&self) -> (u32, u32) {
+        self.field
+    }
+
+    // true when the whole field can't be shown at once
+    pub fn is_scrolling(&self) -> bool {
+        self.view.0 < self.field.0 || self.view.1 < self.field.1
+    }
+
+    // used when zooming in and out, the origin is kept around the same center
+    pub fn resize(&mut self, view: (u32, u32)) {
+        let center = (
+            self.origin.0 + self.view.0 / 2,
+            self.origin.1 + self.view.1 / 2,
+        );
+        self.view = (view.0.min(self.field.0), view.1.min(self.field.1));
+        self.center_on(center);
+    }
+
+    pub fn center_on(&mut self, position: (u32, u32)) {
+        self.origin = (
+            clamp_axis(
+                position.0 as i64 - (self.view.0 / 2) as i64,
+                self.view.0,
+                self.field.0,
+            ),
+            clamp_axis(
+                position.1 as i64 - (self.view.1 / 2) as i64,
+                self.view.1,
+                self.field.1,
+            ),
+        );
+    }
+
+    // scroll only as much as needed to bring the head back inside the dead zone
+    pub fn follow(&mut self, head: (u32, u32)) {
+        self.origin = (
+            follow_axis(
+                self.origin.0,
+                head.0,
+                self.view.0,
+                self.field.0,
+                self.dead_zone,
+            ),
+            follow_axis(
+                self.origin.1,
+                head.1,
+                self.view.1,
+                self.field.1,
+                self.dead_zone,
+            ),
+        );
+    }
+
+    // the position of a cell relative to the view, none if it is off screen
+    pub fn to_view(&self, position: (u32, u32)) -> Option<(u32, u32)> {
+        if position.0 < self.origin.0
+            || position.1 < self.origin.1
+            || position.0 >= self.origin.0 + self.view.0
+            || position.1 >= self.origin.1 + self.view.1
+        {
+            return None;
+        }
+        Some((position.0 - self.origin.0, position.1 - self.origin.1))
+    }
+}
+
+fn clamp_axis(origin: i64, view: u32, field: u32) -> u32 {
+    origin.clamp(0, (field - view) as i64) as u32
+}
+
+fn follow_axis(origin: u32, head: u32, view: u32, field: u32, dead_zone: u32) -> u32 {
+    let zone = view * dead_zone / 100;
+    let zone_start = origin as i64 + ((view - zone) / 2) as i64;
+    let zone_end = zone_start + zone as i64;
+    let head = head as i64;
+
+    let mut new_origin = origin as i64;
+    if head < zone_start {
+        new_origin -= zone_start - head;
+    } else if head > zone_end {
+        new_origin += head - zone_end;
+    }
+    clamp_axis(new_origin, view, field)
+}
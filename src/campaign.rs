@@ -0,0 +1,296 @@
+// the campaign: handmade levels played one after the other, winning one unlocks the next
+//
+// a level is a text file in levels/ with its settings first and the board after them:
+//   name The box
+//   goal eat 10
+//   #####
+//   #.S.#
+//   #####
+// the goal is eat <apples>, survive <seconds> or exit. the other settings are tick <milliseconds>,
+// apples <count>, edges <edges>, patrols <count> and gates <count>. on the board # is an
+// obstacle, S is where the head of the snake starts with its tail to the left of it and E is the
+// exit
+use crate::mode::Mode;
+use crate::model::Edges;
+use crate::SnakeGame;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// how fast a level is played unless it says otherwise
+const DEFAULT_TICK: u32 = 150;
+
+const BUNDLED: [(&str, &str); 8] = [
+    ("first-steps", include_str!("../levels/01-first-steps.txt")),
+    ("the-box", include_str!("../levels/02-the-box.txt")),
+    ("pillars", include_str!("../levels/03-pillars.txt")),
+    ("hold-on", include_str!("../levels/04-hold-on.txt")),
+    ("the-maze", include_str!("../levels/05-the-maze.txt")),
+    ("corridors", include_str!("../levels/06-corridors.txt")),
+    ("crossroads", include_str!("../levels/07-crossroads.txt")),
+    ("last-stand", include_str!("../levels/08-last-stand.txt")),
+];
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    // what the progress and the replays know it by
+    pub id: String,
+    pub name: String,
+    pub goal: Mode,
+    // in milliseconds
+    pub tick: u32,
+    pub size: (u32, u32),
+    obstacles: Vec<(u32, u32)>,
+    start: (u32, u32),
+    apples: u32,
+    edges: Edges,
+    patrols: u32,
+    gates: u32,
+}
+
+impl Level {
+    pub fn parse(id: &str, text: &str) -> Result<Self, String> {
+        let mut name = id.to_string();
+        let mut goal = None;
+        let mut tick = DEFAULT_TICK;
+        let mut apples = 1;
+        let mut edges = Edges::TORUS;
+        let mut patrols = 0;
+        let mut gates = 0;
+        let mut rows: Vec<&str> = vec![];
+        let number = |value: &str| {
+            value
+                .parse::<u32>()
+                .map_err(|e| format!("level {}: \"{}\" is no number: {}", id, value, e))
+        };
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let is_row = line.chars().all(|c| matches!(c, '#' | '.' | 'S' | 'E'));
+            if is_row {
+                rows.push(line);
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "name" => name = value.to_string(),
+                "goal" => goal = Some(value.to_string()),
+                "tick" => tick = number(value)?.max(1),
+                "apples" => apples = number(value)?.max(1),
+                "edges" => edges = value.parse()?,
+                "patrols" => patrols = number(value)?,
+                "gates" => gates = number(value)?,
+                _ => return Err(format!("level {}: unknown setting \"{}\"", id, line)),
+            }
+        }
+
+        let height = rows.len() as u32;
+        let width = rows.first().map_or(0, |row| row.len() as u32);
+        if width < 3 || height < 3 || rows.iter().any(|row| row.len() as u32 != width) {
+            return Err(format!(
+                "level {}: the board needs rows of the same length",
+                id
+            ));
+        }
+        let cells = |wanted: char| -> Vec<(u32, u32)> {
+            rows.iter()
+                .enumerate()
+                .flat_map(|(y, row)| {
+                    row.chars()
+                        .enumerate()
+                        .filter(move |(_, c)| *c == wanted)
+                        .map(move |(x, _)| (x as u32, y as u32))
+                })
+                .collect()
+        };
+        let obstacles = cells('#');
+        let start = match cells('S')[..] {
+            [start] if start.0 > 0 && !obstacles.contains(&(start.0 - 1, start.1)) => start,
+            [_] => return Err(format!("level {}: the tail needs room left of S", id)),
+            _ => return Err(format!("level {}: the board needs one S", id)),
+        };
+        let goal = match goal
+            .as_deref()
+            .map(|goal| goal.split_once(' ').unwrap_or((goal, "")))
+        {
+            Some(("eat", count)) => Mode::AppleSprint {
+                apples: number(count)?.max(1),
+            },
+            Some(("survive", seconds)) => Mode::TimeAttack {
+                ticks: (number(seconds)? as u64 * 1000 / tick as u64).max(1),
+            },
+            Some(("exit", _)) => match cells('E')[..] {
+                [(x, y)] => Mode::Exit { x, y },
+                _ => return Err(format!("level {}: an exit needs one E", id)),
+            },
+            _ => return Err(format!("level {}: the goal is eat, survive or exit", id)),
+        };
+        Ok(Self {
+            id: id.to_string(),
+            name,
+            goal,
+            tick,
+            size: (width, height),
+            obstacles,
+            start,
+            apples,
+            edges,
+            patrols,
+            gates,
+        })
+    }
+
+    // the mode goes first so nothing is put on the exit
+    pub fn new_game(&self, seed: u64) -> SnakeGame {
+        let mut game = SnakeGame::from_seed(self.size.0, self.size.1, false, (false, 0), seed)
+            .with_mode(self.goal)
+            .with_layout(self.obstacles.clone(), self.start)
+            .with_apples(self.apples as usize, false)
+            .with_edges(self.edges)
+            .with_moving_obstacles(self.patrols as usize, self.gates as usize);
+        game.level = Some(self.id.clone());
+        game
+    }
+
+    // what has to be done, for the level select
+    pub fn describe_goal(&self) -> String {
+        match self.goal {
+            Mode::AppleSprint { apples } => format!("eat {} apples", apples),
+            Mode::TimeAttack { ticks } => {
+                format!("survive {} seconds", ticks * self.tick as u64 / 1000)
+            }
+            Mode::Exit { .. } => "reach the exit".to_string(),
+            Mode::Classic | Mode::Endless => "fill the board".to_string(),
+        }
+    }
+}
+
+// every level that comes with the game, in the order they are played
+pub fn levels() -> Vec<Level> {
+    BUNDLED
+        .iter()
+        .map(|(id, text)| Level::parse(id, text).expect("a bundled level is broken"))
+        .collect()
+}
+
+pub fn level_by_id(id: &str) -> Option<Level> {
+    levels().into_iter().find(|level| level.id == id)
+}
+
+// the levels that were won so far, kept in a text file with one id per line
+pub struct Progress {
+    path: PathBuf,
+    pub completed: Vec<String>,
+}
+
+impl Progress {
+    // nothing was won yet when the file isn't there
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            completed: text
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect(),
+        })
+    }
+
+    pub fn complete(&mut self, level: &Level) -> Result<(), String> {
+        if self.is_completed(level) {
+            return Ok(());
+        }
+        self.completed.push(level.id.clone());
+        let text: String = self
+            .completed
+            .iter()
+            .map(|id| format!("{}\n", id))
+            .collect();
+        fs::write(&self.path, text)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    pub fn is_completed(&self, level: &Level) -> bool {
+        self.completed.contains(&level.id)
+    }
+
+    // the first level is always open, every other one once the one before it was won
+    pub fn is_unlocked(&self, levels: &[Level], index: usize) -> bool {
+        index == 0 || self.is_completed(&levels[index - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: &str = "name The box\ngoal eat 3\ntick 100\napples 2\n#####\n#.S.#\n#...#\n#####\n";
+
+    #[test]
+    fn a_level_reads_its_settings_and_board() {
+        let level = Level::parse("box", BOX).unwrap();
+        assert_eq!(level.name, "The box");
+        assert_eq!(level.goal, Mode::AppleSprint { apples: 3 });
+        assert_eq!(level.tick, 100);
+        assert_eq!(level.size, (5, 4));
+        assert_eq!(level.start, (2, 1));
+        assert_eq!(level.apples, 2);
+        assert_eq!(level.obstacles.len(), 14);
+
+        let survive = Level::parse("long", &BOX.replace("goal eat 3", "goal survive 3")).unwrap();
+        assert_eq!(survive.goal, Mode::TimeAttack { ticks: 30 });
+        let exit = Level::parse(
+            "out",
+            &BOX.replace("goal eat 3", "goal exit")
+                .replace("#...#", "#..E#"),
+        )
+        .unwrap();
+        assert_eq!(exit.goal, Mode::Exit { x: 3, y: 2 });
+    }
+
+    #[test]
+    fn broken_levels_are_refused() {
+        for text in [
+            BOX.replace("goal eat 3", "goal eat lots"),
+            BOX.replace("goal eat 3", "goal win"),
+            BOX.replace("goal eat 3\n", ""),
+            BOX.replace("goal eat 3", "goal exit"),
+            BOX.replace("tick 100", "speed 100"),
+            BOX.replace("#.S.#", "#...#"),
+            BOX.replace("#...#", "#.S.#"),
+            BOX.replace("#.S.#", "#S..#"),
+            BOX.replace("#...#", "#....#"),
+            "name Nothing\ngoal eat 1\n".to_string(),
+        ] {
+            assert!(
+                Level::parse("broken", &text).is_err(),
+                "{:?} was read",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn the_bundled_levels_start_safely() {
+        for level in levels() {
+            let game = level.new_game(0);
+            assert_eq!(game.level.as_deref(), Some(level.id.as_str()));
+            assert_eq!(game.field.size, level.size);
+            let obstacles = game.field.obstacles.clone().unwrap_or_default();
+            for body_part in &game.players[0].snake.body {
+                assert!(
+                    !obstacles.contains(&body_part.get().position),
+                    "{}",
+                    level.id
+                );
+            }
+            for apple in &game.apples {
+                assert!(!obstacles.contains(&apple.position), "{}", level.id);
+                assert_ne!(Some(apple.position), game.exit(), "{}", level.id);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// draws games without a window through sdl's software renderer, used for screenshots and recordings
+use crate::render::{draw_game, dummy_texture, SQUARE_SIZE};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
+use snake_game_sdl::camera::Camera;
+use snake_game_sdl::palette::Palette;
+use snake_game_sdl::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// big boards are drawn with smaller squares so the images stay reasonable
+const MAX_CAPTURE_SIZE: u32 = 1024;
+
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    // rgb, three bytes per pixel without any padding
+    pub pixels: Vec<u8>,
+}
+
+// the whole field is drawn, the camera of the window doesn't matter here
+pub fn render_frame(game: &SnakeGame, palette: &Palette) -> Result<Frame, String> {
+    let (field_width, field_height) = game.field.size;
+    let zoom = (MAX_CAPTURE_SIZE / field_width.max(field_height)).clamp(1, SQUARE_SIZE);
+    let (width, height) = (field_width * zoom, field_height * zoom);
+
+    let surface = Surface::new(width, height, PixelFormatEnum::RGB24)?;
+    let mut canvas = surface.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let sprites = dummy_texture(&mut canvas, &texture_creator, palette)?;
+    let camera = Camera::from(game.field.size, game.field.size, 0);
+
+    draw_game(&mut canvas, &sprites, game, &camera, zoom, false, None);
+
+    Ok(Frame {
+        width,
+        height,
+        pixels: canvas.read_pixels(None, PixelFormatEnum::RGB24)?,
+    })
+}
+
+pub fn save_png(path: &Path, frame: &Frame) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        frame.width,
+        frame.height,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&frame.pixels)?;
+    Ok(())
+}
+
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    // in hundredths of a second like the gif format wants it
+    delay: u16,
+    last_state: Option<GameState>,
+}
+
+impl GifRecorder {
+    pub fn create(
+        path: &Path,
+        game: &SnakeGame,
+        tickspeed: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let first_frame = render_frame(game, &Palette::default())?;
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            first_frame.width as u16,
+            first_frame.height as u16,
+            &[],
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        Ok(Self {
+            encoder,
+            delay: (tickspeed / 10).max(2) as u16,
+            last_state: None,
+        })
+    }
+
+    // called every tick, paused stretches only end up once in the file
+    pub fn record_tick(
+        &mut self,
+        game: &SnakeGame,
+        palette: &Palette,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let changed = self.last_state != Some(game.state);
+        if game.state == GameState::Running || changed {
+            self.add_frame(&render_frame(game, palette)?)?;
+        }
+        self.last_state = Some(game.state);
+        Ok(())
+    }
+
+    pub fn add_frame(&mut self, frame: &Frame) -> Result<(), Box<dyn std::error::Error>> {
+        let mut gif_frame = indexed_frame(frame).unwrap_or_else(|| {
+            gif::Frame::from_rgb_speed(frame.width as u16, frame.height as u16, &frame.pixels, 10)
+        });
+        gif_frame.delay = self.delay;
+        self.encoder.write_frame(&gif_frame)?;
+        Ok(())
+    }
+}
+
+// the game only uses a handful of colors so they are mapped exactly instead of quantized,
+// none is returned when a frame somehow has more colors than a gif palette can hold
+fn indexed_frame(frame: &Frame) -> Option<gif::Frame<'static>> {
+    let mut palette: Vec<u8> = vec![];
+    let mut indices: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut buffer = Vec::with_capacity((frame.width * frame.height) as usize);
+
+    for pixel in frame.pixels.chunks_exact(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match indices.get(&color) {
+            Some(index) => *index,
+            None => {
+                if indices.len() == 256 {
+                    return None;
+                }
+                let index = indices.len() as u8;
+                indices.insert(color, index);
+                palette.extend_from_slice(&color);
+                index
+            }
+        };
+        buffer.push(index);
+    }
+
+    Some(gif::Frame {
+        width: frame.width as u16,
+        height: frame.height as u16,
+        buffer: Cow::Owned(buffer),
+        palette: Some(palette),
+        ..gif::Frame::default()
+    })
+}
@@ -0,0 +1,94 @@
+// anything that can steer a snake: the keyboard, a replay file or a bot
+use crate::model::{Apple, Direction, Field, Snake};
+use crate::GameState;
+use std::collections::VecDeque;
+
+// a read only look at the game, handed to controllers every tick
+pub struct GameView<'a> {
+    pub state: GameState,
+    pub field: &'a Field,
+    // the snake being controlled
+    pub snake: &'a Snake,
+    // the snakes of everyone else still in the game
+    pub others: Vec<&'a Snake>,
+    // the closest apple worth eating, what the bots go for
+    pub apple: &'a Apple,
+    // every apple on the field, golden, poison and rotten ones too
+    pub apples: &'a [Apple],
+    pub ticks: u64,
+}
+
+pub trait Controller {
+    // called once per tick before the snake moves, none keeps the current direction
+    fn decide(&mut self, view: &GameView) -> Option<Direction>;
+
+    // why the controller can't go on playing, the game ends as soon as this is set
+    fn disqualified(&self) -> Option<&str> {
+        None
+    }
+}
+
+// the frontends push the keys in here as they come,
+// one turn is handed out per tick so quick presses can't make the snake reverse into itself
+#[derive(Default)]
+pub struct HumanController {
+    pending_turns: VecDeque<Direction>,
+}
+
+impl HumanController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, direction: Direction) {
+        self.pending_turns.push_back(direction);
+    }
+
+    pub fn clear(&mut self) {
+        self.pending_turns.clear();
+    }
+}
+
+impl Controller for HumanController {
+    fn decide(&mut self, _view: &GameView) -> Option<Direction> {
+        self.pending_turns.pop_front()
+    }
+}
+
+// plays back the turns of a recorded game, it has to be started with the same seed to match
+pub struct ReplayController {
+    moves: VecDeque<(u64, Direction)>,
+}
+
+impl ReplayController {
+    pub fn from(moves: &[(u64, Direction)]) -> Self {
+        Self {
+            moves: moves.iter().copied().collect(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.moves.is_empty()
+    }
+}
+
+impl Controller for ReplayController {
+    fn decide(&mut self, view: &GameView) -> Option<Direction> {
+        // turns that were somehow missed are dropped instead of being played late
+        while let Some((tick, _)) = self.moves.front() {
+            if *tick >= view.ticks {
+                break;
+            }
+            self.moves.pop_front();
+        }
+
+        match self.moves.front() {
+            Some((tick, direction)) if *tick == view.ticks => {
+                let direction = *direction;
+                self.moves.pop_front();
+                Some(direction)
+            }
+            _ => None,
+        }
+    }
+}
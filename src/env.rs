@@ -0,0 +1,639 @@
+// the game wrapped up for reinforcement learning, in the style of gym: reset with a seed, then step
+// with an action and get back what the agent sees, a reward and whether the episode is over
+use crate::ai::grid::Grid;
+use crate::model::Direction;
+use crate::sim::Ending;
+use crate::{GameEvent, GameState, SnakeGame};
+
+// relative to where the snake is heading so the agent can never pick the reversed direction
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Action {
+    Straight,
+    TurnLeft,
+    TurnRight,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Straight, Action::TurnLeft, Action::TurnRight];
+
+    // for agents that pick an index from their output
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    pub fn apply(self, heading: Direction) -> Direction {
+        let (x, y) = vector(heading);
+        // y grows downwards on the screen so a left turn is clockwise in the math sense
+        let turned = match self {
+            Action::Straight => (x, y),
+            Action::TurnLeft => (y, -x),
+            Action::TurnRight => (-y, x),
+        };
+        direction_of(turned)
+    }
+}
+
+// how a direction moves the head, left and right are the other way around like in move_snake
+fn vector(direction: Direction) -> (i64, i64) {
+    match direction {
+        Direction::UP => (0, -1),
+        Direction::DOWN => (0, 1),
+        Direction::LEFT => (1, 0),
+        Direction::RIGHT => (-1, 0),
+    }
+}
+
+fn direction_of(vector: (i64, i64)) -> Direction {
+    match vector {
+        (0, -1) => Direction::UP,
+        (0, 1) => Direction::DOWN,
+        (1, 0) => Direction::LEFT,
+        _ => Direction::RIGHT,
+    }
+}
+
+// the environment plays with a single apple like the first game did, the apple in every encoding
+// is apples[0] and the rules that add more of them aren't turned on
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Encoding {
+    // the whole field as 4 planes of height x width: head, body, apple and obstacles
+    Grid,
+    // a square of 2 * radius + 1 cells around the head turned so the snake always looks up,
+    // as 3 planes: body, apple and obstacles
+    Egocentric { radius: u32 },
+    // a short list of hand picked numbers, see features()
+    Features,
+}
+
+pub const FEATURE_COUNT: usize = 11;
+
+// a flat tensor, shape tells how to read it (planes first, then rows, then columns)
+#[derive(PartialEq, Debug, Clone)]
+pub struct Observation {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Rewards {
+    pub apple: f32,
+    pub death: f32,
+    pub win: f32,
+    // given every step, a small negative one keeps the agent from circling forever
+    pub step: f32,
+    // given for every step that gets the head closer to the apple and taken for every step away
+    pub closer: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            apple: 1.0,
+            death: -1.0,
+            win: 10.0,
+            step: -0.01,
+            closer: 0.0,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct EnvConfig {
+    pub width: u32,
+    pub height: u32,
+    pub border: bool,
+    pub obstacles: u32,
+    pub encoding: Encoding,
+    pub rewards: Rewards,
+    // the episode is cut short after this many steps without an apple, 0 never cuts it
+    pub max_hungry_steps: u64,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            width: crate::PLAYGROUND_WIDTH,
+            height: crate::PLAYGROUND_HEIGHT,
+            border: false,
+            obstacles: 0,
+            encoding: Encoding::Grid,
+            rewards: Rewards::default(),
+            max_hungry_steps: 1000,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct StepInfo {
+    pub score: usize,
+    pub ticks: u64,
+    pub apple_eaten: bool,
+    // set once the episode is over
+    pub ending: Option<Ending>,
+    // the episode was cut short by max_hungry_steps instead of ending in the game
+    pub truncated: bool,
+}
+
+pub struct SnakeEnv {
+    pub config: EnvConfig,
+    pub game: SnakeGame,
+    hungry_steps: u64,
+}
+
+impl SnakeEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        let mut env = Self {
+            config,
+            game: SnakeGame::from_seed(
+                config.width,
+                config.height,
+                config.border,
+                (config.obstacles > 0, config.obstacles),
+                0,
+            ),
+            hungry_steps: 0,
+        };
+        env.reset(0);
+        env
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let config = self.config;
+        self.game = SnakeGame::from_seed(
+            config.width,
+            config.height,
+            config.border,
+            (config.obstacles > 0, config.obstacles),
+            seed,
+        );
+        self.game.resume();
+        self.game.drain_events();
+        self.hungry_steps = 0;
+        self.observe()
+    }
+
+    // stepping a finished episode does nothing but report it again, call reset first
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, StepInfo) {
+        let rewards = self.config.rewards;
+        let apple = self.game.apples[0].position;
+        let distance_before = self.apple_distance();
+        let mut reward = 0.0;
+
+        if self.game.state == GameState::Running {
+            let direction = action.apply(self.game.snake().head_direction());
+            self.game.change_head_direction(direction);
+            self.game.update();
+            self.hungry_steps += 1;
+            reward += rewards.step;
+        }
+
+        let apple_eaten = self.game.drain_events().contains(&GameEvent::AppleEaten);
+        if apple_eaten {
+            self.hungry_steps = 0;
+            reward += rewards.apple;
+        } else if self.game.apples[0].position == apple {
+            let distance_after = self.apple_distance();
+            if distance_after < distance_before {
+                reward += rewards.closer;
+            } else if distance_after > distance_before {
+                reward -= rewards.closer;
+            }
+        }
+
+        let over = self.game.state == GameState::GameOver;
+        let truncated = !over
+            && self.config.max_hungry_steps > 0
+            && self.hungry_steps >= self.config.max_hungry_steps;
+        let ending = (over || truncated).then(|| Ending::of(&self.game));
+        match ending {
+            Some(Ending::Won) => reward += rewards.win,
+            Some(Ending::Timeout) | None => {}
+            Some(_) => reward += rewards.death,
+        }
+
+        let info = StepInfo {
+            score: self.game.score(),
+            ticks: self.game.ticks,
+            apple_eaten,
+            ending,
+            truncated,
+        };
+        (self.observe(), reward, ending.is_some(), info)
+    }
+
+    fn head(&self) -> (u32, u32) {
+        self.game.snake().body[0].get().position
+    }
+
+    fn apple_distance(&self) -> u32 {
+        Grid::from_parts(&self.game.field, &[]).distance(self.head(), self.game.apples[0].position)
+    }
+
+    pub fn observe(&self) -> Observation {
+        match self.config.encoding {
+            Encoding::Grid => self.grid(),
+            Encoding::Egocentric { radius } => self.egocentric(radius),
+            Encoding::Features => self.features(),
+        }
+    }
+
+    fn grid(&self) -> Observation {
+        let (width, height) = self.game.field.size;
+        let plane = (width * height) as usize;
+        let mut data = vec![0.0; plane * 4];
+        let index = |position: (u32, u32)| (position.1 * width + position.0) as usize;
+
+        data[index(self.head())] = 1.0;
+        for body_part in &self.game.snake().body[1..] {
+            data[plane + index(body_part.get().position)] = 1.0;
+        }
+        data[plane * 2 + index(self.game.apples[0].position)] = 1.0;
+        if let Some(obstacles) = &self.game.field.obstacles {
+            for obstacle_part in obstacles {
+                data[plane * 3 + index(*obstacle_part)] = 1.0;
+            }
+        }
+        Observation {
+            shape: vec![4, height as usize, width as usize],
+            data,
+        }
+    }
+
+    fn egocentric(&self, radius: u32) -> Observation {
+        let (width, height) = self.game.field.size;
+        let side = (radius * 2 + 1) as usize;
+        let plane = side * side;
+        let mut data = vec![0.0; plane * 3];
+        let body: Vec<(u32, u32)> = self.game.snake().body[1..]
+            .iter()
+            .map(|body_part| body_part.get().position)
+            .collect();
+        let obstacles = self.game.field.obstacles.as_deref().unwrap_or(&[]);
+
+        let heading = self.game.snake().head_direction();
+        let forward = vector(heading);
+        let right = vector(Action::TurnRight.apply(heading));
+        let head = self.head();
+        let radius = radius as i64;
+        for row in 0..side {
+            for column in 0..side {
+                // the first row is the farthest ahead, the field wraps around like the snake does
+                let ahead = radius - row as i64;
+                let aside = column as i64 - radius;
+                let x = head.0 as i64 + forward.0 * ahead + right.0 * aside;
+                let y = head.1 as i64 + forward.1 * ahead + right.1 * aside;
+                let position = (
+                    x.rem_euclid(width as i64) as u32,
+                    y.rem_euclid(height as i64) as u32,
+                );
+                let cell = row * side + column;
+                if body.contains(&position) {
+                    data[cell] = 1.0;
+                }
+                if position == self.game.apples[0].position {
+                    data[plane + cell] = 1.0;
+                }
+                if obstacles.contains(&position) {
+                    data[plane * 2 + cell] = 1.0;
+                }
+            }
+        }
+        Observation {
+            shape: vec![3, side, side],
+            data,
+        }
+    }
+
+    // danger straight ahead, to the left and to the right, the heading as one of four,
+    // how far the apple is ahead and to the right of the head, the length of the snake and how
+    // hungry it is, everything between -1 and 1
+    fn features(&self) -> Observation {
+        let (width, height) = self.game.field.size;
+        let body: Vec<(u32, u32)> = self
+            .game
+            .snake()
+            .body
+            .iter()
+            .map(|body_part| body_part.get().position)
+            .collect();
+        let grid = Grid::from_parts(&self.game.field, &body);
+        let heading = self.game.snake().head_direction();
+        let head = self.head();
+        let mut data = Vec::with_capacity(FEATURE_COUNT);
+
+        for action in Action::ALL {
+            let free = grid
+                .neighbor(head, action.apply(heading))
+                .is_some_and(|next| grid.is_free_after(next, 1));
+            data.push(if free { 0.0 } else { 1.0 });
+        }
+        for direction in Direction::ALL {
+            data.push(if direction == heading { 1.0 } else { 0.0 });
+        }
+
+        // the shortest way to the apple on each axis, going around when the field wraps
+        let shortest = |from: u32, to: u32, size: u32| {
+            let straight = to as i64 - from as i64;
+            let around = straight - straight.signum() * size as i64;
+            if grid.wraps && around.abs() < straight.abs() {
+                around
+            } else {
+                straight
+            }
+        };
+        let apple = self.game.apples[0].position;
+        let offset = (
+            shortest(head.0, apple.0, width),
+            shortest(head.1, apple.1, height),
+        );
+        let forward = vector(heading);
+        let right = vector(Action::TurnRight.apply(heading));
+        let ahead = offset.0 * forward.0 + offset.1 * forward.1;
+        let aside = offset.0 * right.0 + offset.1 * right.1;
+        let size = width.max(height) as f32;
+        data.push(ahead as f32 / size);
+        data.push(aside as f32 / size);
+
+        data.push(body.len() as f32 / (width * height) as f32);
+        data.push(match self.config.max_hungry_steps {
+            0 => 0.0,
+            max => self.hungry_steps as f32 / max as f32,
+        });
+        Observation {
+            shape: vec![FEATURE_COUNT],
+            data,
+        }
+    }
+}
+
+// a batch of environments stepped together, finished ones start over on their own with the
+// next seed so the batch always stays full
+pub struct VecEnv {
+    pub envs: Vec<SnakeEnv>,
+    next_seed: u64,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, count: usize) -> Self {
+        Self {
+            envs: (0..count).map(|_| SnakeEnv::new(config)).collect(),
+            next_seed: 0,
+        }
+    }
+
+    // the environments get the seeds seed, seed + 1 and so on
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.next_seed = seed;
+        let mut observations = Vec::with_capacity(self.envs.len());
+        for env in &mut self.envs {
+            observations.push(env.reset(self.next_seed));
+            self.next_seed += 1;
+        }
+        observations
+    }
+
+    // one action per environment, when an episode ended its reward, done and info are kept
+    // but the observation is already the first one of the next episode
+    pub fn step(&mut self, actions: &[Action]) -> Vec<(Observation, f32, bool, StepInfo)> {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+        let mut results = Vec::with_capacity(self.envs.len());
+        for (env, action) in self.envs.iter_mut().zip(actions) {
+            let (mut observation, reward, done, info) = env.step(*action);
+            if done {
+                observation = env.reset(self.next_seed);
+                self.next_seed += 1;
+            }
+            results.push((observation, reward, done, info));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(config: EnvConfig, seed: u64) -> SnakeEnv {
+        let mut env = SnakeEnv::new(config);
+        env.reset(seed);
+        env
+    }
+
+    // the cell the head moves onto when it goes straight
+    fn ahead(env: &SnakeEnv) -> (u32, u32) {
+        let (x, y) = vector(env.game.snake().head_direction());
+        let head = env.head();
+        let (width, height) = env.game.field.size;
+        (
+            (head.0 as i64 + x).rem_euclid(width as i64) as u32,
+            (head.1 as i64 + y).rem_euclid(height as i64) as u32,
+        )
+    }
+
+    #[test]
+    fn the_same_seed_and_actions_play_the_same() {
+        let config = EnvConfig {
+            obstacles: 10,
+            encoding: Encoding::Features,
+            ..EnvConfig::default()
+        };
+        let (mut first, mut second) = (SnakeEnv::new(config), SnakeEnv::new(config));
+        assert_eq!(first.reset(5), second.reset(5));
+        for step in 0..200 {
+            let action = Action::ALL[step * 7 % 5 % 3];
+            assert_eq!(first.step(action), second.step(action), "step {}", step);
+        }
+        assert_ne!(first.reset(5), env(config, 6).observe());
+    }
+
+    #[test]
+    fn turns_are_relative_to_the_heading() {
+        for heading in Direction::ALL {
+            assert_eq!(Action::Straight.apply(heading), heading);
+            let left = Action::TurnLeft.apply(heading);
+            assert_ne!(left, heading);
+            assert_ne!(left, heading.opposite());
+            assert_eq!(Action::TurnRight.apply(left), heading);
+        }
+        assert_eq!(Action::from_index(2), Some(Action::TurnRight));
+        assert_eq!(Action::from_index(3), None);
+    }
+
+    #[test]
+    fn eating_gives_the_apple_reward() {
+        let mut env = env(EnvConfig::default(), 1);
+        env.game.apples[0].position = ahead(&env);
+        let (_, reward, done, info) = env.step(Action::Straight);
+        let rewards = Rewards::default();
+        assert_eq!(reward, rewards.apple + rewards.step);
+        assert!(info.apple_eaten && !done);
+        assert_eq!(info.score, 1);
+        assert_eq!(env.hungry_steps, 0);
+    }
+
+    #[test]
+    fn getting_closer_is_rewarded_and_moving_away_taken() {
+        let config = EnvConfig {
+            rewards: Rewards {
+                closer: 0.5,
+                step: 0.0,
+                ..Rewards::default()
+            },
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 1);
+        // the apple starts straight ahead of the snake
+        assert_eq!(env.step(Action::Straight).1, 0.5);
+        assert_eq!(env.step(Action::TurnLeft).1, -0.5);
+    }
+
+    #[test]
+    fn crashing_ends_the_episode() {
+        let config = EnvConfig {
+            border: true,
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 1);
+        let rewards = Rewards::default();
+        loop {
+            let (_, reward, done, info) = env.step(Action::Straight);
+            if done {
+                assert_eq!(info.ending, Some(Ending::Wall));
+                assert!(!info.truncated);
+                assert_eq!(reward, rewards.step + rewards.death);
+                break;
+            }
+            assert!(env.game.ticks < 100, "the snake never hit the wall");
+        }
+        // stepping a finished episode only reports it again
+        let ticks = env.game.ticks;
+        let (_, reward, done, info) = env.step(Action::TurnLeft);
+        assert_eq!((reward, done, info.ticks), (rewards.death, true, ticks));
+    }
+
+    #[test]
+    fn going_hungry_for_too_long_cuts_the_episode_short() {
+        let config = EnvConfig {
+            max_hungry_steps: 10,
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 1);
+        let rewards = Rewards::default();
+        // circling on the spot never gets to the apple or into the body
+        for step in 1..=10 {
+            let (_, reward, done, info) = env.step(Action::TurnLeft);
+            assert_eq!(reward, rewards.step);
+            assert_eq!(done, step == 10);
+            assert_eq!(info.truncated, step == 10);
+            assert_eq!(info.ending, (step == 10).then_some(Ending::Timeout));
+        }
+        assert_eq!(env.game.state, GameState::Running);
+    }
+
+    #[test]
+    fn the_grid_has_a_plane_for_everything() {
+        let config = EnvConfig {
+            width: 12,
+            height: 9,
+            obstacles: 4,
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 3);
+        env.game.apples[0].position = ahead(&env);
+        env.step(Action::Straight);
+        let observation = env.observe();
+        assert_eq!(observation.shape, [4, 9, 12]);
+        let plane = |index: usize| &observation.data[index * 108..(index + 1) * 108];
+        let cells = |index: usize| -> Vec<(u32, u32)> {
+            let on = plane(index)
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value == 1.0);
+            on.map(|(cell, _)| (cell as u32 % 12, cell as u32 / 12))
+                .collect()
+        };
+        let body = &env.game.snake().body;
+        assert_eq!(cells(0), [env.head()]);
+        assert_eq!(cells(1).len(), body.len() - 1);
+        assert_eq!(cells(2), [env.game.apples[0].position]);
+        assert_eq!(cells(3).len(), env.game.field.num_obstacles() as usize);
+        assert_eq!(
+            observation.data.iter().sum::<f32>(),
+            (body.len() + 5) as f32
+        );
+    }
+
+    #[test]
+    fn the_egocentric_view_looks_ahead_of_the_snake() {
+        let config = EnvConfig {
+            encoding: Encoding::Egocentric { radius: 2 },
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 1);
+        // whichever way the snake heads, the apple right in front is in the middle of the row
+        // above the head and the body right below it
+        for action in [Action::Straight, Action::TurnLeft, Action::TurnRight] {
+            env.step(action);
+            env.game.apples[0].position = ahead(&env);
+            let observation = env.observe();
+            assert_eq!(observation.shape, [3, 5, 5]);
+            let apples: Vec<usize> = (0..25)
+                .filter(|cell| observation.data[25 + cell] == 1.0)
+                .collect();
+            assert_eq!(apples, [7]);
+            assert_eq!(observation.data[17], 1.0);
+        }
+    }
+
+    #[test]
+    fn the_features_see_the_danger_and_the_apple() {
+        let config = EnvConfig {
+            border: true,
+            encoding: Encoding::Features,
+            ..EnvConfig::default()
+        };
+        let mut env = env(config, 1);
+        let observation = env.observe();
+        assert_eq!(observation.shape, [FEATURE_COUNT]);
+        assert!(observation
+            .data
+            .iter()
+            .all(|value| (-1.0..=1.0).contains(value)));
+        // nothing in the way yet and the apple is straight ahead
+        assert_eq!(observation.data[..3], [0.0, 0.0, 0.0]);
+        assert!(observation.data[7] > 0.0);
+        assert_eq!(observation.data[8], 0.0);
+        let heading = Direction::ALL
+            .iter()
+            .position(|direction| *direction == env.game.snake().head_direction())
+            .unwrap();
+        assert_eq!(observation.data[3 + heading], 1.0);
+
+        // it sees the wall coming right before the snake runs into it
+        while env.observe().data[0] == 0.0 {
+            assert!(!env.step(Action::Straight).2, "the wall came unseen");
+        }
+        assert_eq!(env.step(Action::Straight).3.ending, Some(Ending::Wall));
+    }
+
+    #[test]
+    fn a_batch_starts_finished_episodes_over_with_the_next_seed() {
+        let config = EnvConfig {
+            max_hungry_steps: 3,
+            ..EnvConfig::default()
+        };
+        let mut batch = VecEnv::new(config, 2);
+        let observations = batch.reset(10);
+        assert_eq!(observations[0], env(config, 10).observe());
+        assert_eq!(observations[1], env(config, 11).observe());
+        for step in 1..=3 {
+            let results = batch.step(&[Action::TurnLeft, Action::TurnRight]);
+            assert!(results.iter().all(|(_, _, done, _)| *done == (step == 3)));
+            if step == 3 {
+                assert_eq!(results[0].0, env(config, 12).observe());
+                assert_eq!(results[1].0, env(config, 13).observe());
+            }
+        }
+        assert_eq!(batch.envs[0].game.ticks, 0);
+    }
+}
@@ -0,0 +1,105 @@
+// gamepads steer the snakes the keyboard layouts don't reach, the d-pad and the left stick both work
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::{GameControllerSubsystem, Sdl};
+use snake_game_sdl::model::Direction;
+
+// how far the stick has to be pushed before it counts
+const STICK_DEAD_ZONE: i16 = 16_000;
+// the two keyboard layouts take the first two players
+const KEYBOARD_PLAYERS: usize = 2;
+
+pub struct Gamepads {
+    // none when sdl has no gamepad support here
+    subsystem: Option<GameControllerSubsystem>,
+    // in the order they were plugged in, with where the stick points so holding it turns once
+    pads: Vec<(GameController, Option<Direction>)>,
+}
+
+impl Gamepads {
+    pub fn open(sdl_context: &Sdl) -> Self {
+        let subsystem = match sdl_context.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(e) => {
+                eprintln!("No gamepad support, playing with the keyboard only: {}", e);
+                None
+            }
+        };
+        // pads that are already plugged in show up as added events on the first poll
+        Self {
+            subsystem,
+            pads: vec![],
+        }
+    }
+
+    // the player and the direction when the event steers a snake, plugging pads in and out is
+    // handled here too
+    pub fn handle(&mut self, event: &Event, players: usize) -> Option<(usize, Direction)> {
+        let (which, direction) = match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.as_ref()?.open(which) {
+                    Ok(pad) => {
+                        println!("Gamepad connected: {}", pad.name());
+                        self.pads.push((pad, None));
+                    }
+                    Err(e) => eprintln!("Failed to open the gamepad: {}", e),
+                }
+                return None;
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.retain(|(pad, _)| pad.instance_id() != which);
+                return None;
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                let direction = match button {
+                    Button::DPadUp => Direction::UP,
+                    Button::DPadDown => Direction::DOWN,
+                    // the same way around as the keyboard
+                    Button::DPadLeft => Direction::RIGHT,
+                    Button::DPadRight => Direction::LEFT,
+                    _ => return None,
+                };
+                (which, direction)
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let pushed = value.unsigned_abs() > STICK_DEAD_ZONE as u16;
+                let direction = match axis {
+                    Axis::LeftX if pushed && value < 0 => Some(Direction::RIGHT),
+                    Axis::LeftX if pushed => Some(Direction::LEFT),
+                    Axis::LeftY if pushed && value < 0 => Some(Direction::UP),
+                    Axis::LeftY if pushed => Some(Direction::DOWN),
+                    Axis::LeftX | Axis::LeftY => None,
+                    _ => return None,
+                };
+                let index = self.index_of(which)?;
+                let stick = &mut self.pads[index].1;
+                if *stick == direction {
+                    return None;
+                }
+                *stick = direction;
+                (which, direction?)
+            }
+            _ => return None,
+        };
+        let index = self.index_of(which)?;
+        Some((player_of_pad(index, players), direction))
+    }
+
+    fn index_of(&self, instance_id: u32) -> Option<usize> {
+        self.pads
+            .iter()
+            .position(|(pad, _)| pad.instance_id() == instance_id)
+    }
+}
+
+// pads go to the players after the keyboard ones, when there are none left they steer the
+// keyboard players too so two people can use pads in a two player game
+fn player_of_pad(index: usize, players: usize) -> usize {
+    if index + KEYBOARD_PLAYERS < players {
+        index + KEYBOARD_PLAYERS
+    } else {
+        index % players
+    }
+}
@@ -0,0 +1,177 @@
+// racing the best run so far on the same board, the run is played again next to the live game and
+// only its snake is drawn. it can't run into anything and nothing can run into it
+//
+// the best runs are kept in a folder with a file for every seed and set of rules, the file is the
+// replay of the run with a line in front telling how good it was:
+//   best result
+//   seed width height border obstacles players flags
+//   tick direction player
+// the result is the points, or the ticks it took for a sprint or to get out of a level
+use crate::controller::ReplayController;
+use crate::mode::Mode;
+use crate::model::Snake;
+use crate::replay::Replay;
+use crate::{GameState, SnakeGame};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub struct Ghost {
+    // the file the run came from
+    pub path: PathBuf,
+    replay: Replay,
+    game: SnakeGame,
+    controller: ReplayController,
+}
+
+impl Ghost {
+    // none when there is no best run on this board yet
+    pub fn load(folder: &Path, game: &SnakeGame) -> Result<Option<Self>, String> {
+        let path = path_of(folder, game);
+        let Some((_, replay)) = load_best(&path)? else {
+            return Ok(None);
+        };
+        let mut ghost_game = replay.new_game();
+        ghost_game.resume();
+        Ok(Some(Self {
+            path,
+            controller: ReplayController::from(&replay.moves[0]),
+            game: ghost_game,
+            replay,
+        }))
+    }
+
+    // keeps up with the live game, when that one went back it starts over and catches up
+    pub fn follow(&mut self, ticks: u64) {
+        if self.game.ticks > ticks {
+            self.game = self.replay.new_game();
+            self.game.resume();
+            self.controller = ReplayController::from(&self.replay.moves[0]);
+        }
+        while self.game.ticks < ticks && self.game.state == GameState::Running {
+            self.game.drive_player(0, &mut self.controller);
+            self.game.update();
+        }
+    }
+
+    // gone once it crashed
+    pub fn snake(&self) -> Option<&Snake> {
+        let player = &self.game.players[0];
+        player.alive.then_some(&player.snake)
+    }
+}
+
+// how good a finished single player game was, none for a sprint or a way out that wasn't finished
+pub fn result(game: &SnakeGame) -> Option<u64> {
+    match game.mode {
+        Mode::AppleSprint { .. } | Mode::Exit { .. } if !game.has_won() => None,
+        Mode::AppleSprint { .. } | Mode::Exit { .. } => Some(game.ticks),
+        _ => Some(game.score() as u64),
+    }
+}
+
+// keeps a finished game as the ghost of its board when it beat the best run, true when it did
+pub fn offer(folder: &Path, game: &SnakeGame) -> Result<bool, String> {
+    // a run with its mistakes taken back would be no fair one to race, like on the leaderboard
+    if !game.rewinds.is_empty() {
+        return Ok(false);
+    }
+    let Some(result) = result(game) else {
+        return Ok(false);
+    };
+    let path = path_of(folder, game);
+    // a tie goes to the older run
+    let is_better = match load_best(&path)? {
+        Some((best, _)) if game.mode.lower_is_better() => result < best,
+        Some((best, _)) => result > best,
+        None => true,
+    };
+    if !is_better {
+        return Ok(false);
+    }
+    fs::create_dir_all(folder)
+        .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+    let text = format!("best {}\n{}", result, Replay::from_game(game).to_text());
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+// the file is named after the first line of the replay, the seed and the rules
+fn path_of(folder: &Path, game: &SnakeGame) -> PathBuf {
+    let text = Replay::from_game(game).to_text();
+    let name: String = text
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '-' || c == '=' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    folder.join(format!("{}.txt", name))
+}
+
+fn load_best(path: &Path) -> Result<Option<(u64, Replay)>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let (first, replay) = text.split_once('\n').unwrap_or((&text, ""));
+    let Some(Ok(best)) = first.strip_prefix("best ").map(|best| best.trim().parse()) else {
+        return Err(format!("{} is no best run", path.display()));
+    };
+    Ok(Some((best, Replay::parse(replay)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::tests::{bots, play, snapshot};
+
+    fn folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("snake-ghosts-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        folder
+    }
+
+    fn played(ticks: u64) -> SnakeGame {
+        let mut game = SnakeGame::from_seed(21, 21, true, (true, 5), 7);
+        let mut controllers = bots(&game);
+        play(&mut game, &mut controllers, ticks);
+        game
+    }
+
+    #[test]
+    fn only_a_better_run_becomes_the_ghost() {
+        let folder = folder("better");
+        let short = played(60);
+        let long = played(400);
+        assert!(long.score() > short.score());
+        assert!(Ghost::load(&folder, &short).unwrap().is_none());
+        assert_eq!(offer(&folder, &short), Ok(true));
+        // a tie goes to the older run
+        assert_eq!(offer(&folder, &short), Ok(false));
+        assert_eq!(offer(&folder, &long), Ok(true));
+        assert_eq!(offer(&folder, &short), Ok(false));
+
+        // the ghost plays the best run again on the same board
+        let mut ghost = Ghost::load(&folder, &played(0)).unwrap().unwrap();
+        ghost.follow(long.ticks);
+        assert_eq!(snapshot(&ghost.game), snapshot(&long));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn a_rewound_run_never_becomes_the_ghost() {
+        let folder = folder("rewound");
+        let mut game = played(400);
+        game.rewinds.push((120, 80));
+        assert_eq!(offer(&folder, &game), Ok(false));
+        assert!(Ghost::load(&folder, &game).unwrap().is_none());
+    }
+}
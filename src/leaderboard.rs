@@ -0,0 +1,161 @@
+// the best results of every game mode, kept in a text file with one result per line:
+//   time-attack-60s-21x21-150ms+border 23 1760000000
+// the key of the mode, the board, the speed and the rules, the result and the unix time it was
+// made. what a result means depends on the mode, points for most of them and milliseconds for a
+// sprint
+use crate::replay::Replay;
+use crate::SnakeGame;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub key: String,
+    pub result: u64,
+    pub time: u64,
+}
+
+// the key a game goes on the leaderboard with, only games with the same key can be compared. the
+// time of a sprint and the ticks of a time attack go by the speed, so it is part of the key too.
+// power-ups that change the pace don't change the time, it goes by the ticks
+pub fn key(mode: &str, tickspeed: u32, game: &SnakeGame) -> String {
+    let replay = Replay::from_game(game);
+    format!(
+        "{}-{}x{}-{}ms{}",
+        mode,
+        replay.width,
+        replay.height,
+        tickspeed,
+        replay.rules()
+    )
+}
+
+pub struct Leaderboard {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    // a file that isn't there yet is an empty leaderboard
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut entries = vec![];
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [key, result, time] = parts[..] else {
+                return Err(format!("malformed result \"{}\"", line));
+            };
+            entries.push(Entry {
+                key: key.to_string(),
+                result: result
+                    .parse()
+                    .map_err(|_| format!("malformed result \"{}\"", line))?,
+                time: time
+                    .parse()
+                    .map_err(|_| format!("malformed result \"{}\"", line))?,
+            });
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    // adds a result and saves the file, the place it got is counted from 1 and ties go to the
+    // older result
+    pub fn add(&mut self, key: &str, result: u64, lower_is_better: bool) -> Result<usize, String> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let better = self
+            .entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .filter(|entry| match lower_is_better {
+                true => entry.result <= result,
+                false => entry.result >= result,
+            })
+            .count();
+        self.entries.push(Entry {
+            key: key.to_string(),
+            result,
+            time,
+        });
+        let text: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{} {} {}\n", entry.key, entry.result, entry.time))
+            .collect();
+        fs::write(&self.path, text)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        Ok(better + 1)
+    }
+
+    // the best results of a mode, the best first
+    pub fn top(&self, key: &str, lower_is_better: bool, count: usize) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .collect();
+        // a stable sort keeps the older one first on a tie
+        match lower_is_better {
+            true => entries.sort_by_key(|entry| entry.result),
+            false => entries.sort_by_key(|entry| std::cmp::Reverse(entry.result)),
+        }
+        entries.truncate(count);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_key_tells_the_board_the_speed_and_the_rules_apart() {
+        let plain = SnakeGame::from_seed(21, 21, false, (false, 0), 1);
+        assert_eq!(key("classic", 150, &plain), "classic-21x21-150ms");
+        assert_ne!(key("classic", 150, &plain), key("classic", 10, &plain));
+        assert_eq!(
+            key("classic", 150, &plain),
+            key(
+                "classic",
+                150,
+                &SnakeGame::from_seed(21, 21, false, (false, 0), 2)
+            )
+        );
+        let busy = SnakeGame::from_seed(30, 25, true, (true, 5), 1).with_apples(3, false);
+        assert_eq!(
+            key("sprint-20", 100, &busy),
+            "sprint-20-30x25-100ms+border+obstacles=5+apples=3"
+        );
+        assert!(!key("sprint-20", 100, &busy).contains(char::is_whitespace));
+    }
+
+    #[test]
+    fn results_are_ranked_within_their_key() {
+        let path = std::env::temp_dir().join(format!("snake-scores-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut leaderboard = Leaderboard::load(&path).unwrap();
+        assert_eq!(leaderboard.add("a", 10, false), Ok(1));
+        assert_eq!(leaderboard.add("a", 20, false), Ok(1));
+        assert_eq!(leaderboard.add("a", 10, false), Ok(3));
+        assert_eq!(leaderboard.add("b", 5, true), Ok(1));
+        let leaderboard = Leaderboard::load(&path).unwrap();
+        let best: Vec<u64> = leaderboard
+            .top("a", false, 2)
+            .iter()
+            .map(|e| e.result)
+            .collect();
+        assert_eq!(best, [20, 10]);
+        assert_eq!(leaderboard.top("b", true, 5).len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,38 +3,185 @@
 //use model::snake;
 //
 //
+pub mod ai;
+pub mod bot;
+pub mod camera;
+pub mod campaign;
+pub mod controller;
+pub mod env;
+pub mod ghost;
+pub mod leaderboard;
+pub mod mode;
 pub mod model;
-use model::{Apple, Field, Snake};
-use rand::Rng;
+pub mod net;
+pub mod palette;
+pub mod replay;
+pub mod rewind;
+pub mod save;
+pub mod sim;
+pub mod tournament;
+use controller::{Controller, GameView};
+use mode::Mode;
+use model::{
+    stack_effect, Apple, AppleKind, Direction, Edge, Edges, Effect, Field, Gate, Patrol, PowerUp,
+    PowerUpKind, Snake, GATE_PERIOD, PATROL_PACE,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashSet;
 
 // to make them global
 pub const PLAYGROUND_WIDTH: u32 = 21;
 pub const PLAYGROUND_HEIGHT: u32 = 21;
 pub const HAS_BORDER: bool = true;
+// the smallest and the biggest side of a board anything plays on, the window takes 21 cells at
+// least but the simulator and the tournament go down to this
+pub const MIN_SIDE: u32 = 4;
+pub const MAX_SIDE: u32 = 1000;
+// how often a power-up shows up when they are turned on, and how long it stays
+pub const POWER_UP_INTERVAL: u64 = 50;
+pub const POWER_UP_LIFETIME: u64 = 150;
+const MAX_POWER_UPS: usize = 2;
+// how many parts a shrink takes off
+const SHRINK_PARTS: usize = 3;
+// how close the apple has to be for the magnet to pull it
+const MAGNET_RANGE: u32 = 6;
+// what the other kinds of apples do when they are turned on
+const GOLDEN_POINTS: usize = 5;
+const GOLDEN_LIFETIME: u64 = 60;
+const ROTTEN_TICKS: u64 = 30;
+// how many cells the patrols walk and the gates block
+const PATROL_LENGTH: usize = 5;
+const GATE_LENGTH: usize = 4;
+// obstacles don't show up this close in front of a head
+const SAFE_DISTANCE: u32 = 3;
+// how often a straight line of free cells is looked for before giving up
+const FREE_LINE_TRIES: usize = 20;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum GameState {
     Running,
     Paused,
     GameOver,
 }
 
+// what happened since the frontend last asked, used for sounds and effects
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum GameEvent {
+    AppleEaten,
+    Turned,
+    Collided,
+    Won,
+    Paused,
+    Resumed,
+    Disqualified,
+    PoweredUp,
+    Poisoned,
+}
+
+// one snake and whatever belongs to the one steering it
+#[derive(Clone)]
+pub struct Player {
+    pub snake: Snake,
+    // false once the snake crashed, it is taken off the field from then on
+    pub alive: bool,
+    // every turn that was taken and the tick it happened on, enough to replay the game
+    pub moves: Vec<(u64, Direction)>,
+    // why the controller was thrown out of the game, if it was
+    pub disqualified: Option<String>,
+    // what the power-ups it picked up still do
+    pub effects: Vec<Effect>,
+    // every apple is worth a point, more with a multiplier
+    pub points: usize,
+    // how many apples worth eating it ate
+    pub eaten: usize,
+    // how many more ticks a rotten apple keeps the controls turned around
+    pub reversed: u64,
+}
+
+impl Player {
+    fn at(x: u32, y: u32) -> Self {
+        Self {
+            snake: Snake::add_to_field_at_start(x, y),
+            alive: true,
+            moves: vec![],
+            disqualified: None,
+            effects: vec![],
+            points: 0,
+            eaten: 0,
+            reversed: 0,
+        }
+    }
+
+    pub fn score(&self) -> usize {
+        self.points
+    }
+
+    pub fn effect(&self, kind: PowerUpKind) -> Option<&Effect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    // what an apple is worth right now
+    pub fn multiplier(&self) -> usize {
+        match self.effect(PowerUpKind::Multiplier) {
+            Some(effect) => 1 + effect.stacks as usize,
+            None => 1,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SnakeGame {
     pub state: GameState,
     pub field: Field,
-    pub snake: Snake, // dummy field and to chaage
-    pub apple: Apple,
+    // the first player is the one a single player game is about
+    pub players: Vec<Player>,
+    // the first one is where the apple always was, more can be added with with_apples
+    pub apples: Vec<Apple>,
+    // how many apples the game asked for, on a crowded board there may not have been room for all
+    pub num_apples: u32,
+    // golden, poison and rotten apples show up besides the normal ones
+    pub apple_kinds: bool,
+    // the power-ups lying on the field, only when they are turned on
+    pub power_ups: Vec<PowerUp>,
+    pub spawns_power_ups: bool,
+    pub mode: Mode,
+    // the id of the campaign level this game is on
+    pub level: Option<String>,
+    // every time the game was rewound and played on from an earlier tick, from which tick to which
+    pub rewinds: Vec<(u64, u64)>,
+    events: Vec<GameEvent>,
+    // everything random in a game comes from here so the same seed plays out the same way
+    pub seed: u64,
+    rng: ChaCha8Rng,
+    // how many times the snakes moved
+    pub ticks: u64,
+    // how many obstacles showed up because of the points so far
+    grown_obstacles: u32,
 }
 
-fn check_if_occupied_by_obstacle_or_snake(place: (u32, u32), snake: &Snake, field: &Field) -> bool {
-    for body_part in snake.body.iter() {
-        if body_part.get().position.0 == place.0 && body_part.get().position.1 == place.1 {
-            return true;
+fn alive_snakes(players: &[Player]) -> impl Iterator<Item = &Snake> {
+    players
+        .iter()
+        .filter(|player| player.alive)
+        .map(|player| &player.snake)
+}
+
+fn check_if_occupied_by_obstacle_or_snake(
+    place: (u32, u32),
+    players: &[Player],
+    field: &Field,
+) -> bool {
+    for snake in alive_snakes(players) {
+        for body_part in snake.body.iter() {
+            if body_part.get().position.0 == place.0 && body_part.get().position.1 == place.1 {
+                return true;
+            }
         }
     }
 
-    if field.obstacles.is_some() {
-        for obstacle_part in field.obstacles.as_ref().expect("this is gay") {
+    if let Some(obstacles) = &field.obstacles {
+        for obstacle_part in obstacles {
             if obstacle_part.0 == place.0 && obstacle_part.1 == place.1 {
                 return true;
             }
@@ -43,25 +190,22 @@ fn check_if_occupied_by_obstacle_or_snake(place: (u32, u32), snake: &Snake, fiel
     false
 }
 
-fn check_if_occupied_by_apple(snake_head: (u32, u32), apple_position: (u32, u32)) -> bool {
-    snake_head.0 == apple_position.0 && snake_head.1 == apple_position.1
-}
-
 // broken implementation
-fn check_collision(snake: &mut Snake, field: &Field) -> bool {
-    // check the snake head if it has the same position as an obstacle or one of its body parts
+fn check_collision(snake: &mut Snake, field: &Field, ghost: bool) -> bool {
+    // check the snake head if it has the same position as an obstacle or one of its body parts,
+    // a ghost goes right through its own body
     let snake_head = snake.body[0].get().position;
-    for body_part in &snake.body[1..] {
+    let own_body = if ghost { &[][..] } else { &snake.body[1..] };
+    for body_part in own_body {
         if snake_head.0 == body_part.get().position.0 && snake_head.1 == body_part.get().position.1
         {
             snake.grow_snake(field.size.0, field.size.1);
-            println!("COLLISION DETECTED WITH SNAKES");
             return true;
         }
     }
 
-    if field.obstacles.is_some() {
-        for obstacle_part in field.obstacles.as_ref().expect("this is gay") {
+    if let Some(obstacles) = &field.obstacles {
+        for obstacle_part in obstacles {
             if snake_head.0 == obstacle_part.0 && snake_head.1 == obstacle_part.1 {
                 snake.grow_snake(field.size.0, field.size.1);
                 return true;
@@ -71,41 +215,284 @@ fn check_collision(snake: &mut Snake, field: &Field) -> bool {
     false
 }
 
-// need to add that to the game
-fn no_free_space_available(snake: &Snake, field: &Field) -> bool {
-    let total_number_space_available = PLAYGROUND_WIDTH * PLAYGROUND_HEIGHT;
+// running into any part of another snake, two heads meeting takes out both
+fn check_collision_with_others(index: usize, players: &[Player]) -> bool {
+    let head = players[index].snake.body[0].get().position;
+    players
+        .iter()
+        .enumerate()
+        .filter(|(other, player)| *other != index && player.alive)
+        .any(|(_, player)| {
+            player
+                .snake
+                .body
+                .iter()
+                .any(|body_part| body_part.get().position == head)
+        })
+}
 
-    if field.obstacles.is_some() {
-        return total_number_space_available
-            - snake.body.len() as u32
-            - field.obstacles.as_ref().expect("this is gay").len() as u32
-            == 0;
+// obstacles can be generated on top of each other so they are counted once per cell
+fn occupied_cells(players: &[Player], field: &Field) -> HashSet<(u32, u32)> {
+    let mut occupied: HashSet<(u32, u32)> = alive_snakes(players)
+        .flat_map(|snake| snake.body.iter().map(|body_part| body_part.get().position))
+        .collect();
+    if let Some(obstacles) = &field.obstacles {
+        occupied.extend(obstacles.iter().copied());
     }
-    total_number_space_available - snake.body.len() as u32 == 0
+    occupied
+}
+
+// the number of moves between two cells without wrapping around
+fn distance(from: (u32, u32), to: (u32, u32)) -> u32 {
+    from.0.abs_diff(to.0) + from.1.abs_diff(to.1)
+}
+
+fn no_free_space_available(players: &[Player], field: &Field) -> bool {
+    occupied_cells(players, field).len() as u32 >= field.size.0 * field.size.1
+}
+
+// a random cell that isn't taken, none when the snakes filled the whole field, the items are
+// whatever else lies on the field and shouldn't end up under the new one
+fn random_free_place(
+    rng: &mut impl Rng,
+    players: &[Player],
+    field: &Field,
+    items: &[(u32, u32)],
+) -> Option<(u32, u32)> {
+    // guessing is fast while the field is mostly empty
+    for _ in 0..64 {
+        let place = (
+            rng.gen_range(0..field.size.0),
+            rng.gen_range(0..field.size.1),
+        );
+        if !check_if_occupied_by_obstacle_or_snake(place, players, field) && !items.contains(&place)
+        {
+            return Some(place);
+        }
+    }
+
+    let occupied = occupied_cells(players, field);
+    let free_places: Vec<(u32, u32)> = (0..field.size.1)
+        .flat_map(|y| (0..field.size.0).map(move |x| (x, y)))
+        .filter(|place| !occupied.contains(place) && !items.contains(place))
+        .collect();
+    if free_places.is_empty() {
+        return None;
+    }
+    Some(free_places[rng.gen_range(0..free_places.len())])
 }
 
 impl SnakeGame {
     pub fn from(width: u32, height: u32, has_border: bool, obstacles: (bool, u32)) -> Self {
+        Self::from_seed(width, height, has_border, obstacles, rand::random())
+    }
+
+    pub fn from_seed(
+        width: u32,
+        height: u32,
+        has_border: bool,
+        obstacles: (bool, u32),
+        seed: u64,
+    ) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         Self {
             state: GameState::Paused,
-            field: Field::from((width, height), has_border, obstacles.0, obstacles.1),
+            field: Field::from_rng(
+                (width, height),
+                has_border,
+                obstacles.0,
+                obstacles.1,
+                &mut rng,
+            ),
             // the initial position of the snake must be determined
-            snake: Snake::add_to_field_at_start(width / 3, height / 2),
-            apple: Apple::add_to_field_at_start((width * 2) / 3, height / 2),
+            players: vec![Player::at(width / 3, height / 2)],
+            apples: vec![Apple::add_to_field_at_start((width * 2) / 3, height / 2)],
+            num_apples: 1,
+            apple_kinds: false,
+            power_ups: vec![],
+            spawns_power_ups: false,
+            mode: Mode::Classic,
+            level: None,
+            rewinds: vec![],
+            events: vec![],
+            seed,
+            rng,
+            ticks: 0,
+            grown_obstacles: 0,
         }
     }
 
     pub fn new() -> Self {
-        Self {
-            state: GameState::Paused,
-            field: Field::from((PLAYGROUND_WIDTH, PLAYGROUND_HEIGHT), false, false, 0),
-            // the initial position of the snake must be determined
-            snake: Snake::add_to_field_at_start(PLAYGROUND_WIDTH / 3, PLAYGROUND_HEIGHT / 2),
-            apple: Apple::add_to_field_at_start((PLAYGROUND_WIDTH * 2) / 3, PLAYGROUND_HEIGHT / 2),
+        Self::from(PLAYGROUND_WIDTH, PLAYGROUND_HEIGHT, false, (false, 0))
+    }
+
+    // the snakes start on evenly spread rows all heading the same way, obstacles in their way
+    // at the start are taken off so nobody loses on the first tick
+    pub fn with_players(mut self, count: usize) -> Self {
+        if count <= 1 {
+            return self;
+        }
+        let (width, height) = self.field.size;
+        let rows: Vec<u32> = (1..=count as u32)
+            .map(|row| height * row / (count as u32 + 1))
+            .collect();
+        self.players = rows.iter().map(|row| Player::at(width / 3, *row)).collect();
+        if let Some(obstacles) = &mut self.field.obstacles {
+            obstacles.retain(|obstacle_part| {
+                let in_the_way =
+                    obstacle_part.0 + 1 >= width / 3 && obstacle_part.0 <= width / 3 + 2;
+                !(in_the_way && rows.contains(&obstacle_part.1))
+            });
+        }
+        self
+    }
+
+    // a board made by hand with a single snake starting at the given cell, apples that ended up
+    // under an obstacle, the snake or the exit go somewhere else
+    pub fn with_layout(mut self, obstacles: Vec<(u32, u32)>, start: (u32, u32)) -> Self {
+        self.field.obstacles = Some(obstacles);
+        self.players = vec![Player::at(start.0, start.1)];
+        for index in 0..self.apples.len() {
+            let position = self.apples[index].position;
+            if check_if_occupied_by_obstacle_or_snake(position, &self.players, &self.field)
+                || Some(position) == self.exit()
+            {
+                self.replace_apple(index);
+            }
+        }
+        self
+    }
+
+    // power-ups show up every POWER_UP_INTERVAL ticks, they are off unless turned on here
+    pub fn with_power_ups(mut self, on: bool) -> Self {
+        self.spawns_power_ups = on;
+        self
+    }
+
+    // several apples at once and the other kinds of apples, added after the players so the new
+    // apples don't end up under a snake
+    pub fn with_apples(mut self, count: usize, kinds: bool) -> Self {
+        self.apple_kinds = kinds;
+        self.num_apples = count as u32;
+        while self.apples.len() < count {
+            let kind = self.next_apple_kind(None);
+            let items = self.item_positions();
+            let Some((x, y)) = random_free_place(&mut self.rng, &self.players, &self.field, &items)
+            else {
+                break;
+            };
+            self.apples.push(Apple {
+                position: (x, y),
+                kind,
+                expires: None,
+            });
+            self.set_lifetime(self.apples.len() - 1);
         }
+        self
+    }
+
+    // what happens at the sides of the field, wrapping around everywhere unless changed here
+    pub fn with_edges(mut self, edges: Edges) -> Self {
+        self.field.edges = edges;
+        self
+    }
+
+    // pairs of portals on free cells, added after the players like the apples
+    pub fn with_portals(mut self, pairs: usize) -> Self {
+        self.field.num_portals = pairs as u32;
+        for _ in 0..pairs {
+            let items = self.item_positions();
+            let Some(first) = random_free_place(&mut self.rng, &self.players, &self.field, &items)
+            else {
+                break;
+            };
+            let mut items = self.item_positions();
+            items.push(first);
+            let Some(second) = random_free_place(&mut self.rng, &self.players, &self.field, &items)
+            else {
+                break;
+            };
+            self.field.portals.push((first, second));
+        }
+        self
+    }
+
+    // patrols and gates on free lines, added after the players like the portals
+    pub fn with_moving_obstacles(mut self, patrols: usize, gates: usize) -> Self {
+        self.field.num_patrols = patrols as u32;
+        self.field.num_gates = gates as u32;
+        for _ in 0..patrols {
+            let Some(path) = self.free_line(PATROL_LENGTH) else {
+                break;
+            };
+            self.field.add_obstacle(path[0]);
+            self.field.patrols.push(Patrol::new(path));
+        }
+        for _ in 0..gates {
+            let Some(cells) = self.free_line(GATE_LENGTH) else {
+                break;
+            };
+            // they don't all close at the same time
+            let switches = self.rng.gen_range(GATE_PERIOD / 2..=GATE_PERIOD);
+            self.field.gates.push(Gate {
+                cells,
+                closed: false,
+                switches,
+            });
+        }
+        self
+    }
+
+    // one more obstacle every time the snakes together make this many more points
+    pub fn with_growing_obstacles(mut self, points: u32) -> Self {
+        self.field.growing_obstacles = points;
+        self
+    }
+
+    // a ring of walls closes around the arena every this many ticks
+    pub fn with_shrinking_arena(mut self, ticks: u32) -> Self {
+        self.field.shrinking_arena = ticks;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // how much longer a tick takes than usual, slow-mo and speed change it for everyone
+    pub fn pace(&self) -> f64 {
+        self.players
+            .iter()
+            .filter(|player| player.alive)
+            .flat_map(|player| player.effects.iter())
+            .map(|effect| match effect.kind {
+                PowerUpKind::SlowMotion => 2.0,
+                PowerUpKind::Speed => 0.5,
+                _ => 1.0,
+            })
+            .product()
+    }
+
+    pub fn snake(&self) -> &Snake {
+        &self.players[0].snake
+    }
+
+    // the snakes to draw with the player they belong to, a crashed snake stays on screen in a
+    // single player game so the player sees what happened
+    pub fn visible_snakes(&self) -> impl Iterator<Item = (usize, &Snake)> {
+        let single_player = self.players.len() == 1;
+        self.players
+            .iter()
+            .enumerate()
+            .filter(move |(_, player)| player.alive || single_player)
+            .map(|(index, player)| (index, &player.snake))
     }
 
     pub fn resume(&mut self) {
+        if self.state == GameState::Paused {
+            self.events.push(GameEvent::Resumed);
+        }
         if self.state == GameState::GameOver {
             return;
         }
@@ -115,13 +502,86 @@ impl SnakeGame {
     pub fn toggle_pause(&mut self) {
         if self.state == GameState::Running {
             self.state = GameState::Paused;
+            self.events.push(GameEvent::Paused);
         } else if self.state == GameState::Paused {
             self.state = GameState::Running;
+            self.events.push(GameEvent::Resumed);
         }
     }
 
-    pub fn change_head_direction(&mut self, direction: model::Direction) {
-        self.snake.set_direction(direction);
+    pub fn change_head_direction(&mut self, direction: Direction) {
+        self.turn(0, direction);
+    }
+
+    pub fn turn(&mut self, index: usize, direction: Direction) {
+        let ticks = self.ticks;
+        let player = &mut self.players[index];
+        let old_direction = player.snake.head_direction();
+        // the turn is recorded the way it was asked for so replays turn it around again
+        match player.reversed > 0 {
+            true => player.snake.set_direction(direction.opposite()),
+            false => player.snake.set_direction(direction),
+        }
+        if player.snake.head_direction() != old_direction {
+            self.events.push(GameEvent::Turned);
+            player.moves.push((ticks, direction));
+        }
+    }
+
+    // what a controller gets to see before deciding
+    pub fn view(&self) -> GameView<'_> {
+        self.view_of(0)
+    }
+
+    pub fn view_of(&self, index: usize) -> GameView<'_> {
+        GameView {
+            state: self.state,
+            field: &self.field,
+            snake: &self.players[index].snake,
+            others: self
+                .players
+                .iter()
+                .enumerate()
+                .filter(|(other, player)| *other != index && player.alive)
+                .map(|(_, player)| &player.snake)
+                .collect(),
+            apple: self.food_for(index),
+            apples: &self.apples,
+            ticks: self.ticks,
+        }
+    }
+
+    // asks the controller where to go and turns the snake, true if it wanted to move
+    pub fn drive(&mut self, controller: &mut dyn Controller) -> bool {
+        self.drive_player(0, controller)
+    }
+
+    pub fn drive_player(&mut self, index: usize, controller: &mut dyn Controller) -> bool {
+        if !self.players[index].alive {
+            return false;
+        }
+        let decision = controller.decide(&self.view_of(index));
+        if let Some(reason) = controller.disqualified() {
+            if self.state != GameState::GameOver {
+                let player = &mut self.players[index];
+                player.alive = false;
+                player.disqualified = Some(reason.to_string());
+                self.events.push(GameEvent::Disqualified);
+                self.check_for_last_snake_standing();
+            }
+            return false;
+        }
+        match decision {
+            Some(direction) => {
+                self.turn(index, direction);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
     }
 
     pub fn update(&mut self) {
@@ -129,45 +589,497 @@ impl SnakeGame {
             return;
         };
 
-        self.snake.move_snake(self.field.size.0, self.field.size.1);
-
-        let (mut x, mut y);
-        // check if the snake has eaten an apple
-        if check_if_occupied_by_apple(self.snake.body[0].get().position, self.apple.position) {
-            let mut rng = rand::thread_rng();
-            self.snake.grow_snake(self.field.size.0, self.field.size.1);
-            loop {
-                (x, y) = (
-                    rng.gen_range(1..self.field.size.0 - 1),
-                    rng.gen_range(1..self.field.size.1 - 1),
-                );
-                if !check_if_occupied_by_obstacle_or_snake((x, y), &self.snake, &self.field) {
-                    break;
+        let (width, height) = self.field.size;
+        // a snake running into a wall stays where it is and crashes below
+        let mut hit_wall = vec![];
+        for (index, player) in self.players.iter_mut().enumerate() {
+            if !player.alive {
+                continue;
+            }
+            let head = player.snake.body[0].get().position;
+            let heading = player.snake.head_direction();
+            match self.field.edge_ahead(head, heading) {
+                Some(Edge::Wall) => {
+                    hit_wall.push(index);
+                    continue;
                 }
+                // not a turn of the player so it isn't recorded, replays bounce the same way
+                Some(Edge::Bounce) => {
+                    player.snake.set_direction(self.field.bounce(head, heading));
+                }
+                Some(Edge::Wrap) | None => {}
+            }
+            player.snake.move_snake(width, height);
+            if let Some(exit) = self.field.portal_exit(player.snake.body[0].get().position) {
+                player.snake.teleport_head(exit);
             }
-            self.apple.place(x, y);
         }
+        self.ticks += 1;
 
-        // check if the snake has hit the wall
-        if check_collision(&mut self.snake, &self.field) {
-            self.state = GameState::GameOver;
-            print!("{}[2J", 27_u8 as char);
-            println!(
-                "Game Over!\nScore: {}\nPress <R> to restart!",
-                self.snake.body.len() - 1
-            );
+        // check if a snake has eaten an apple
+        for index in 0..self.players.len() {
+            let player = &mut self.players[index];
+            if !player.alive {
+                continue;
+            }
+            let head = player.snake.body[0].get().position;
+            let Some(eaten) = self.apples.iter().position(|apple| apple.position == head) else {
+                continue;
+            };
+            match self.apples[eaten].kind {
+                AppleKind::Normal | AppleKind::Rotten => {
+                    player.points += player.multiplier();
+                    player.eaten += 1;
+                    player.snake.grow_snake(width, height);
+                    self.events.push(GameEvent::AppleEaten);
+                }
+                AppleKind::Golden => {
+                    player.points += GOLDEN_POINTS * player.multiplier();
+                    player.eaten += 1;
+                    player.snake.grow_snake(width, height);
+                    self.events.push(GameEvent::AppleEaten);
+                }
+                // a snake of a head and a tail has nothing left to lose
+                AppleKind::Poison if player.snake.body.len() <= 2 => {
+                    player.alive = false;
+                    self.events.push(GameEvent::Collided);
+                }
+                AppleKind::Poison => {
+                    player.snake.shrink(1);
+                    player.points = player.points.saturating_sub(1);
+                    self.events.push(GameEvent::Poisoned);
+                }
+            }
+            if self.apples[eaten].kind == AppleKind::Rotten {
+                player.reversed = ROTTEN_TICKS;
+            }
+            // when nothing is free the apple stays under the head and the game is won below
+            self.replace_apple(eaten);
+        }
+        self.pick_up_power_ups();
+        self.pull_apple();
+
+        // every snake moved at the same time so the crashes are found before anyone is removed
+        let crashed: Vec<usize> = (0..self.players.len())
+            .filter(|index| self.players[*index].alive)
+            .filter(|index| check_collision_with_others(*index, &self.players))
+            .collect();
+        for index in 0..self.players.len() {
+            let player = &mut self.players[index];
+            // check if the snake has hit the wall
+            let ghost = player.effect(PowerUpKind::Ghost).is_some();
+            if player.alive
+                && (check_collision(&mut player.snake, &self.field, ghost)
+                    || crashed.contains(&index)
+                    || hit_wall.contains(&index))
+            {
+                player.alive = false;
+                self.events.push(GameEvent::Collided);
+            }
+        }
+        self.wear_off_effects();
+        self.spawn_power_up();
+        self.shrink_arena();
+        self.check_for_last_snake_standing();
+        if self.state == GameState::GameOver {
             return;
         }
+        // after the crashes so a snake always runs into an obstacle and never the other way
+        self.move_obstacles();
 
-        if no_free_space_available(&self.snake, &self.field) {
+        // an endless game has nothing to win, not even with a full board
+        let is_full =
+            self.mode != Mode::Endless && no_free_space_available(&self.players, &self.field);
+        if is_full || (0..self.players.len()).any(|index| self.reached_goal(index)) {
             self.state = GameState::GameOver;
-            print!("{}[2J", 27_u8 as char);
-            println!(
-                "You Win!\nScore: {}\nPress <R> to restart!",
-                self.snake.body.len() - 1
-            );
+            self.events.push(GameEvent::Won);
+        }
+    }
+
+    // the time ran out with the snake still going, it ate enough apples for the sprint or it got
+    // to the exit
+    fn reached_goal(&self, index: usize) -> bool {
+        let player = &self.players[index];
+        match self.mode {
+            Mode::TimeAttack { ticks } => player.alive && self.ticks >= ticks,
+            Mode::AppleSprint { apples } => player.eaten >= apples as usize,
+            Mode::Exit { x, y } => player.alive && player.snake.body[0].get().position == (x, y),
+            Mode::Classic | Mode::Endless => false,
+        }
+    }
+
+    // the cell a campaign level is left through
+    pub fn exit(&self) -> Option<(u32, u32)> {
+        match self.mode {
+            Mode::Exit { x, y } => Some((x, y)),
+            _ => None,
+        }
+    }
+
+    fn pick_up_power_ups(&mut self) {
+        for player in self.players.iter_mut().filter(|player| player.alive) {
+            let head = player.snake.body[0].get().position;
+            let Some(index) = self.power_ups.iter().position(|item| item.position == head) else {
+                continue;
+            };
+            let kind = self.power_ups.remove(index).kind;
+            if kind == PowerUpKind::Shrink {
+                player.snake.shrink(SHRINK_PARTS);
+            }
+            stack_effect(&mut player.effects, kind);
+            self.events.push(GameEvent::PoweredUp);
+        }
+    }
+
+    // the apple for the snake to go for, the closest one that is worth eating
+    fn food_for(&self, index: usize) -> &Apple {
+        let head = self.players[index].snake.body[0].get().position;
+        self.apples
+            .iter()
+            .filter(|apple| apple.kind.is_food())
+            .min_by_key(|apple| distance(head, apple.position))
+            .unwrap_or(&self.apples[0])
+    }
+
+    // where the apples, the power-ups, the portals, the gates, open or not, and the exit are
+    fn item_positions(&self) -> Vec<(u32, u32)> {
+        self.apples
+            .iter()
+            .map(|apple| apple.position)
+            .chain(self.power_ups.iter().map(|item| item.position))
+            .chain(
+                self.field
+                    .portals
+                    .iter()
+                    .flat_map(|(first, second)| [*first, *second]),
+            )
+            .chain(self.field.gates.iter().flat_map(|gate| gate.cells.clone()))
+            .chain(self.exit())
+            .collect()
+    }
+
+    // the cells right in front of the snakes that are still in the game
+    fn cells_ahead(&self, count: u32) -> Vec<(u32, u32)> {
+        let (width, height) = self.field.size;
+        let mut cells = vec![];
+        for player in self.players.iter().filter(|player| player.alive) {
+            let mut cell = player.snake.body[0].get().position;
+            for _ in 0..count {
+                cell = player.snake.head_direction().step(cell, width, height);
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    // a straight line of free cells out of the way of the snakes, the lines don't wrap around
+    fn free_line(&mut self, length: usize) -> Option<Vec<(u32, u32)>> {
+        let (width, height) = self.field.size;
+        let mut blocked = self.item_positions();
+        blocked.extend(self.cells_ahead(SAFE_DISTANCE));
+        for _ in 0..FREE_LINE_TRIES {
+            let start = random_free_place(&mut self.rng, &self.players, &self.field, &blocked)?;
+            let horizontal = self.rng.gen_bool(0.5);
+            let mut line = vec![start];
+            while line.len() < length {
+                let (x, y) = line[line.len() - 1];
+                let next = if horizontal { (x + 1, y) } else { (x, y + 1) };
+                if next.0 >= width
+                    || next.1 >= height
+                    || check_if_occupied_by_obstacle_or_snake(next, &self.players, &self.field)
+                    || blocked.contains(&next)
+                {
+                    break;
+                }
+                line.push(next);
+            }
+            if line.len() == length {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    // the patrols take a step, the gates open and close and more obstacles show up with the
+    // points, none of them ever moves onto a snake or into the cell right in front of a head
+    fn move_obstacles(&mut self) {
+        if !self.field.changes() {
+            return;
+        }
+        let since_last_step = self.ticks % PATROL_PACE;
+        if since_last_step == 0 {
+            // the gates are in the items so the patrols never get stuck in a closing one
+            let mut blocked = self.item_positions();
+            blocked.extend(self.cells_ahead(1));
+            for index in 0..self.field.patrols.len() {
+                let patrol = &self.field.patrols[index];
+                let (from, step) = (patrol.position(), patrol.next_step());
+                let to = patrol.path[step];
+                if check_if_occupied_by_obstacle_or_snake(to, &self.players, &self.field)
+                    || blocked.contains(&to)
+                {
+                    // it tries the other way next time
+                    let patrol = &mut self.field.patrols[index];
+                    patrol.forward = !patrol.forward;
+                    continue;
+                }
+                self.field.remove_obstacle(from);
+                self.field.add_obstacle(to);
+                self.field.patrols[index].go_to(step);
+            }
+        }
+
+        let mut in_the_way: Vec<(u32, u32)> = self
+            .apples
+            .iter()
+            .map(|apple| apple.position)
+            .chain(self.power_ups.iter().map(|item| item.position))
+            .collect();
+        in_the_way.extend(self.cells_ahead(1));
+        for index in 0..self.field.gates.len() {
+            let gate = &self.field.gates[index];
+            if gate.switches > self.ticks {
+                continue;
+            }
+            let closed = !gate.closed;
+            // a gate waits with closing until nothing is in it
+            let is_blocked = closed
+                && gate.cells.iter().any(|cell| {
+                    in_the_way.contains(cell)
+                        || check_if_occupied_by_obstacle_or_snake(*cell, &self.players, &self.field)
+                });
+            if is_blocked {
+                self.field.gates[index].switches = self.ticks + 1;
+                continue;
+            }
+            let cells = gate.cells.clone();
+            for cell in cells {
+                match closed {
+                    true => self.field.add_obstacle(cell),
+                    false => self.field.remove_obstacle(cell),
+                }
+            }
+            let gate = &mut self.field.gates[index];
+            gate.closed = closed;
+            gate.switches = self.ticks + GATE_PERIOD;
+        }
+
+        if self.field.growing_obstacles == 0 {
+            return;
+        }
+        let points: usize = self.players.iter().map(|player| player.points).sum();
+        let due = points / self.field.growing_obstacles as usize;
+        while (self.grown_obstacles as usize) < due {
+            let mut blocked = self.item_positions();
+            blocked.extend(self.cells_ahead(SAFE_DISTANCE));
+            if let Some(place) =
+                random_free_place(&mut self.rng, &self.players, &self.field, &blocked)
+            {
+                self.field.add_obstacle(place);
+            }
+            self.grown_obstacles += 1;
+        }
+    }
+
+    // the kinds are only rolled when they are turned on so games without them play out like
+    // they always did, one normal apple besides the one being replaced is always left
+    fn next_apple_kind(&mut self, replacing: Option<usize>) -> AppleKind {
+        let has_normal = self
+            .apples
+            .iter()
+            .enumerate()
+            .any(|(index, apple)| Some(index) != replacing && apple.kind == AppleKind::Normal);
+        if !self.apple_kinds || !has_normal {
+            return AppleKind::Normal;
+        }
+        match self.rng.gen_range(0..10) {
+            7 => AppleKind::Golden,
+            8 => AppleKind::Poison,
+            9 => AppleKind::Rotten,
+            _ => AppleKind::Normal,
         }
     }
+
+    fn set_lifetime(&mut self, index: usize) {
+        let apple = &mut self.apples[index];
+        apple.expires = match apple.kind {
+            AppleKind::Golden => Some(self.ticks + GOLDEN_LIFETIME),
+            _ => None,
+        };
+    }
+
+    // an apple that was eaten or went away shows up somewhere else, maybe as another kind
+    fn replace_apple(&mut self, index: usize) {
+        self.apples[index].kind = self.next_apple_kind(Some(index));
+        let items = self.item_positions();
+        if let Some((x, y)) = random_free_place(&mut self.rng, &self.players, &self.field, &items) {
+            self.apples[index].place(x, y);
+        }
+        self.set_lifetime(index);
+    }
+
+    // a magnet moves the closest apple worth eating one cell closer to the head if that cell is free
+    fn pull_apple(&mut self) {
+        let heads: Vec<(u32, u32)> = self
+            .players
+            .iter()
+            .filter(|player| player.alive && player.effect(PowerUpKind::Magnet).is_some())
+            .map(|player| player.snake.body[0].get().position)
+            .collect();
+        let Some((head, pulled)) = heads
+            .into_iter()
+            .flat_map(|head| {
+                self.apples
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, apple)| apple.kind.is_food())
+                    .map(move |(index, _)| (head, index))
+            })
+            .min_by_key(|(head, index)| distance(*head, self.apples[*index].position))
+        else {
+            return;
+        };
+        let apple = self.apples[pulled].position;
+        let (dx, dy) = (head.0.abs_diff(apple.0), head.1.abs_diff(apple.1));
+        // the apple is only ever under a head when the field is full
+        if dx + dy > MAGNET_RANGE || dx + dy == 0 {
+            return;
+        }
+        let toward = |from: u32, to: u32| if to > from { from + 1 } else { from - 1 };
+        let next = if dx >= dy {
+            (toward(apple.0, head.0), apple.1)
+        } else {
+            (apple.0, toward(apple.1, head.1))
+        };
+        let is_taken = check_if_occupied_by_obstacle_or_snake(next, &self.players, &self.field)
+            || self.item_positions().contains(&next);
+        if !is_taken {
+            self.apples[pulled].place(next.0, next.1);
+        }
+    }
+
+    fn wear_off_effects(&mut self) {
+        for player in &mut self.players {
+            for effect in &mut player.effects {
+                effect.remaining = effect.remaining.saturating_sub(1);
+            }
+            player.effects.retain(|effect| effect.remaining > 0);
+            player.reversed = player.reversed.saturating_sub(1);
+        }
+        let ticks = self.ticks;
+        self.power_ups.retain(|item| item.expires > ticks);
+        for index in 0..self.apples.len() {
+            if self.apples[index]
+                .expires
+                .is_some_and(|expires| expires <= ticks)
+            {
+                self.replace_apple(index);
+            }
+        }
+    }
+
+    fn spawn_power_up(&mut self) {
+        let since_last_spawn = self.ticks % POWER_UP_INTERVAL;
+        if !self.spawns_power_ups || since_last_spawn > 0 || self.power_ups.len() >= MAX_POWER_UPS {
+            return;
+        }
+        let kind = PowerUpKind::ALL[self.rng.gen_range(0..PowerUpKind::ALL.len())];
+        let items = self.item_positions();
+        if let Some(position) = random_free_place(&mut self.rng, &self.players, &self.field, &items)
+        {
+            self.power_ups.push(PowerUp {
+                kind,
+                position,
+                expires: self.ticks + POWER_UP_LIFETIME,
+            });
+        }
+    }
+
+    // every few ticks the arena loses its outermost ring, snakes caught in it crash and whatever
+    // else was there goes away or shows up somewhere else
+    fn shrink_arena(&mut self) {
+        let Some(index) = self.field.next_ring() else {
+            return;
+        };
+        let since_last_ring = self.ticks % self.field.shrinking_arena as u64;
+        if since_last_ring > 0 {
+            return;
+        }
+        let ring = self.field.ring(index);
+        self.field.closed_rings += 1;
+        for cell in &ring {
+            self.field.add_obstacle(*cell);
+        }
+        for player in self.players.iter_mut().filter(|player| player.alive) {
+            let caught = player
+                .snake
+                .body
+                .iter()
+                .any(|body_part| ring.contains(&body_part.get().position));
+            if caught {
+                player.alive = false;
+                self.events.push(GameEvent::Collided);
+            }
+        }
+        self.field
+            .portals
+            .retain(|(first, second)| !ring.contains(first) && !ring.contains(second));
+        for gate in &mut self.field.gates {
+            gate.cells.retain(|cell| !ring.contains(cell));
+        }
+        self.field.gates.retain(|gate| !gate.cells.is_empty());
+        self.power_ups.retain(|item| !ring.contains(&item.position));
+        for index in 0..self.apples.len() {
+            if ring.contains(&self.apples[index].position) {
+                self.replace_apple(index);
+            }
+        }
+    }
+
+    // a single player game is over with its snake, a game of several once one snake is left
+    fn check_for_last_snake_standing(&mut self) {
+        let alive = self.players.iter().filter(|player| player.alive).count();
+        let is_over = if self.players.len() == 1 {
+            alive == 0
+        } else {
+            alive <= 1
+        };
+        if !is_over {
+            return;
+        }
+        self.state = GameState::GameOver;
+        if alive == 1 {
+            self.events.push(GameEvent::Won);
+        }
+    }
+
+    // the player that won, none while the game goes on or when nobody did
+    pub fn winner(&self) -> Option<usize> {
+        if self.state != GameState::GameOver {
+            return None;
+        }
+        if self.players.len() == 1 {
+            let is_full =
+                self.mode != Mode::Endless && no_free_space_available(&self.players, &self.field);
+            return (is_full || self.reached_goal(0)).then_some(0);
+        }
+        // a full field can leave several snakes, the longest one wins then
+        self.players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.alive)
+            .max_by_key(|(_, player)| player.score())
+            .map(|(index, _)| index)
+    }
+
+    // the frontends decide how to show the end of the game
+    pub fn has_won(&self) -> bool {
+        self.winner() == Some(0)
+    }
+
+    pub fn score(&self) -> usize {
+        self.players[0].score()
+    }
 }
 
 impl Default for SnakeGame {
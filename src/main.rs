@@ -1,117 +1,705 @@
+#[cfg(feature = "sdl")]
+use audio::{Audio, Volume};
+#[cfg(feature = "sdl")]
+use capture::GifRecorder;
 use clap::{command, Arg};
+#[cfg(feature = "sdl")]
+use gamepad::Gamepads;
+#[cfg(feature = "sdl")]
+use render::draw_game;
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
 use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::{Canvas, Texture, TextureCreator};
-use sdl2::video::Window;
-use sdl2::video::WindowContext;
-use snake_game_sdl::model::Direction;
+#[cfg(feature = "sdl")]
+use sdl2::render::TextureCreator;
+use snake_game_sdl::bot::ExternalController;
+#[cfg(feature = "sdl")]
+use snake_game_sdl::camera::Camera;
+use snake_game_sdl::campaign::{self, Level, Progress};
+use snake_game_sdl::controller::{Controller, HumanController, ReplayController};
+use snake_game_sdl::ghost::{self, Ghost};
+use snake_game_sdl::leaderboard::{self, Leaderboard};
+use snake_game_sdl::mode::Mode;
+use snake_game_sdl::model::{Direction, Edges};
+use snake_game_sdl::net::{Board, Broadcaster, NetClient, Server, ServerConfig};
+use snake_game_sdl::palette::Palette;
+use snake_game_sdl::replay::Replay;
+#[cfg(feature = "sdl")]
+use snake_game_sdl::rewind::Rewind;
+use snake_game_sdl::save::Save;
 use snake_game_sdl::*;
-use snake_game_sdl::{PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
 use std::env;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
+#[cfg(feature = "sdl")]
+use std::time::{SystemTime, UNIX_EPOCH};
 
+// everything drawn or played through sdl, a build without the sdl feature only has the terminal
+#[cfg(feature = "sdl")]
+mod audio;
+#[cfg(feature = "sdl")]
+mod capture;
+#[cfg(feature = "sdl")]
+mod gamepad;
+#[cfg(feature = "sdl")]
+mod render;
+mod tui;
+
+#[cfg(feature = "sdl")]
 const WINDOW_TITLE: &str = "Snake Game";
-const SQUARE_SIZE: u32 = 16;
-const PIXEL_PADDING: u32 = 3;
+// used when the display size can't be queried
+#[cfg(feature = "sdl")]
+const FALLBACK_MAX_WINDOW: (u32, u32) = (1280, 720);
+const MIN_ZOOM: u32 = 4;
+const MAX_ZOOM: u32 = 64;
+const ONLINE_POLL_TIME: Duration = Duration::from_millis(5);
+// until a campaign level is picked
+const DEFAULT_TICKSPEED: u32 = 150;
 
-struct WindowProperties {
-    window_height: u32,
-    window_width: u32,
-    window_title: String,
+// the options shared by every frontend, the sound and the recording need sdl
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct GameSettings {
+    width: u32,
+    height: u32,
+    border: bool,
+    obstacles: u32,
+    tickspeed: u32,
+    deadzone: u32,
+    record: Option<PathBuf>,
+    volume: u32,
+    music_volume: u32,
+    mute: bool,
+    palette: Palette,
+    seed: Option<u64>,
+    // when set every game is this replay played back
+    replay: Option<Replay>,
+    save_replay: Option<PathBuf>,
+    ai: Option<String>,
+    // a command that starts a bot speaking the json protocol
+    bot: Option<String>,
+    bot_time_limit: u32,
+    players: usize,
+    // the bot playing every snake but the first
+    rival_ai: Option<String>,
+    // the port spectators can watch the game on
+    broadcast: Option<u16>,
+    power_ups: bool,
+    apples: u32,
+    apple_kinds: bool,
+    edges: Edges,
+    // pairs of portals
+    portals: u32,
+    patrols: u32,
+    gates: u32,
+    // one more obstacle every this many points, 0 for never
+    growing_obstacles: u32,
+    // a ring of walls closes every this many ticks, 0 for never
+    shrinking_arena: u32,
+    mode: Mode,
+    // the mode results go on the leaderboard with, the board, the speed and the rules of the game
+    // are added to it. none for games that don't count
+    leaderboard: Option<String>,
+    scores: PathBuf,
+    // the campaign level every game is on, it brings its own board
+    level: Option<Level>,
+    // the game left halfway that the first game picks up
+    resume: Option<Save>,
+    save_file: PathBuf,
+    // where the best runs are kept when racing them
+    ghosts: Option<PathBuf>,
+}
+
+// the levels of the campaign and how far the player got, the level select is up until a level
+// is started
+struct Campaign {
+    levels: Vec<Level>,
+    progress: Progress,
+    picked: usize,
+    selecting: bool,
 }
 
-fn is_within_padded_area(x: u32, y: u32, small: bool) -> bool {
-    let mut padding = PIXEL_PADDING;
-    if small {
-        padding = PIXEL_PADDING + 2;
+impl Campaign {
+    // the level select starts on the first level that wasn't won yet
+    fn load(path: &Path) -> Result<Self, String> {
+        let levels = campaign::levels();
+        let progress = Progress::load(path)?;
+        let picked = levels
+            .iter()
+            .position(|level| !progress.is_completed(level))
+            .unwrap_or(0);
+        Ok(Self {
+            levels,
+            progress,
+            picked,
+            selecting: true,
+        })
+    }
+
+    fn level(&self) -> &Level {
+        &self.levels[self.picked]
+    }
+
+    fn is_unlocked(&self) -> bool {
+        self.progress.is_unlocked(&self.levels, self.picked)
+    }
+
+    // false when there is no level that way
+    fn move_pick(&mut self, difference: isize) -> bool {
+        match self.picked.checked_add_signed(difference) {
+            Some(picked) if picked < self.levels.len() => {
+                self.picked = picked;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // one line about a level for the level select
+    fn describe(&self, index: usize) -> String {
+        let level = &self.levels[index];
+        let state = if self.progress.is_completed(level) {
+            " (won)"
+        } else if !self.progress.is_unlocked(&self.levels, index) {
+            " (locked)"
+        } else {
+            ""
+        };
+        format!(
+            "{}. {}: {}{}",
+            index + 1,
+            level.name,
+            level.describe_goal(),
+            state
+        )
+    }
+
+    #[cfg(feature = "sdl")]
+    fn menu(&self) -> String {
+        let mut lines = vec!["Campaign, pick a level with <W/S> and press <Enter>!".to_string()];
+        for index in 0..self.levels.len() {
+            let cursor = if index == self.picked { ">" } else { " " };
+            lines.push(format!("{} {}", cursor, self.describe(index)));
+        }
+        lines.join("\n")
     }
-    let padded_start = padding;
-    let padded_end = SQUARE_SIZE - padding;
 
-    (x >= padded_start && x <= padded_end) && (y >= padded_start && y <= padded_end)
+    // saves the progress once a level is won and tells what comes next
+    fn finish(&mut self, game: &SnakeGame) -> String {
+        if !game.has_won() {
+            return "Press <R> to try again or <L> for the level select!".to_string();
+        }
+        if let Err(e) = self.progress.complete(&self.levels[self.picked]) {
+            eprintln!("Failed to save the progress: {}", e);
+        }
+        match self.picked + 1 == self.levels.len() {
+            true => "That was the last level! Press <L> for the level select!".to_string(),
+            false => {
+                "Level won! Press <R> for the next level or <L> for the level select!".to_string()
+            }
+        }
+    }
+
+    // after a win the restart goes on to the next level
+    fn restart(&mut self, game: &SnakeGame) {
+        if game.state == GameState::GameOver && game.has_won() {
+            self.move_pick(1);
+        }
+    }
 }
 
-fn dummy_texture<'a>(
-    canvas: &mut Canvas<Window>,
-    texture_creator: &'a TextureCreator<WindowContext>,
-) -> Result<(Texture<'a>, Texture<'a>, Texture<'a>), String> {
-    enum TextureColors {
-        Red,
-        Green,
-        White,
-    }
-
-    let mut snake_body = texture_creator
-        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
-        .map_err(|e| e.to_string())?;
-    let mut apple = texture_creator
-        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
-        .map_err(|e| e.to_string())?;
-    let mut collision = texture_creator
-        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
-        .map_err(|e| e.to_string())?;
-
-    {
-        let textures = [
-            (&mut snake_body, TextureColors::Green),
-            (&mut apple, TextureColors::Red),
-            (&mut collision, TextureColors::White),
-        ];
-
-        // to maybe add some margins to make it prettier
-        canvas
-            .with_multiple_texture_canvas(textures.iter(), |texture_canvas, user_context| {
-                texture_canvas.set_draw_color(Color::RGB(0, 0, 0));
-                texture_canvas.clear();
-                match *user_context {
-                    TextureColors::Green => {
-                        // we draw pixel by pixel the snake body (it's just a rectangle)
-                        let small = false;
-                        for y in 0..SQUARE_SIZE {
-                            for x in 0..SQUARE_SIZE {
-                                texture_canvas.set_draw_color(Color::RGB(0, 255, 0));
-                                if is_within_padded_area(x, y, small) {
-                                    texture_canvas
-                                        .draw_point(Point::new(x as i32, y as i32))
-                                        .expect("failed to draw point");
-                                }
-                            }
-                        }
-                    }
+// who steers every snake, the keys and gamepads go to the human controllers and are only used
+// for the snakes without a bot or a replay
+struct Pilots {
+    humans: Vec<HumanController>,
+    autopilots: Vec<Option<Box<dyn Controller>>>,
+}
 
-                    TextureColors::Red => {
-                        // we draw pixel by pixel the apple (it's just a rectangle)
-                        let small = true;
-                        for y in 0..SQUARE_SIZE {
-                            for x in 0..SQUARE_SIZE {
-                                texture_canvas.set_draw_color(Color::RGB(255, 0, 0));
-                                if is_within_padded_area(x, y, small) {
-                                    texture_canvas
-                                        .draw_point(Point::new(x as i32, y as i32))
-                                        .expect("failed to draw point");
-                                }
-                            }
-                        }
-                    }
+impl Pilots {
+    // nobody has to press a key first so the game starts right away
+    fn is_automatic(&self) -> bool {
+        self.autopilots.iter().all(|autopilot| autopilot.is_some())
+    }
 
-                    TextureColors::White => {
-                        // we draw pixel by pixel the collition (it's just a rectangle)
-                        for y in 0..SQUARE_SIZE {
-                            for x in 0..SQUARE_SIZE {
-                                texture_canvas.set_draw_color(Color::RGB(255, 255, 255));
-                                texture_canvas
-                                    .draw_point(Point::new(x as i32, y as i32))
-                                    .expect("failed to draw point");
-                            }
-                        }
-                    }
+    // a key press starts the game, the turn itself waits for the next tick
+    fn steer(&mut self, game: &mut SnakeGame, player: usize, direction: Direction) {
+        if let Some(human) = self.humans.get_mut(player) {
+            game.resume();
+            human.push(direction);
+        }
+    }
+
+    // keys pressed before a rewind were meant for the game that was there then
+    fn clear_turns(&mut self) {
+        for human in &mut self.humans {
+            human.clear();
+        }
+    }
+
+    fn drive(&mut self, game: &mut SnakeGame) {
+        for (player, autopilot) in self.autopilots.iter_mut().enumerate() {
+            match autopilot {
+                Some(controller) => game.drive_player(player, controller.as_mut()),
+                None => game.drive_player(player, &mut self.humans[player]),
+            };
+        }
+    }
+}
+
+// where the turns come from, the pilots on this computer or the server of an online game, a local
+// game can be streamed to spectators as it is played
+enum Session {
+    Local(Pilots, Option<Broadcaster>),
+    Online(Box<NetClient>),
+}
+
+impl Session {
+    fn steer(&mut self, game: &mut SnakeGame, player: usize, direction: Direction) {
+        match self {
+            Session::Local(pilots, _) => pilots.steer(game, player, direction),
+            // every key layout steers our own snake online
+            Session::Online(client) => client.turn(direction),
+        }
+    }
+
+    // one tick of a local game, online the game moves on as the server says
+    fn advance(&mut self, game: &mut SnakeGame) {
+        match self {
+            Session::Local(pilots, broadcaster) => {
+                pilots.drive(game);
+                game.update();
+                if let Some(broadcaster) = broadcaster {
+                    broadcaster.publish(game);
+                }
+            }
+            Session::Online(client) => client.sync(game),
+        }
+    }
+
+    // only local games can be saved, online the game belongs to the server
+    fn is_local(&self) -> bool {
+        matches!(self, Session::Local(..))
+    }
+
+    fn clear_turns(&mut self) {
+        if let Session::Local(pilots, _) = self {
+            pilots.clear_turns();
+        }
+    }
+
+    // online the server keeps the clock so nobody can pause the game for everyone
+    fn toggle_pause(&self, game: &mut SnakeGame) {
+        if let Session::Local(..) = self {
+            game.toggle_pause();
+        }
+    }
+
+    // a new game right away, online it tells the server we are ready for the next one
+    fn restart(&mut self, settings: &GameSettings, game: &mut SnakeGame) -> Result<(), String> {
+        match self {
+            Session::Local(pilots, _) => {
+                *game = settings.new_game();
+                *pilots = settings.new_pilots(game)?;
+                if pilots.is_automatic() {
+                    game.resume();
+                }
+            }
+            Session::Online(client) => client.toggle_ready(),
+        }
+        Ok(())
+    }
+
+    // how long a tick takes without power-ups, the clocks of the modes go by it
+    fn base_tick(&self, settings: &GameSettings) -> Duration {
+        match self {
+            Session::Local(..) => Duration::from_millis(settings.tickspeed as u64),
+            Session::Online(client) => client.tick,
+        }
+    }
+
+    // how long a tick of the game takes right now, power-ups can change it
+    fn tick_length(&self, settings: &GameSettings, game: &SnakeGame) -> Duration {
+        self.base_tick(settings).mul_f64(game.pace())
+    }
+
+    // how long to wait between two ticks, online it is checked often so the ticks show up on time
+    fn tick_time(&self, settings: &GameSettings, game: &SnakeGame) -> Duration {
+        match self {
+            Session::Local(..) => self.tick_length(settings, game),
+            Session::Online(_) => ONLINE_POLL_TIME,
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        match self {
+            Session::Local(..) => None,
+            Session::Online(client) => Some(client.status()),
+        }
+    }
+}
+
+// the first snake still in the game, the one the camera follows
+fn leading_head(game: &SnakeGame) -> (u32, u32) {
+    let player = game
+        .players
+        .iter()
+        .find(|player| player.alive)
+        .unwrap_or(&game.players[0]);
+    player.snake.body[0].get().position
+}
+
+// the scores of everyone, or just the number in a single player game
+fn scores(game: &SnakeGame) -> String {
+    if game.players.len() == 1 {
+        return game.score().to_string();
+    }
+    let scores: Vec<String> = game
+        .players
+        .iter()
+        .enumerate()
+        .map(|(index, player)| format!("P{} {}", index + 1, player.score()))
+        .collect();
+    scores.join("  ")
+}
+
+// the time that matters in the mode, what is left of a time attack and what went by otherwise
+fn clock(game: &SnakeGame, tick: Duration) -> String {
+    let seconds = |ticks: u64| (tick * ticks as u32).as_secs();
+    match game.mode {
+        Mode::Classic => String::new(),
+        Mode::TimeAttack { ticks } => {
+            let left = tick * ticks.saturating_sub(game.ticks) as u32;
+            format!("{}s left", left.as_secs_f64().ceil())
+        }
+        Mode::AppleSprint { apples } => {
+            let eaten = game.players.iter().map(|player| player.eaten).max();
+            format!(
+                "{}/{} apples {}s",
+                eaten.unwrap_or(0),
+                apples,
+                seconds(game.ticks)
+            )
+        }
+        Mode::Endless | Mode::Exit { .. } => format!("{}s", seconds(game.ticks)),
+    }
+}
+
+// the power-ups that are still going and rotten apples with the seconds they have left
+fn effects(game: &SnakeGame, tick: Duration) -> String {
+    let mut effects = vec![];
+    for (index, player) in game.players.iter().enumerate() {
+        let mut remaining: Vec<(String, u64)> = player
+            .effects
+            .iter()
+            .map(|effect| match effect.kind {
+                model::PowerUpKind::Multiplier => {
+                    (format!("x{}", player.multiplier()), effect.remaining)
                 }
+                kind => (kind.name().to_string(), effect.remaining),
             })
-            .map_err(|e| e.to_string())?;
+            .collect();
+        if player.reversed > 0 {
+            remaining.push(("reversed".to_string(), player.reversed));
+        }
+        for (name, ticks) in remaining {
+            let seconds = (tick * ticks as u32).as_secs_f64().ceil();
+            match game.players.len() {
+                1 => effects.push(format!("{} {}s", name, seconds)),
+                _ => effects.push(format!("P{} {} {}s", index + 1, name, seconds)),
+            }
+        }
+    }
+    effects.join("  ")
+}
+
+// what to tell the players once the game is over
+fn game_over_message(game: &SnakeGame, tick: Duration) -> String {
+    if game.players.len() == 1 {
+        if let Some(reason) = &game.players[0].disqualified {
+            return format!("The bot was disqualified, {}!", reason);
+        }
+        let seconds = |ticks: u64| format!("{:.1}s", (tick * ticks as u32).as_secs_f64());
+        let score = game.score();
+        return match (game.mode, game.has_won()) {
+            (Mode::Classic, true) => "You Win!".to_string(),
+            (Mode::Classic, false) => "Game Over!".to_string(),
+            (Mode::TimeAttack { .. }, true) => format!("Time's up! {} points!", score),
+            (Mode::TimeAttack { ticks }, false) => format!(
+                "Game Over! {} points with {} left!",
+                score,
+                seconds(ticks.saturating_sub(game.ticks))
+            ),
+            (Mode::AppleSprint { apples }, true) => {
+                format!("{} apples in {}!", apples, seconds(game.ticks))
+            }
+            (Mode::AppleSprint { apples }, false) => {
+                format!("Game Over! {} of {} apples!", game.players[0].eaten, apples)
+            }
+            (Mode::Endless, _) => {
+                format!("Game Over! {} points in {}!", score, seconds(game.ticks))
+            }
+            (Mode::Exit { .. }, true) => format!("Made it out in {}!", seconds(game.ticks)),
+            (Mode::Exit { .. }, false) => "Game Over!".to_string(),
+        };
+    }
+    match game.winner() {
+        Some(player) => format!("Player {} wins!", player + 1),
+        None => "Nobody survived!".to_string(),
+    }
+}
+
+impl GameSettings {
+    fn new_game(&self) -> SnakeGame {
+        if let Some(replay) = &self.replay {
+            return replay.new_game();
+        }
+        if let Some(level) = &self.level {
+            return level.new_game(self.seed.unwrap_or_else(rand::random));
+        }
+        let obstacles = (self.obstacles > 0, self.obstacles);
+        let game = match self.seed {
+            Some(seed) => {
+                SnakeGame::from_seed(self.width, self.height, self.border, obstacles, seed)
+            }
+            None => SnakeGame::from(self.width, self.height, self.border, obstacles),
+        };
+        game.with_players(self.players)
+            .with_power_ups(self.power_ups)
+            .with_apples(self.apples as usize, self.apple_kinds)
+            .with_edges(self.edges)
+            .with_portals(self.portals as usize)
+            .with_moving_obstacles(self.patrols as usize, self.gates as usize)
+            .with_growing_obstacles(self.growing_obstacles)
+            .with_shrinking_arena(self.shrinking_arena)
+            .with_mode(self.mode)
+    }
+
+    // the board and the speed come from the level
+    fn pick_level(&mut self, level: &Level) {
+        self.width = level.size.0;
+        self.height = level.size.1;
+        self.tickspeed = level.tick;
+        self.level = Some(level.clone());
+    }
+
+    fn new_session(&self, online: Option<NetClient>) -> Result<(SnakeGame, Session), String> {
+        if let Some(client) = online {
+            return Ok((client.new_game(), Session::Online(Box::new(client))));
+        }
+        let mut game = match &self.resume {
+            Some(save) => save.new_game(),
+            None => self.new_game(),
+        };
+        let pilots = self.new_pilots(&game)?;
+        if pilots.is_automatic() {
+            game.resume();
+        }
+        let broadcaster = match self.broadcast {
+            Some(port) => Some(Broadcaster::bind(port)?),
+            None => None,
+        };
+        Ok((game, Session::Local(pilots, broadcaster)))
+    }
+
+    fn new_pilots(&self, game: &SnakeGame) -> Result<Pilots, String> {
+        let mut autopilots = vec![self.new_autopilot(game)?];
+        for player in 1..game.players.len() {
+            let autopilot: Option<Box<dyn Controller>> = match (&self.replay, &self.rival_ai) {
+                (Some(replay), _) => Some(Box::new(ReplayController::from(&replay.moves[player]))),
+                (None, Some(name)) => Some(ai::by_name(name, &game.field)?),
+                (None, None) => None,
+            };
+            autopilots.push(autopilot);
+        }
+        Ok(Pilots {
+            humans: (0..game.players.len())
+                .map(|_| HumanController::new())
+                .collect(),
+            autopilots,
+        })
+    }
+
+    // whatever steers the first snake instead of the keyboard
+    fn new_autopilot(&self, game: &SnakeGame) -> Result<Option<Box<dyn Controller>>, String> {
+        if let Some(replay) = &self.replay {
+            return Ok(Some(Box::new(ReplayController::from(&replay.moves[0]))));
+        }
+        if let Some(command) = &self.bot {
+            let time_limit = Duration::from_millis(self.bot_time_limit as u64);
+            return Ok(Some(Box::new(ExternalController::spawn(
+                command, time_limit,
+            )?)));
+        }
+        match &self.ai {
+            Some(name) => ai::by_name(name, &game.field).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn save_replay(&self, game: &SnakeGame) {
+        if let Some(path) = &self.save_replay {
+            if let Err(e) = Replay::from_game(game).save(path) {
+                eprintln!("Failed to save the replay: {}", e);
+            }
+        }
+    }
+
+    // a game that was quit halfway is still worth a replay once a turn was taken
+    fn save_unfinished_replay(&self, game: &SnakeGame) {
+        if was_left_halfway(game) {
+            self.save_replay(game);
+        }
+    }
+
+    // the best run on the board of the game, none when there is none yet or nobody races it
+    fn new_ghost(&self, game: &SnakeGame) -> Option<Ghost> {
+        let folder = self.ghosts.as_ref()?;
+        match Ghost::load(folder, game) {
+            Ok(ghost) => ghost,
+            Err(e) => {
+                eprintln!("Failed to load the ghost: {}", e);
+                None
+            }
+        }
+    }
+
+    // a finished game that beat the best run on its board is the ghost from now on
+    fn offer_ghost(&self, game: &SnakeGame) -> Option<String> {
+        let folder = self.ghosts.as_ref()?;
+        match ghost::offer(folder, game) {
+            Ok(true) => {
+                Some("Your best run on this board, the ghost plays it from now on!".to_string())
+            }
+            Ok(false) => None,
+            Err(e) => {
+                eprintln!("Failed to save the ghost: {}", e);
+                None
+            }
+        }
+    }
+
+    // a ranked game can't be taken back and neither can one that is played back, watched or
+    // online. a rewound game of the other modes is kept off the leaderboard instead
+    fn can_rewind(&self, session: &Session) -> bool {
+        session.is_local()
+            && !self.mode.is_ranked()
+            && self.replay.is_none()
+            && self.broadcast.is_none()
+    }
+
+    // the rules and the speed of a saved game are kept for the games after it too
+    fn resume_from(&mut self, save: Save) {
+        let replay = &save.replay;
+        self.width = replay.width;
+        self.height = replay.height;
+        self.border = replay.has_border;
+        self.obstacles = replay.obstacles;
+        self.players = replay.moves.len();
+        self.power_ups = replay.power_ups;
+        self.apples = replay.apples;
+        self.apple_kinds = replay.apple_kinds;
+        self.edges = replay.edges;
+        self.portals = replay.portals;
+        self.patrols = replay.patrols;
+        self.gates = replay.gates;
+        self.growing_obstacles = replay.growing_obstacles;
+        self.shrinking_arena = replay.shrinking_arena;
+        self.mode = replay.mode;
+        self.tickspeed = save.tickspeed;
+        self.resume = Some(save);
+    }
+
+    // what to tell the player after saving the game for --resume
+    fn save_game(&self, game: &SnakeGame) -> String {
+        match Save::from_game(game, self.tickspeed).save(&self.save_file) {
+            Ok(()) => format!(
+                "Saved the game to {}, --resume picks it up again",
+                self.save_file.display()
+            ),
+            Err(e) => format!("Failed to save the game: {}", e),
+        }
+    }
+
+    // quitting in the middle of a game saves it so nothing is lost
+    fn save_unfinished_game(&self, game: &SnakeGame, session: &Session) -> Option<String> {
+        match session.is_local() && was_left_halfway(game) {
+            true => Some(self.save_game(game)),
+            false => None,
+        }
+    }
+
+    // puts a finished game on the leaderboard of its mode, board, speed and rules and tells how it
+    // compares
+    fn record_result(&self, game: &SnakeGame) -> Option<String> {
+        let mode = self.leaderboard.as_ref()?;
+        // taking back mistakes isn't fair against the games that didn't
+        if !game.rewinds.is_empty() {
+            return Some("Rewound games don't go on the leaderboard".to_string());
+        }
+        let key = &leaderboard::key(mode, self.tickspeed, game);
+        let tick = Duration::from_millis(self.tickspeed as u64);
+        let lower_is_better = game.mode.lower_is_better();
+        let result = match game.mode {
+            // a sprint that wasn't finished has no time to put up
+            Mode::AppleSprint { .. } if !game.has_won() => return None,
+            Mode::AppleSprint { .. } => (tick * game.ticks as u32).as_millis() as u64,
+            _ => game.score() as u64,
+        };
+        let recorded = Leaderboard::load(&self.scores).and_then(|mut leaderboard| {
+            let place = leaderboard.add(key, result, lower_is_better)?;
+            Ok((place, leaderboard))
+        });
+        let (place, leaderboard) = match recorded {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                eprintln!("Failed to save the result: {}", e);
+                return None;
+            }
+        };
+        let best: Vec<String> = leaderboard
+            .top(key, lower_is_better, 5)
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| match lower_is_better {
+                true => format!("{}. {:.1}s", index + 1, entry.result as f64 / 1000.0),
+                false => format!("{}. {}", index + 1, entry.result),
+            })
+            .collect();
+        let new_best = if place == 1 { "New best! " } else { "" };
+        Some(format!("{}Best {}: {}", new_best, key, best.join("  ")))
+    }
+
+    #[cfg(feature = "sdl")]
+    fn open_audio(&self, sdl_context: &sdl2::Sdl) -> Audio {
+        Audio::open(
+            sdl_context,
+            Volume {
+                effects: self.volume,
+                music: self.music_volume,
+                muted: self.mute,
+            },
+            self.tickspeed,
+        )
+    }
+
+    #[cfg(feature = "sdl")]
+    fn new_recorder(
+        &self,
+        game: &SnakeGame,
+    ) -> Result<Option<GifRecorder>, Box<dyn std::error::Error>> {
+        match &self.record {
+            Some(path) => Ok(Some(GifRecorder::create(path, game, self.tickspeed)?)),
+            None => Ok(None),
+        }
     }
-    Ok((snake_body, apple, collision))
+}
+
+// the game isn't over and somebody took a turn in it
+fn was_left_halfway(game: &SnakeGame) -> bool {
+    let was_played = game.players.iter().any(|player| !player.moves.is_empty());
+    game.state != GameState::GameOver && was_played
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -123,59 +711,591 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Arg::new("width")
                 .long("width")
                 .help("Sets the width of the game board by grid square size.")
-                .required(true)
+                .required_unless_present_any(["join", "watch", "campaign", "resume"])
                 .value_parser(clap::value_parser!(u32).range(21..=1000)),
         )
         .arg(
             Arg::new("height")
                 .long("height")
                 .help("Sets the height of the game board by grid square size.")
-                .required(true)
+                .required_unless_present_any(["join", "watch", "campaign", "resume"])
                 .value_parser(clap::value_parser!(u32).range(21..=1000)),
         )
         .arg(
             Arg::new("border")
                 .long("border")
                 .help("Sets the border of the game board by grid square size.")
-                .required(true)
+                .required_unless_present_any(["campaign", "resume"])
                 .value_parser(clap::value_parser!(bool)),
         )
         .arg(
             Arg::new("obstacles")
                 .long("obstacles")
                 .help("Sets the number of obstacles generated randomly throughout the board.")
-                .required(true)
+                .required_unless_present_any(["campaign", "resume"])
                 .value_parser(clap::value_parser!(u32).range(0..=100)),
         )
         .arg(
             Arg::new("tickspeed")
                 .long("tickspeed")
                 .help("Sets the tick speed to a value in milliseconds.")
-                .required(true)
+                .required_unless_present_any(["campaign", "resume"])
                 .value_parser(clap::value_parser!(u32).range(10..=5000)),
         )
+        .arg(
+            Arg::new("zoom")
+                .long("zoom")
+                .help("Sets the size of a grid square in pixels.")
+                .default_value("16")
+                .value_parser(clap::value_parser!(u32).range(MIN_ZOOM as i64..=MAX_ZOOM as i64)),
+        )
+        .arg(
+            Arg::new("deadzone")
+                .long("deadzone")
+                .help("Sets the percentage of the screen the snake can move in before the camera scrolls.")
+                .default_value("50")
+                .value_parser(clap::value_parser!(u32).range(0..=100)),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .help("Records the whole session as an animated gif to the given file.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("screenshot")
+                .long("screenshot")
+                .help("Saves the starting board as a png to the given file and exits without opening a window.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("volume")
+                .long("volume")
+                .help("Sets the volume of the sound effects in percent.")
+                .default_value("80")
+                .value_parser(clap::value_parser!(u32).range(0..=100)),
+        )
+        .arg(
+            Arg::new("music-volume")
+                .long("music-volume")
+                .help("Sets the volume of the background music in percent.")
+                .default_value("50")
+                .value_parser(clap::value_parser!(u32).range(0..=100)),
+        )
+        .arg(
+            Arg::new("mute")
+                .long("mute")
+                .help("Starts the game without sound.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("palette")
+                .long("palette")
+                .help("Sets the colors of the game, made for color blindness and high contrast.")
+                .default_value("default")
+                .value_parser(Palette::names()),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Sets the seed of the board and the apples so games can be played again.")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("replay")
+                .long("replay")
+                .help("Plays back a replay file, the board settings are taken from the replay.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("save-replay")
+                .long("save-replay")
+                .help("Saves the last game as a replay file when it ends.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("ai")
+                .long("ai")
+                .help("Lets a bot play instead of the keyboard.")
+                .value_parser(ai::NAMES),
+        )
+        .arg(
+            Arg::new("bot")
+                .long("bot")
+                .help("Lets an outside program play, it gets the board as json lines on stdin and answers on stdout.")
+                .conflicts_with("ai"),
+        )
+        .arg(
+            Arg::new("bot-time-limit")
+                .long("bot-time-limit")
+                .help("Sets the milliseconds a bot has to answer before it gets disqualified.")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u32).range(1..=60000)),
+        )
+        .arg(
+            Arg::new("players")
+                .long("players")
+                .help("Sets the number of snakes on the field, the second one plays with the arrow keys and the rest with gamepads.")
+                .default_value("1")
+                .value_parser(clap::value_parser!(u32).range(1..=4)),
+        )
+        .arg(
+            Arg::new("rival-ai")
+                .long("rival-ai")
+                .help("Lets a bot play every snake but the first.")
+                .value_parser(ai::NAMES),
+        )
+        .arg(
+            Arg::new("power-ups")
+                .long("power-ups")
+                .help("Lets power-ups show up on the field: slow-mo, speed, ghost, shrink, score multiplier and magnet.")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("apples")
+                .long("apples")
+                .help("Sets how many apples are on the field at once.")
+                .default_value("1")
+                .value_parser(clap::value_parser!(u32).range(1..=20))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("apple-kinds")
+                .long("apple-kinds")
+                .help("Mixes golden apples worth more, poison apples that shrink the snake and rotten apples that turn the controls around in with the normal ones.")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("edges")
+                .long("edges")
+                .help("Sets what the sides of the board do: wrap, wall or bounce for all of them, torus, cylinder or four of them for top,right,bottom,left.")
+                .default_value("torus")
+                .value_parser(|edges: &str| edges.parse::<Edges>())
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("portals")
+                .long("portals")
+                .help("Sets the number of portal pairs, a snake going into one comes out of the other.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=9))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("patrols")
+                .long("patrols")
+                .help("Sets the number of obstacles walking back and forth along a line.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=9))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("gates")
+                .long("gates")
+                .help("Sets the number of walls that open and close every few seconds, they flash before closing.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=9))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("growing-obstacles")
+                .long("growing-obstacles")
+                .help("Adds an obstacle every time the score goes up by this many points, 0 turns it off.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=100))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("shrinking-arena")
+                .long("shrinking-arena")
+                .help("Closes a ring of walls around the arena every this many ticks, snakes caught in it die, 0 turns it off.")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u32).range(0..=10000))
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("mode")
+                .long("mode")
+                .help("Sets what the game is about: filling the board, the most points in --time-limit, the fastest --sprint-apples apples or going on for as long as you can.")
+                .default_value("classic")
+                .value_parser(["classic", "time-attack", "sprint", "endless"])
+                .conflicts_with_all(["join", "watch", "replay", "campaign", "resume"]),
+        )
+        .arg(
+            Arg::new("time-limit")
+                .long("time-limit")
+                .help("Sets the seconds of a time attack.")
+                .default_value("60")
+                .value_parser(clap::value_parser!(u32).range(10..=3600)),
+        )
+        .arg(
+            Arg::new("sprint-apples")
+                .long("sprint-apples")
+                .help("Sets how many apples a sprint is about.")
+                .default_value("20")
+                .value_parser(clap::value_parser!(u32).range(1..=1000)),
+        )
+        .arg(
+            Arg::new("scores")
+                .long("scores")
+                .help("Sets the file the best results of every mode are kept in.")
+                .default_value("snake-scores.txt")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("campaign")
+                .long("campaign")
+                .help("Plays the campaign, handmade levels that are unlocked one after the other, the board and the speed come from the level.")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["join", "watch", "replay", "host", "broadcast", "ai", "bot", "players", "rival-ai"]),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Sets the file the won campaign levels are kept in.")
+                .default_value("snake-campaign.txt")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help("Picks up the game saved with <F5> or by quitting in the middle of it, the board and the speed come from the save.")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["join", "watch", "replay", "host", "campaign", "players"]),
+        )
+        .arg(
+            Arg::new("save-file")
+                .long("save-file")
+                .help("Sets the file games are saved to and resumed from.")
+                .default_value("snake-save.txt")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("ghost")
+                .long("ghost")
+                .help("Races a see-through snake playing your best run on the same seed and rules, it can't run into anything.")
+                .action(clap::ArgAction::SetTrue)
+                .requires("seed")
+                .conflicts_with_all(["join", "watch", "replay", "host", "ai", "bot", "players", "rival-ai"]),
+        )
+        .arg(
+            Arg::new("ghost-folder")
+                .long("ghost-folder")
+                .help("Sets the folder the best runs for --ghost are kept in.")
+                .default_value("snake-ghosts")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("host")
+                .long("host")
+                .help("Runs an online game on this port and plays in it, the others join with --join.")
+                .value_parser(clap::value_parser!(u16))
+                .conflicts_with_all(["join", "replay", "ai", "bot", "players", "rival-ai"]),
+        )
+        .arg(
+            Arg::new("join")
+                .long("join")
+                .help("Joins the online game at this address, like 192.168.1.20:7777, the board is the host's.")
+                .conflicts_with_all(["replay", "ai", "bot", "players", "rival-ai"]),
+        )
+        .arg(
+            Arg::new("broadcast")
+                .long("broadcast")
+                .help("Lets spectators watch the games played here on this port with --watch.")
+                .value_parser(clap::value_parser!(u16))
+                .conflicts_with_all(["host", "join"]),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Watches the game at this address, hosted or broadcast, without playing.")
+                .conflicts_with_all(["host", "join", "broadcast", "replay", "ai", "bot", "players", "rival-ai"]),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .help("Sets the name the others see in an online game, defaults to the user name."),
+        )
+        .arg(
+            Arg::new("frontend")
+                .long("frontend")
+                .help("Sets where the game is drawn, tui plays inside the terminal.")
+                .default_value(if cfg!(feature = "sdl") { "sdl" } else { "tui" })
+                .value_parser(["sdl", "tui"]),
+        )
         .get_matches();
 
-    let width = matched_args.get_one::<u32>("width").unwrap();
-    let height = matched_args.get_one::<u32>("height").unwrap();
-    let border = matched_args.get_one::<bool>("border").unwrap();
-    let obstacles = matched_args.get_one::<u32>("obstacles").unwrap();
-    let tickspeed = matched_args.get_one::<u32>("tickspeed").unwrap();
+    let mut settings = GameSettings {
+        // an online game that is joined takes the size of the host below
+        width: matched_args
+            .get_one::<u32>("width")
+            .copied()
+            .unwrap_or(PLAYGROUND_WIDTH),
+        height: matched_args
+            .get_one::<u32>("height")
+            .copied()
+            .unwrap_or(PLAYGROUND_HEIGHT),
+        // a campaign level brings its own board and speed
+        border: matched_args
+            .get_one::<bool>("border")
+            .copied()
+            .unwrap_or(false),
+        obstacles: matched_args
+            .get_one::<u32>("obstacles")
+            .copied()
+            .unwrap_or(0),
+        tickspeed: matched_args
+            .get_one::<u32>("tickspeed")
+            .copied()
+            .unwrap_or(DEFAULT_TICKSPEED),
+        deadzone: *matched_args.get_one::<u32>("deadzone").unwrap(),
+        record: matched_args.get_one::<PathBuf>("record").cloned(),
+        volume: *matched_args.get_one::<u32>("volume").unwrap(),
+        music_volume: *matched_args.get_one::<u32>("music-volume").unwrap(),
+        mute: matched_args.get_flag("mute"),
+        palette: Palette::by_name(matched_args.get_one::<String>("palette").unwrap()).unwrap(),
+        seed: matched_args.get_one::<u64>("seed").copied(),
+        replay: match matched_args.get_one::<PathBuf>("replay") {
+            Some(path) => Some(Replay::load(path)?),
+            None => None,
+        },
+        save_replay: matched_args.get_one::<PathBuf>("save-replay").cloned(),
+        ai: matched_args.get_one::<String>("ai").cloned(),
+        bot: matched_args.get_one::<String>("bot").cloned(),
+        bot_time_limit: *matched_args.get_one::<u32>("bot-time-limit").unwrap(),
+        players: *matched_args.get_one::<u32>("players").unwrap() as usize,
+        rival_ai: matched_args.get_one::<String>("rival-ai").cloned(),
+        broadcast: matched_args.get_one::<u16>("broadcast").copied(),
+        power_ups: matched_args.get_flag("power-ups"),
+        apples: *matched_args.get_one::<u32>("apples").unwrap(),
+        apple_kinds: matched_args.get_flag("apple-kinds"),
+        edges: *matched_args.get_one::<Edges>("edges").unwrap(),
+        portals: *matched_args.get_one::<u32>("portals").unwrap(),
+        patrols: *matched_args.get_one::<u32>("patrols").unwrap(),
+        gates: *matched_args.get_one::<u32>("gates").unwrap(),
+        growing_obstacles: *matched_args.get_one::<u32>("growing-obstacles").unwrap(),
+        shrinking_arena: *matched_args.get_one::<u32>("shrinking-arena").unwrap(),
+        mode: Mode::Classic,
+        leaderboard: None,
+        scores: matched_args.get_one::<PathBuf>("scores").unwrap().clone(),
+        level: None,
+        resume: None,
+        save_file: matched_args
+            .get_one::<PathBuf>("save-file")
+            .unwrap()
+            .clone(),
+        ghosts: match matched_args.get_flag("ghost") {
+            true => matched_args.get_one::<PathBuf>("ghost-folder").cloned(),
+            false => None,
+        },
+    };
+    // the clock of the game counts ticks, a time attack is as many as fit in its time
+    let time_limit = *matched_args.get_one::<u32>("time-limit").unwrap();
+    let sprint_apples = *matched_args.get_one::<u32>("sprint-apples").unwrap();
+    let (mode, mode_key) = match matched_args.get_one::<String>("mode").unwrap().as_str() {
+        "time-attack" => (
+            Mode::TimeAttack {
+                ticks: (time_limit as u64 * 1000 / settings.tickspeed as u64).max(1),
+            },
+            format!("time-attack-{}s", time_limit),
+        ),
+        "sprint" => (
+            Mode::AppleSprint {
+                apples: sprint_apples,
+            },
+            format!("sprint-{}", sprint_apples),
+        ),
+        "endless" => (Mode::Endless, "endless".to_string()),
+        _ => (Mode::Classic, "classic".to_string()),
+    };
+    settings.mode = mode;
+    // only games played alone by hand go on the leaderboard, every board size, speed and set of
+    // rules has its own
+    let is_online = ["host", "join", "watch"]
+        .iter()
+        .any(|id| matched_args.contains_id(id));
+    // a resumed game could have been saved right before every mistake
+    let counts = settings.players == 1
+        && !matched_args.get_flag("campaign")
+        && !matched_args.get_flag("resume")
+        && settings.ai.is_none()
+        && settings.bot.is_none()
+        && settings.replay.is_none()
+        && !is_online;
+    if counts {
+        settings.leaderboard = Some(mode_key);
+    }
+
+    let name = matched_args
+        .get_one::<String>("name")
+        .cloned()
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or("Player".to_string());
+    let online = match (
+        matched_args.get_one::<u16>("host"),
+        matched_args.get_one::<String>("join"),
+    ) {
+        (Some(port), _) => {
+            let config = ServerConfig {
+                board: Board {
+                    width: settings.width,
+                    height: settings.height,
+                    border: settings.border,
+                    obstacles: settings.obstacles,
+                    power_ups: settings.power_ups,
+                    apples: settings.apples,
+                    apple_kinds: settings.apple_kinds,
+                    edges: settings.edges,
+                    portals: settings.portals,
+                    patrols: settings.patrols,
+                    gates: settings.gates,
+                    growing_obstacles: settings.growing_obstacles,
+                    shrinking_arena: settings.shrinking_arena,
+                    mode: settings.mode,
+                },
+                tick: Duration::from_millis(settings.tickspeed as u64),
+                seed: settings.seed,
+            };
+            Server::bind(*port, config)?.spawn();
+            println!(
+                "Hosting on port {}, the others join with --join <this computer>:{}",
+                port, port
+            );
+            Some(NetClient::join(&format!("127.0.0.1:{}", port), &name)?)
+        }
+        (None, Some(address)) => Some(NetClient::join(address, &name)?),
+        (None, None) => match matched_args.get_one::<String>("watch") {
+            Some(address) => Some(NetClient::watch(address)?),
+            None => None,
+        },
+    };
+    if let Some(client) = &online {
+        settings.width = client.board.width;
+        settings.height = client.board.height;
+        settings.border = client.board.border;
+        settings.obstacles = client.board.obstacles;
+        settings.power_ups = client.board.power_ups;
+        settings.apples = client.board.apples;
+        settings.apple_kinds = client.board.apple_kinds;
+        settings.edges = client.board.edges;
+        settings.portals = client.board.portals;
+        settings.patrols = client.board.patrols;
+        settings.gates = client.board.gates;
+        settings.growing_obstacles = client.board.growing_obstacles;
+        settings.shrinking_arena = client.board.shrinking_arena;
+        settings.mode = client.board.mode;
+    }
+
+    let progress = matched_args.get_one::<PathBuf>("progress").unwrap();
+    let mut campaign = match matched_args.get_flag("campaign") {
+        true => Some(Campaign::load(progress)?),
+        false => None,
+    };
+    if matched_args.get_flag("resume") {
+        let save = Save::load(&settings.save_file)?;
+        // a campaign level goes on in the campaign so winning it still counts
+        if let Some(id) = &save.replay.level {
+            let mut resumed = Campaign::load(progress)?;
+            resumed.picked = resumed
+                .levels
+                .iter()
+                .position(|level| &level.id == id)
+                .unwrap_or(0);
+            resumed.selecting = false;
+            campaign = Some(resumed);
+        }
+        settings.resume_from(save);
+    }
+    if let Some(campaign) = &campaign {
+        settings.pick_level(campaign.level());
+    }
+
+    #[cfg(feature = "sdl")]
+    if let Some(path) = matched_args.get_one::<PathBuf>("screenshot") {
+        capture::save_png(
+            path,
+            &capture::render_frame(&settings.new_game(), &settings.palette)?,
+        )?;
+        return Ok(());
+    }
+    // the pictures are drawn by sdl
+    #[cfg(not(feature = "sdl"))]
+    if matched_args.contains_id("screenshot") || settings.record.is_some() {
+        return Err("--screenshot and --record need a build with the sdl feature".into());
+    }
+
+    if matched_args.get_one::<String>("frontend").unwrap() == "tui" {
+        return tui::run(&mut settings, online, campaign);
+    }
+    #[cfg(not(feature = "sdl"))]
+    return Err("this build has no window, play it with --frontend tui".into());
+    #[cfg(feature = "sdl")]
+    run_window(
+        settings,
+        online,
+        campaign,
+        *matched_args.get_one::<u32>("zoom").unwrap(),
+    )
+}
+
+// saves the whole field next to the game, named after the time so they don't overwrite each other
+#[cfg(feature = "sdl")]
+fn take_screenshot(
+    game: &SnakeGame,
+    palette: &Palette,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = PathBuf::from(format!("snake-{}.png", seconds));
+    capture::save_png(&path, &capture::render_frame(game, palette)?)?;
+    Ok(path)
+}
+
+#[cfg(feature = "sdl")]
+struct WindowProperties {
+    window_height: u32,
+    window_width: u32,
+    window_title: String,
+}
+
+// how many cells fit in the window at the given zoom
+#[cfg(feature = "sdl")]
+fn view_in_cells(window_property: &WindowProperties, zoom: u32) -> (u32, u32) {
+    (
+        window_property.window_width / zoom,
+        window_property.window_height / zoom,
+    )
+}
 
+// plays the game in a window
+#[cfg(feature = "sdl")]
+fn run_window(
+    mut settings: GameSettings,
+    online: Option<NetClient>,
+    mut campaign: Option<Campaign>,
+    mut zoom: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
     let is_wayland = env::var("WAYLAND_DISPLAY").is_ok();
     if is_wayland {
         env::set_var("SDL_VIDEODRIVER", "wayland");
     } // set the Operating system environment to run on wayland if possible
 
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    // big boards don't fit on screen so the window is capped and the camera scrolls instead
+    let max_window = video_subsystem
+        .desktop_display_mode(0)
+        .map(|mode| ((mode.w as u32) * 9 / 10, (mode.h as u32) * 9 / 10))
+        .unwrap_or(FALLBACK_MAX_WINDOW);
+
     let window_property = WindowProperties {
-        window_height: height * SQUARE_SIZE,
-        window_width: width * SQUARE_SIZE,
+        window_height: (settings.height * zoom).min(max_window.1 / zoom * zoom),
+        window_width: (settings.width * zoom).min(max_window.0 / zoom * zoom),
         window_title: WINDOW_TITLE.to_string(),
     };
 
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-
     let window = video_subsystem
         .window(
             &window_property.window_title,
@@ -194,19 +1314,89 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let texture_creator: TextureCreator<_> = canvas.texture_creator();
 
-    let (texture_snake, texture_apple, texture_collision) =
-        dummy_texture(&mut canvas, &texture_creator)?;
+    let mut palette = settings.palette;
+    let mut sprites = render::dummy_texture(&mut canvas, &texture_creator, &palette)?;
 
-    let mut game = SnakeGame::from(*width, *height, *border, (*obstacles > 0, *obstacles)); // the initialization of the game
+    let (mut game, mut session) = settings.new_session(online)?; // the initialization of the game
+    let mut ghost = settings.new_ghost(&game);
+    let mut gamepads = Gamepads::open(&sdl_context);
+    let mut camera = Camera::from(
+        game.field.size,
+        view_in_cells(&window_property, zoom),
+        settings.deadzone,
+    );
+    camera.center_on(leading_head(&game));
+    let mut show_minimap = true;
+    let mut recorder = settings.new_recorder(&game)?;
+    let mut audio = settings.open_audio(&sdl_context);
     print!("{}[2J", 27_u8 as char);
     println!("SDL Renderer: \"{}\"", canvas.info().name);
     println!("Welcome!\nPress <P> to pause!\nPress <R> to restart!");
+    if let Some(campaign) = &campaign {
+        println!("{}", campaign.menu());
+    }
 
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut last_status = String::new();
+    let can_rewind = settings.can_rewind(&session);
+    let mut rewind = Rewind::new();
     'running: loop {
         canvas.clear();
-        // there's a delay when the key is pressed to add some natural tactility feel
+        // the keys are queued in the human controller and applied one per tick
         for event in event_pump.poll_iter() {
+            // the level select shows the picked level until it is started
+            if let Some(campaign) = campaign.as_mut().filter(|campaign| campaign.selecting) {
+                let difference = match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => break 'running,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::W | Keycode::Up),
+                        ..
+                    } => -1,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::S | Keycode::Down),
+                        ..
+                    } => 1,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Return | Keycode::Space),
+                        ..
+                    } => {
+                        if !campaign.is_unlocked() {
+                            println!("That level is still locked, win the one before it first!");
+                            continue;
+                        }
+                        campaign.selecting = false;
+                        session.restart(&settings, &mut game)?;
+                        ghost = settings.new_ghost(&game);
+                        rewind.clear();
+                        camera.center_on(leading_head(&game));
+                        print!("{}[2J", 27_u8 as char);
+                        println!("{}", campaign.describe(campaign.picked));
+                        println!("Welcome!\nPress <P> to pause!\nPress <R> to restart!");
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if campaign.move_pick(difference) {
+                    settings.pick_level(campaign.level());
+                    game = settings.new_game();
+                    ghost = settings.new_ghost(&game);
+                    camera.center_on(leading_head(&game));
+                    audio.play_navigate();
+                    print!("{}[2J", 27_u8 as char);
+                    println!("{}", campaign.menu());
+                }
+                continue;
+            }
+            if let Some((player, direction)) = gamepads.handle(&event, game.players.len()) {
+                session.steer(&mut game, player, direction);
+                continue;
+            }
+            // the arrow keys are a second layout for the second player, alone they steer the first
+            let arrow_player = 1.min(game.players.len() - 1);
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
@@ -217,119 +1407,245 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     keycode: Some(Keycode::R),
                     ..
                 } => {
-                    game = SnakeGame::from(*width, *height, *border, (*obstacles > 0, *obstacles));
+                    if let Some(campaign) = &mut campaign {
+                        campaign.restart(&game);
+                        settings.pick_level(campaign.level());
+                    }
+                    session.restart(&settings, &mut game)?;
+                    ghost = settings.new_ghost(&game);
+                    rewind.clear();
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record_tick(&game, &palette)?;
+                    }
+                    camera.center_on(leading_head(&game));
 
                     print!("{}[2J", 27_u8 as char);
                     println!("Welcome!\nPress <P> to pause!\nPress <R> to restart!");
                     // the initialization of the game
                 } // The R key restarts the game
+                Event::KeyDown {
+                    keycode: Some(Keycode::L),
+                    ..
+                } => {
+                    if let Some(campaign) = &mut campaign {
+                        settings.save_unfinished_replay(&game);
+                        campaign.selecting = true;
+                        rewind.clear();
+                        game = settings.new_game();
+                        ghost = settings.new_ghost(&game);
+                        camera.center_on(leading_head(&game));
+                        print!("{}[2J", 27_u8 as char);
+                        println!("{}", campaign.menu());
+                    }
+                } // back to the level select
                 Event::KeyDown {
                     keycode: Some(Keycode::P),
                     ..
-                } => game.toggle_pause(),
+                } => session.toggle_pause(&mut game),
                 Event::KeyDown {
-                    keycode: Some(Keycode::W),
+                    keycode: Some(Keycode::M),
+                    ..
+                } => show_minimap = !show_minimap,
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
                     ..
                 } => {
-                    game.resume();
-                    game.change_head_direction(Direction::UP);
-                    std::thread::sleep(Duration::from_millis(50));
-                } // UP Key
+                    palette = palette.next();
+                    sprites = render::dummy_texture(&mut canvas, &texture_creator, &palette)?;
+                    println!("Palette: {}", palette.name);
+                }
                 Event::KeyDown {
-                    keycode: Some(Keycode::A),
+                    keycode: Some(Keycode::N),
+                    ..
+                } => audio.toggle_mute(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::LeftBracket),
+                    ..
+                } => audio.change_volume(-10),
+                Event::KeyDown {
+                    keycode: Some(Keycode::RightBracket),
+                    ..
+                } => audio.change_volume(10),
+                Event::KeyDown {
+                    keycode: Some(keycode @ (Keycode::Comma | Keycode::Period)),
                     ..
                 } => {
-                    game.resume();
-                    game.change_head_direction(Direction::RIGHT);
-                    std::thread::sleep(Duration::from_millis(50));
-                } // Right Key
+                    let stepped = match (can_rewind, keycode) {
+                        (false, _) => {
+                            println!("Rewinding is off in ranked, online and played back games!");
+                            continue;
+                        }
+                        (true, Keycode::Comma) => rewind.back(&mut game),
+                        (true, _) => rewind.forward(&mut game),
+                    };
+                    if stepped {
+                        session.clear_turns();
+                        println!(
+                            "Tick {}, <,> goes back, <.> forward and any direction plays on",
+                            game.ticks
+                        );
+                    }
+                } // rewind
                 Event::KeyDown {
-                    keycode: Some(Keycode::S),
+                    keycode: Some(Keycode::F5),
+                    ..
+                } if session.is_local() => {
+                    if game.state == GameState::Running {
+                        session.toggle_pause(&mut game);
+                    }
+                    println!("{}", settings.save_game(&game));
+                } // quick-save
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    ..
+                } => match take_screenshot(&game, &palette) {
+                    Ok(path) => println!("Screenshot saved to {}", path.display()),
+                    Err(e) => eprintln!("Failed to save the screenshot: {}", e),
+                },
+                Event::KeyDown {
+                    keycode: Some(Keycode::Equals | Keycode::KpPlus),
                     ..
                 } => {
-                    game.resume();
-                    game.change_head_direction(Direction::DOWN);
-                    std::thread::sleep(Duration::from_millis(50));
-                } // Down Key
+                    zoom = (zoom * 2).min(MAX_ZOOM);
+                    camera.resize(view_in_cells(&window_property, zoom));
+                } // zoom in
                 Event::KeyDown {
-                    keycode: Some(Keycode::D),
+                    keycode: Some(Keycode::Minus | Keycode::KpMinus),
                     ..
                 } => {
-                    game.resume();
-                    game.change_head_direction(Direction::LEFT);
-                    std::thread::sleep(Duration::from_millis(50));
-                } // Left key
+                    zoom = (zoom / 2).max(MIN_ZOOM);
+                    camera.resize(view_in_cells(&window_property, zoom));
+                } // zoom out
+                Event::KeyDown {
+                    keycode: Some(Keycode::W),
+                    ..
+                } => session.steer(&mut game, 0, Direction::UP), // UP Key
+                Event::KeyDown {
+                    keycode: Some(Keycode::A),
+                    ..
+                } => session.steer(&mut game, 0, Direction::RIGHT), // Right Key
+                Event::KeyDown {
+                    keycode: Some(Keycode::S),
+                    ..
+                } => session.steer(&mut game, 0, Direction::DOWN), // Down Key
+                Event::KeyDown {
+                    keycode: Some(Keycode::D),
+                    ..
+                } => session.steer(&mut game, 0, Direction::LEFT), // Left key
+                Event::KeyDown {
+                    keycode: Some(Keycode::Up),
+                    ..
+                } => session.steer(&mut game, arrow_player, Direction::UP),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Left),
+                    ..
+                } => session.steer(&mut game, arrow_player, Direction::RIGHT),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Down),
+                    ..
+                } => session.steer(&mut game, arrow_player, Direction::DOWN),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Right),
+                    ..
+                } => session.steer(&mut game, arrow_player, Direction::LEFT),
                 _ => {}
             }
         }
         // the start of the game loop
 
-        let mut old_score = game.snake.body.len();
+        let old_scores = scores(&game);
+        let old_effects = effects(&game, session.tick_length(&settings, &game));
+        let old_clock = clock(&game, session.base_tick(&settings));
+        let was_over = game.state == GameState::GameOver;
 
-        game.update();
+        if can_rewind && game.state == GameState::Running {
+            rewind.record(&mut game);
+        }
+        session.advance(&mut game);
+        if let Some(ghost) = &mut ghost {
+            ghost.follow(game.ticks);
+        }
+        // games watched over the network can change the board between games
+        if camera.field() != game.field.size {
+            camera = Camera::from(
+                game.field.size,
+                view_in_cells(&window_property, zoom),
+                settings.deadzone,
+            );
+            camera.center_on(leading_head(&game));
+        }
 
-        if game.state != GameState::GameOver && game.snake.body.len() != old_score {
+        if let Some(status) = session.status() {
+            if status != last_status {
+                println!("{}", status);
+                last_status = status;
+            }
+        }
+        let new_effects = effects(&game, session.tick_length(&settings, &game));
+        let new_clock = clock(&game, session.base_tick(&settings));
+        if game.state != GameState::GameOver
+            && (scores(&game) != old_scores || new_effects != old_effects || new_clock != old_clock)
+        {
             print!("{}[2J", 27_u8 as char);
             println!("Welcome!\nPress <P> to pause!\nPress <R> to restart!");
-            println!("Score: {}", game.snake.body.len());
-            old_score = game.snake.body.len();
+            println!("Score: {}", scores(&game));
+            if !new_clock.is_empty() {
+                println!("Time: {}", new_clock);
+            }
+            if !new_effects.is_empty() {
+                println!("Power-ups: {}", new_effects);
+            }
         }
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        //canvas.set_draw_color(Color::RGB(255, 255, 255)); // not needed since the textures have
-        //their own color applied to them
-
-        // use the game provider to draw the entities
-        let _ = canvas.copy(
-            &texture_apple,
-            None,
-            Rect::new(
-                game.apple.position.0 as i32 * SQUARE_SIZE as i32,
-                game.apple.position.1 as i32 * SQUARE_SIZE as i32,
-                SQUARE_SIZE,
-                SQUARE_SIZE,
-            ),
-        );
-
-        for snake_part in game.snake.body.iter() {
-            let _ = canvas.copy(
-                &texture_snake,
-                None,
-                Rect::new(
-                    snake_part.get().position.0 as i32 * SQUARE_SIZE as i32,
-                    snake_part.get().position.1 as i32 * SQUARE_SIZE as i32,
-                    SQUARE_SIZE,
-                    SQUARE_SIZE,
-                ),
+        if !was_over && game.state == GameState::GameOver {
+            settings.save_replay(&game);
+            print!("{}[2J", 27_u8 as char);
+            println!(
+                "{}\nScore: {}",
+                game_over_message(&game, session.base_tick(&settings)),
+                scores(&game)
             );
-        }
-        if game.field.obstacles.is_some() {
-            for obstacle_part in game
-                .field
-                .obstacles
-                .as_ref()
-                .expect("Illegal game instruction")
-            {
-                let _ = canvas.copy(
-                    &texture_collision,
-                    None,
-                    Rect::new(
-                        obstacle_part.0 as i32 * SQUARE_SIZE as i32,
-                        obstacle_part.1 as i32 * SQUARE_SIZE as i32,
-                        SQUARE_SIZE,
-                        SQUARE_SIZE,
-                    ),
-                );
+            if let Some(result) = settings.record_result(&game) {
+                println!("{}", result);
+            }
+            if let Some(best) = settings.offer_ghost(&game) {
+                println!("{}", best);
             }
+            match &mut campaign {
+                Some(campaign) => println!("{}", campaign.finish(&game)),
+                None => println!("Press <R> to restart!"),
+            }
+        }
+
+        if let Some(recorder) = &mut recorder {
+            recorder.record_tick(&game, &palette)?;
         }
+        audio.play_events(&game.drain_events());
+        audio.sync_music(game.state);
+
+        //canvas.set_draw_color(Color::RGB(255, 255, 255)); // not needed since the textures have
+        //their own color applied to them
+        camera.follow(leading_head(&game));
+        draw_game(
+            &mut canvas,
+            &sprites,
+            &game,
+            &camera,
+            zoom,
+            show_minimap,
+            ghost.as_ref().and_then(|ghost| ghost.snake()),
+        );
 
         canvas.present();
-        std::thread::sleep(Duration::from_millis(*tickspeed as u64));
+        std::thread::sleep(session.tick_time(&settings, &game));
 
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60)); // sloppy FPS limit for
                                                                        // weird stuff
     }
 
+    settings.save_unfinished_replay(&game);
+    if let Some(saved) = settings.save_unfinished_game(&game, &session) {
+        println!("{}", saved);
+    }
     Ok(())
 }
@@ -0,0 +1,103 @@
+// what a game is about, the classic one is filling the board
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+    #[default]
+    Classic,
+    // as many points as possible before this many ticks are over
+    TimeAttack {
+        ticks: u64,
+    },
+    // this many apples as fast as possible
+    AppleSprint {
+        apples: u32,
+    },
+    // nothing to win, it goes on until the snake crashes
+    Endless,
+    // a campaign level that is won by getting the head onto this cell
+    Exit {
+        x: u32,
+        y: u32,
+    },
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Classic => "classic",
+            Mode::TimeAttack { .. } => "time-attack",
+            Mode::AppleSprint { .. } => "sprint",
+            Mode::Endless => "endless",
+            Mode::Exit { .. } => "exit",
+        }
+    }
+
+    // a sprint and getting out are about the time it took, everything else about the points
+    pub fn lower_is_better(self) -> bool {
+        matches!(self, Mode::AppleSprint { .. } | Mode::Exit { .. })
+    }
+
+    // the races against the clock, stepping back in them would be turning the clock back
+    pub fn is_ranked(self) -> bool {
+        matches!(self, Mode::TimeAttack { .. } | Mode::AppleSprint { .. })
+    }
+}
+
+// the name and what it needs after a colon, like time-attack:600, sprint:20 or exit:3,4
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let number = |what: &str| -> Result<u64, String> {
+            value
+                .ok_or(format!("{} needs {} like {}:20", name, what, name))?
+                .parse::<u64>()
+                .map_err(|e| format!("{} needs {}: {}", name, what, e))
+        };
+        match name {
+            "classic" => Ok(Mode::Classic),
+            "time-attack" => Ok(Mode::TimeAttack {
+                ticks: number("the ticks")?.max(1),
+            }),
+            "sprint" => Ok(Mode::AppleSprint {
+                apples: number("the apples")?.max(1) as u32,
+            }),
+            "endless" => Ok(Mode::Endless),
+            "exit" => {
+                let (x, y) = value
+                    .and_then(|value| value.split_once(','))
+                    .ok_or("exit needs the cell like exit:3,4")?;
+                let number = |value: &str| {
+                    value
+                        .parse::<u32>()
+                        .map_err(|e| format!("exit needs the cell: {}", e))
+                };
+                Ok(Mode::Exit {
+                    x: number(x)?,
+                    y: number(y)?,
+                })
+            }
+            _ => Err(format!(
+                "unknown mode \"{}\", it is classic, time-attack, sprint or endless",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::TimeAttack { ticks } => write!(f, "{}:{}", self.name(), ticks),
+            Mode::AppleSprint { apples } => write!(f, "{}:{}", self.name(), apples),
+            Mode::Exit { x, y } => write!(f, "{}:{},{}", self.name(), x, y),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
@@ -1,12 +1,52 @@
 // this will spawn an apple in a location that is not taken
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AppleKind {
+    Normal,
+    // worth more but gone after a while
+    Golden,
+    // takes a part off the snake, a snake as short as it gets dies from it
+    Poison,
+    // turns the controls around for a bit
+    Rotten,
+}
+
+impl AppleKind {
+    pub const ALL: [AppleKind; 4] = [
+        AppleKind::Normal,
+        AppleKind::Golden,
+        AppleKind::Poison,
+        AppleKind::Rotten,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AppleKind::Normal => "normal",
+            AppleKind::Golden => "golden",
+            AppleKind::Poison => "poison",
+            AppleKind::Rotten => "rotten",
+        }
+    }
+
+    // the ones a snake wants to eat
+    pub fn is_food(self) -> bool {
+        matches!(self, AppleKind::Normal | AppleKind::Golden)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct Apple {
     pub position: (u32, u32),
+    pub kind: AppleKind,
+    // the tick it disappears on, only golden apples do
+    pub expires: Option<u64>,
 }
 
 impl Apple {
     pub fn add_to_field_at_start(field_dimension_x: u32, field_dimension_y: u32) -> Self {
         Self {
             position: (field_dimension_x, field_dimension_y),
+            kind: AppleKind::Normal,
+            expires: None,
         }
     }
 
@@ -15,3 +55,116 @@ impl Apple {
         self.position.1 = new_place_y;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Direction;
+    use crate::{GameEvent, GameState, SnakeGame, GOLDEN_LIFETIME, GOLDEN_POINTS, ROTTEN_TICKS};
+
+    // a snake in the middle of an empty field heading to bigger x, only normal apples come next
+    fn game() -> SnakeGame {
+        let mut game = SnakeGame::from_seed(21, 21, false, (false, 0), 1);
+        game.resume();
+        game
+    }
+
+    // puts an apple right in front of the head and lets the snake eat it
+    fn eat(game: &mut SnakeGame, kind: AppleKind) {
+        let (width, height) = game.field.size;
+        let head = game.snake().body[0].get().position;
+        let apple = &mut game.apples[0];
+        apple.position = game.players[0]
+            .snake
+            .head_direction()
+            .step(head, width, height);
+        apple.kind = kind;
+        game.update();
+    }
+
+    #[test]
+    fn golden_apples_are_worth_more_but_go_away() {
+        let mut game = game();
+        eat(&mut game, AppleKind::Golden);
+        assert_eq!(game.score(), GOLDEN_POINTS);
+        assert_eq!(game.players[0].eaten, 1);
+        assert_eq!(game.snake().body.len(), 3);
+
+        game.apples[0] = Apple {
+            position: (0, 0),
+            kind: AppleKind::Golden,
+            expires: Some(game.ticks + GOLDEN_LIFETIME),
+        };
+        for _ in 1..GOLDEN_LIFETIME {
+            game.update();
+        }
+        assert_eq!(game.apples[0].position, (0, 0));
+        game.update();
+        assert_ne!(game.apples[0].position, (0, 0));
+        assert_eq!(
+            (game.apples[0].kind, game.apples[0].expires),
+            (AppleKind::Normal, None)
+        );
+    }
+
+    #[test]
+    fn poison_takes_a_part_and_a_point() {
+        let mut game = game();
+        eat(&mut game, AppleKind::Normal);
+        eat(&mut game, AppleKind::Normal);
+        game.drain_events();
+        eat(&mut game, AppleKind::Poison);
+        assert_eq!((game.snake().body.len(), game.score()), (3, 1));
+        assert_eq!(game.players[0].eaten, 2);
+        assert!(game.drain_events().contains(&GameEvent::Poisoned));
+        assert!(game.players[0].alive);
+    }
+
+    #[test]
+    fn poison_kills_a_snake_that_is_only_a_head_and_a_tail() {
+        let mut game = game();
+        assert_eq!(game.snake().body.len(), 2);
+        eat(&mut game, AppleKind::Poison);
+        assert!(!game.players[0].alive);
+        assert_eq!(game.state, GameState::GameOver);
+        assert!(game.drain_events().contains(&GameEvent::Collided));
+    }
+
+    #[test]
+    fn rotten_apples_turn_the_controls_around_for_a_while() {
+        let mut game = game();
+        eat(&mut game, AppleKind::Rotten);
+        assert_eq!(game.score(), 1);
+        assert_eq!(game.players[0].reversed, ROTTEN_TICKS - 1);
+        game.change_head_direction(Direction::UP);
+        assert_eq!(game.snake().head_direction(), Direction::DOWN);
+        // the turn is kept the way it was asked for
+        assert_eq!(game.players[0].moves.last(), Some(&(1, Direction::UP)));
+        while game.players[0].reversed > 0 {
+            game.update();
+        }
+        game.change_head_direction(Direction::LEFT);
+        assert_eq!(game.snake().head_direction(), Direction::LEFT);
+    }
+
+    #[test]
+    fn there_is_always_a_normal_apple() {
+        let mut kinds = vec![];
+        for seed in 0..20 {
+            let game = SnakeGame::from_seed(21, 21, false, (false, 0), seed).with_apples(8, true);
+            let normal = game
+                .apples
+                .iter()
+                .filter(|apple| apple.kind == AppleKind::Normal);
+            assert!(normal.count() > 0);
+            for apple in &game.apples {
+                assert_eq!(apple.expires.is_some(), apple.kind == AppleKind::Golden);
+                kinds.push(apple.kind);
+            }
+        }
+        assert!(AppleKind::ALL.iter().all(|kind| kinds.contains(kind)));
+        let plain = SnakeGame::from_seed(21, 21, false, (false, 0), 1).with_apples(8, false);
+        let kinds: Vec<AppleKind> = plain.apples.iter().map(|apple| apple.kind).collect();
+        assert_eq!(kinds, [AppleKind::Normal; 8]);
+    }
+}
@@ -1,22 +1,198 @@
 // this is used to generate the field with some has_obstacles
+use super::{Direction, Gate, Patrol};
 use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
 
+// what happens to a snake that runs off the field
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    // it comes back in on the other side
+    Wrap,
+    // it crashes
+    Wall,
+    // it turns along the edge towards the side with more room
+    Bounce,
+}
+
+impl FromStr for Edge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(Edge::Wrap),
+            "wall" => Ok(Edge::Wall),
+            "bounce" => Ok(Edge::Bounce),
+            _ => Err(format!(
+                "unknown edge \"{}\", it is wrap, wall or bounce",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Edge::Wrap => "wrap",
+            Edge::Wall => "wall",
+            Edge::Bounce => "bounce",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// the four sides of the field the way they are on screen, right is where x is biggest
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Edges {
+    pub top: Edge,
+    pub right: Edge,
+    pub bottom: Edge,
+    pub left: Edge,
+}
+
+impl Edges {
+    // wraps around everywhere, how the game always was
+    pub const TORUS: Edges = Edges::all(Edge::Wrap);
+    // wraps around from left to right, the top and the bottom are walls
+    pub const CYLINDER: Edges = Edges {
+        top: Edge::Wall,
+        right: Edge::Wrap,
+        bottom: Edge::Wall,
+        left: Edge::Wrap,
+    };
+
+    pub const fn all(edge: Edge) -> Self {
+        Self {
+            top: edge,
+            right: edge,
+            bottom: edge,
+            left: edge,
+        }
+    }
+
+    // the edge a head crosses when it moves this way from here, none while it stays inside
+    pub fn crossed(
+        &self,
+        position: (u32, u32),
+        direction: Direction,
+        size: (u32, u32),
+    ) -> Option<Edge> {
+        match direction {
+            Direction::UP if position.1 == 0 => Some(self.top),
+            Direction::DOWN if position.1 == size.1 - 1 => Some(self.bottom),
+            // left goes towards bigger x like everywhere else
+            Direction::LEFT if position.0 == size.0 - 1 => Some(self.right),
+            Direction::RIGHT if position.0 == 0 => Some(self.left),
+            _ => None,
+        }
+    }
+
+    pub fn wraps_horizontally(&self) -> bool {
+        self.left == Edge::Wrap && self.right == Edge::Wrap
+    }
+
+    pub fn wraps_vertically(&self) -> bool {
+        self.top == Edge::Wrap && self.bottom == Edge::Wrap
+    }
+}
+
+impl Default for Edges {
+    fn default() -> Self {
+        Self::TORUS
+    }
+}
+
+// one name for all sides, torus, cylinder or four edges like css: top,right,bottom,left
+impl FromStr for Edges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "torus" => return Ok(Self::TORUS),
+            "cylinder" => return Ok(Self::CYLINDER),
+            _ => {}
+        }
+        let sides = s
+            .split(',')
+            .map(|side| side.trim().parse())
+            .collect::<Result<Vec<Edge>, String>>()?;
+        match sides[..] {
+            [edge] => Ok(Self::all(edge)),
+            [top, right, bottom, left] => Ok(Self {
+                top,
+                right,
+                bottom,
+                left,
+            }),
+            _ => Err(format!(
+                "\"{}\" needs one edge or four for top,right,bottom,left",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Edges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+
+// the arena doesn't shrink any further than this many cells across
+const MIN_ARENA: u32 = 5;
+// how many ticks before a ring of walls closes the frontends flash it
+pub const RING_WARNING: u64 = 20;
+
+// the two ends of a portal
+pub type Portal = ((u32, u32), (u32, u32));
+
+#[derive(Clone)]
 pub struct Field {
     pub size: (u32, u32),
     has_border: bool,
     has_obstacles: bool,
     num_obstacles: u32,
     pub obstacles: Option<Vec<(u32, u32)>>,
+    pub edges: Edges,
+    // a head that moves onto one end of a portal comes out of the other end going the same way
+    pub portals: Vec<Portal>,
+    pub patrols: Vec<Patrol>,
+    pub gates: Vec<Gate>,
+    // how many portals, patrols and gates the game asked for, there may not have been room for all
+    // of them and the arena can close over some of them later
+    pub num_portals: u32,
+    pub num_patrols: u32,
+    pub num_gates: u32,
+    // one more obstacle shows up every time the snakes make this many points, 0 for never
+    pub growing_obstacles: u32,
+    // a ring of walls closes around the arena every this many ticks, 0 for never
+    pub shrinking_arena: u32,
+    // how many rings closed so far
+    pub closed_rings: u32,
 }
 
 pub fn generate_obstacles(
     size: (u32, u32),
     num_obstacles: u32,
     has_border: bool,
+) -> Vec<(u32, u32)> {
+    generate_obstacles_with(size, num_obstacles, has_border, &mut rand::thread_rng())
+}
+
+// the same as generate_obstacles but with a given generator so seeded games get the same board
+pub fn generate_obstacles_with(
+    size: (u32, u32),
+    num_obstacles: u32,
+    has_border: bool,
+    rng: &mut impl Rng,
 ) -> Vec<(u32, u32)> {
     // generate obstacles
     let mut random_obstacles: Vec<(u32, u32)> = vec![];
-    let mut rng = rand::thread_rng(); // seed the random number generator
     let (mut x, mut y);
     for _ in 0..num_obstacles {
         loop {
@@ -42,6 +218,19 @@ pub fn generate_obstacles(
     random_obstacles
 }
 
+// the cell a head ends up on when it moves onto this one
+pub fn portal_exit(portals: &[Portal], position: (u32, u32)) -> Option<(u32, u32)> {
+    portals.iter().find_map(|(first, second)| {
+        if *first == position {
+            Some(*second)
+        } else if *second == position {
+            Some(*first)
+        } else {
+            None
+        }
+    })
+}
+
 impl Field {
     // initialilze the field with the default settings
     pub fn new() -> Self {
@@ -51,6 +240,16 @@ impl Field {
             has_obstacles: false,
             num_obstacles: 0,
             obstacles: None,
+            edges: Edges::TORUS,
+            portals: vec![],
+            patrols: vec![],
+            gates: vec![],
+            num_portals: 0,
+            num_patrols: 0,
+            num_gates: 0,
+            growing_obstacles: 0,
+            shrinking_arena: 0,
+            closed_rings: 0,
         }
     }
 
@@ -59,6 +258,22 @@ impl Field {
         has_border: bool,
         has_obstacles: bool,
         num_obstacles: u32,
+    ) -> Self {
+        Self::from_rng(
+            size,
+            has_border,
+            has_obstacles,
+            num_obstacles,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    pub fn from_rng(
+        size: (u32, u32),
+        has_border: bool,
+        has_obstacles: bool,
+        num_obstacles: u32,
+        rng: &mut impl Rng,
     ) -> Self {
         Self {
             size,
@@ -66,12 +281,108 @@ impl Field {
             has_obstacles,
             num_obstacles,
             obstacles: if has_obstacles {
-                Some(generate_obstacles(size, num_obstacles, has_border))
+                Some(generate_obstacles_with(
+                    size,
+                    num_obstacles,
+                    has_border,
+                    rng,
+                ))
             } else if has_border {
-                Some(generate_obstacles(size, 0, has_border))
+                Some(generate_obstacles_with(size, 0, has_border, rng))
             } else {
                 None
             },
+            edges: Edges::TORUS,
+            portals: vec![],
+            patrols: vec![],
+            gates: vec![],
+            num_portals: 0,
+            num_patrols: 0,
+            num_gates: 0,
+            growing_obstacles: 0,
+            shrinking_arena: 0,
+            closed_rings: 0,
+        }
+    }
+
+    pub fn has_border(&self) -> bool {
+        self.has_border
+    }
+
+    pub fn has_obstacles(&self) -> bool {
+        self.has_obstacles
+    }
+
+    pub fn num_obstacles(&self) -> u32 {
+        self.num_obstacles
+    }
+
+    pub fn edge_ahead(&self, position: (u32, u32), direction: Direction) -> Option<Edge> {
+        self.edges.crossed(position, direction, self.size)
+    }
+
+    // where a snake goes after bouncing off an edge, along it towards the side with more room
+    pub fn bounce(&self, position: (u32, u32), direction: Direction) -> Direction {
+        let (width, height) = self.size;
+        match direction {
+            Direction::UP | Direction::DOWN if position.0 >= width - 1 - position.0 => {
+                Direction::RIGHT
+            }
+            Direction::UP | Direction::DOWN => Direction::LEFT,
+            _ if position.1 >= height - 1 - position.1 => Direction::UP,
+            _ => Direction::DOWN,
+        }
+    }
+
+    pub fn portal_exit(&self, position: (u32, u32)) -> Option<(u32, u32)> {
+        portal_exit(&self.portals, position)
+    }
+
+    // true when something shows up or moves around on the field during the game
+    pub fn changes(&self) -> bool {
+        !self.patrols.is_empty()
+            || !self.gates.is_empty()
+            || self.growing_obstacles > 0
+            || self.shrinking_arena > 0
+    }
+
+    // the cells of a ring, the outermost one is 0
+    pub fn ring(&self, index: u32) -> Vec<(u32, u32)> {
+        let (width, height) = self.size;
+        let (right, bottom) = (width - 1 - index, height - 1 - index);
+        (index..=bottom)
+            .flat_map(|y| (index..=right).map(move |x| (x, y)))
+            .filter(|(x, y)| *x == index || *y == index || *x == right || *y == bottom)
+            .collect()
+    }
+
+    // the ring that closes next, none when the arena doesn't shrink or can't get any smaller,
+    // a border already is the outermost ring
+    pub fn next_ring(&self) -> Option<u32> {
+        if self.shrinking_arena == 0 {
+            return None;
+        }
+        let index = self.closed_rings + self.has_border as u32;
+        let smallest_side = self.size.0.min(self.size.1);
+        (smallest_side >= MIN_ARENA + 2 * (index + 1)).then_some(index)
+    }
+
+    // how many ticks until the next ring closes
+    pub fn ring_closes_in(&self, ticks: u64) -> Option<u64> {
+        self.next_ring()?;
+        let every = self.shrinking_arena as u64;
+        Some(every - ticks % every)
+    }
+
+    pub fn add_obstacle(&mut self, position: (u32, u32)) {
+        self.obstacles.get_or_insert_with(Vec::new).push(position);
+    }
+
+    pub fn remove_obstacle(&mut self, position: (u32, u32)) {
+        if let Some(obstacles) = &mut self.obstacles {
+            if let Some(index) = obstacles.iter().position(|cell| *cell == position) {
+                obstacles.remove(index);
+            }
         }
     }
 }
@@ -81,3 +392,164 @@ impl Default for Field {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameState, SnakeGame};
+
+    // a snake in the middle of an empty field heading to bigger x, the apple out of its way
+    fn game(edges: Edges) -> SnakeGame {
+        let mut game = SnakeGame::from_seed(21, 21, false, (false, 0), 1).with_edges(edges);
+        game.apples[0].position = (0, 0);
+        game.resume();
+        game
+    }
+
+    fn head(game: &SnakeGame) -> (u32, u32) {
+        game.snake().body[0].get().position
+    }
+
+    #[test]
+    fn only_a_head_on_a_side_crosses_it() {
+        let edges: Edges = "wall,wrap,bounce,wrap".parse().unwrap();
+        let size = (5, 4);
+        assert_eq!(edges.crossed((2, 0), Direction::UP, size), Some(Edge::Wall));
+        assert_eq!(
+            edges.crossed((2, 3), Direction::DOWN, size),
+            Some(Edge::Bounce)
+        );
+        // left goes towards bigger x
+        assert_eq!(
+            edges.crossed((4, 1), Direction::LEFT, size),
+            Some(Edge::Wrap)
+        );
+        assert_eq!(
+            edges.crossed((0, 1), Direction::RIGHT, size),
+            Some(Edge::Wrap)
+        );
+        assert_eq!(edges.crossed((2, 0), Direction::DOWN, size), None);
+        assert_eq!(edges.crossed((0, 1), Direction::LEFT, size), None);
+        assert_eq!(edges.crossed((2, 2), Direction::UP, size), None);
+    }
+
+    #[test]
+    fn edges_read_back_what_they_write() {
+        assert_eq!("torus".parse(), Ok(Edges::TORUS));
+        assert_eq!("cylinder".parse(), Ok(Edges::CYLINDER));
+        assert_eq!("wall".parse(), Ok(Edges::all(Edge::Wall)));
+        for edges in [
+            Edges::TORUS,
+            Edges::CYLINDER,
+            "bounce,wall,wrap,wall".parse().unwrap(),
+        ] {
+            assert_eq!(edges.to_string().parse(), Ok(edges));
+        }
+        assert!(Edges::CYLINDER.wraps_horizontally() && !Edges::CYLINDER.wraps_vertically());
+        for broken in ["", "round", "wall,wrap", "wall,wrap,wall,wrap,wall"] {
+            assert!(broken.parse::<Edges>().is_err(), "{:?} was read", broken);
+        }
+    }
+
+    #[test]
+    fn a_bounce_goes_along_the_side_with_more_room() {
+        let field = Field::from((10, 8), false, false, 0);
+        assert_eq!(field.bounce((7, 0), Direction::UP), Direction::RIGHT);
+        assert_eq!(field.bounce((2, 7), Direction::DOWN), Direction::LEFT);
+        assert_eq!(field.bounce((9, 6), Direction::LEFT), Direction::UP);
+        assert_eq!(field.bounce((0, 1), Direction::RIGHT), Direction::DOWN);
+    }
+
+    #[test]
+    fn the_snake_wraps_crashes_or_bounces_at_a_side() {
+        for (edge, alive, after) in [
+            (Edge::Wrap, true, (0, 10)),
+            (Edge::Wall, false, (20, 10)),
+            (Edge::Bounce, true, (20, 9)),
+        ] {
+            let mut game = game(Edges::all(edge));
+            while head(&game).0 < 20 {
+                game.update();
+            }
+            game.update();
+            assert_eq!(game.players[0].alive, alive, "{}", edge);
+            assert_eq!(head(&game), after, "{}", edge);
+        }
+    }
+
+    #[test]
+    fn a_portal_puts_the_head_out_of_the_other_end() {
+        assert_eq!(portal_exit(&[((1, 1), (5, 6))], (5, 6)), Some((1, 1)));
+        assert_eq!(portal_exit(&[((1, 1), (5, 6))], (1, 2)), None);
+
+        let mut game = game(Edges::TORUS);
+        game.field.portals = vec![((8, 10), (3, 3))];
+        game.update();
+        assert_eq!(head(&game), (3, 3));
+        assert_eq!(game.snake().head_direction(), Direction::LEFT);
+        game.update();
+        assert_eq!(head(&game), (4, 3));
+        // the tail follows through it
+        assert_eq!(game.snake().body[1].get().position, (3, 3));
+        assert_eq!(game.state, GameState::Running);
+    }
+
+    #[test]
+    fn a_ring_is_the_outline_of_what_is_left() {
+        let field = Field::from((10, 8), false, false, 0);
+        assert_eq!(field.ring(0).len(), 2 * (10 + 8) - 4);
+        assert_eq!(field.ring(1).len(), 2 * (8 + 6) - 4);
+        assert!(field.ring(1).contains(&(1, 1)) && field.ring(1).contains(&(8, 6)));
+        assert!(!field.ring(1).contains(&(0, 0)) && !field.ring(1).contains(&(2, 2)));
+    }
+
+    #[test]
+    fn rings_close_from_the_outside_until_the_arena_is_small() {
+        let mut field = Field::from((21, 21), false, false, 0);
+        assert_eq!(field.next_ring(), None);
+        assert_eq!(field.ring_closes_in(0), None);
+        field.shrinking_arena = 10;
+        assert_eq!(field.next_ring(), Some(0));
+        assert_eq!(field.ring_closes_in(0), Some(10));
+        assert_eq!(field.ring_closes_in(3), Some(7));
+        assert_eq!(field.ring_closes_in(10), Some(10));
+        field.closed_rings = 7;
+        assert_eq!(field.next_ring(), Some(7));
+        field.closed_rings = 8;
+        assert_eq!(field.next_ring(), None);
+        assert_eq!(field.ring_closes_in(0), None);
+
+        let mut bordered = Field::from((21, 21), true, false, 0);
+        bordered.shrinking_arena = 10;
+        assert_eq!(bordered.next_ring(), Some(1));
+        bordered.closed_rings = 7;
+        assert_eq!(bordered.next_ring(), None);
+    }
+
+    #[test]
+    fn a_closing_ring_walls_in_the_arena_and_moves_the_apple() {
+        // the apple is in the outer ring
+        let mut game = game(Edges::TORUS).with_shrinking_arena(10);
+        while game.ticks < 10 {
+            assert_eq!(game.field.obstacles, None);
+            game.update();
+        }
+        let ring = game.field.ring(0);
+        assert_eq!(game.field.obstacles.as_ref(), Some(&ring));
+        assert_eq!(game.field.closed_rings, 1);
+        assert!(!ring.contains(&game.apples[0].position));
+        assert!(game.players[0].alive);
+        assert_eq!(game.field.ring_closes_in(game.ticks), Some(10));
+    }
+
+    #[test]
+    fn a_snake_caught_in_a_closing_ring_dies() {
+        let mut game = game(Edges::TORUS).with_shrinking_arena(13);
+        for _ in 0..13 {
+            game.update();
+        }
+        assert_eq!(head(&game), (20, 10));
+        assert!(!game.players[0].alive);
+        assert_eq!(game.state, GameState::GameOver);
+    }
+}
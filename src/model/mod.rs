@@ -1,6 +1,10 @@
 pub mod apple;
 pub mod field;
+pub mod obstacle;
+pub mod power_up;
 pub mod snake;
 pub use apple::*;
 pub use field::*;
+pub use obstacle::*;
+pub use power_up::*;
 pub use snake::*;
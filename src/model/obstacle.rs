@@ -0,0 +1,192 @@
+// obstacles that don't stay where they are, the cells they block right now are kept with the
+// other obstacles in the field so everything that looks for obstacles finds them too
+
+// how many ticks a patrol waits between two steps, the snakes are twice as fast
+pub const PATROL_PACE: u64 = 2;
+// how many ticks a gate stays open and then closed
+pub const GATE_PERIOD: u64 = 40;
+// how many ticks before a gate closes it starts to flash
+pub const GATE_WARNING: u64 = 10;
+
+// a block walking back and forth along a straight line
+#[derive(Clone, Debug)]
+pub struct Patrol {
+    pub path: Vec<(u32, u32)>,
+    // where on the path it is
+    pub step: usize,
+    pub forward: bool,
+}
+
+impl Patrol {
+    pub fn new(path: Vec<(u32, u32)>) -> Self {
+        Self {
+            path,
+            step: 0,
+            forward: true,
+        }
+    }
+
+    pub fn position(&self) -> (u32, u32) {
+        self.path[self.step]
+    }
+
+    // the step it takes next, it turns around at the ends of the path
+    pub fn next_step(&self) -> usize {
+        let at_end = match self.forward {
+            true => self.step + 1 == self.path.len(),
+            false => self.step == 0,
+        };
+        if self.forward != at_end {
+            self.step + 1
+        } else {
+            self.step - 1
+        }
+    }
+
+    pub fn go_to(&mut self, step: usize) {
+        self.forward = step > self.step;
+        self.step = step;
+    }
+}
+
+// a wall that opens and closes on a timer, it starts open
+#[derive(Clone, Debug)]
+pub struct Gate {
+    pub cells: Vec<(u32, u32)>,
+    pub closed: bool,
+    // the tick it opens or closes next
+    pub switches: u64,
+}
+
+impl Gate {
+    // the frontends flash it for a while before it closes
+    pub fn is_closing(&self, ticks: u64) -> bool {
+        !self.closed && self.switches.saturating_sub(ticks) <= GATE_WARNING
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SnakeGame;
+
+    // the snake runs along the middle row of a torus, the apple out of its way
+    fn game() -> SnakeGame {
+        let mut game = SnakeGame::from_seed(21, 21, false, (false, 0), 1);
+        game.apples[0].position = (0, 0);
+        game.resume();
+        game
+    }
+
+    fn obstacles(game: &SnakeGame) -> Vec<(u32, u32)> {
+        game.field.obstacles.clone().unwrap_or_default()
+    }
+
+    #[test]
+    fn a_patrol_turns_around_at_the_ends() {
+        let mut patrol = Patrol::new(vec![(1, 1), (2, 1), (3, 1)]);
+        let mut walked = vec![patrol.position()];
+        for _ in 0..6 {
+            patrol.go_to(patrol.next_step());
+            walked.push(patrol.position());
+        }
+        assert_eq!(
+            walked,
+            [(1, 1), (2, 1), (3, 1), (2, 1), (1, 1), (2, 1), (3, 1)]
+        );
+    }
+
+    #[test]
+    fn a_gate_flashes_only_before_it_closes() {
+        let mut gate = Gate {
+            cells: vec![(1, 1)],
+            closed: false,
+            switches: 30,
+        };
+        assert!(!gate.is_closing(30 - GATE_WARNING - 1));
+        assert!(gate.is_closing(30 - GATE_WARNING));
+        assert!(gate.is_closing(30));
+        gate.closed = true;
+        assert!(!gate.is_closing(30));
+    }
+
+    #[test]
+    fn a_patrol_takes_a_step_every_few_ticks_and_its_obstacle_goes_along() {
+        let mut game = game();
+        game.field.add_obstacle((2, 2));
+        game.field.patrols = vec![Patrol::new(vec![(2, 2), (3, 2), (4, 2)])];
+        let mut walked = vec![];
+        for _ in 0..4 * PATROL_PACE {
+            game.update();
+            let position = game.field.patrols[0].position();
+            assert!(obstacles(&game).contains(&position));
+            assert_eq!(obstacles(&game).len(), 1);
+            walked.push(position);
+        }
+        assert_eq!(
+            walked,
+            [
+                (2, 2),
+                (3, 2),
+                (3, 2),
+                (4, 2),
+                (4, 2),
+                (3, 2),
+                (3, 2),
+                (2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_gate_closes_and_opens_again_on_time() {
+        let mut game = game();
+        let cells = vec![(2, 4), (3, 4)];
+        game.field.gates = vec![Gate {
+            cells: cells.clone(),
+            closed: false,
+            switches: 5,
+        }];
+        while game.ticks < 5 {
+            assert!(obstacles(&game).is_empty());
+            game.update();
+        }
+        assert!(game.field.gates[0].closed);
+        assert_eq!(obstacles(&game), cells);
+        while game.ticks < 5 + GATE_PERIOD {
+            assert!(game.field.gates[0].closed);
+            game.update();
+        }
+        assert!(!game.field.gates[0].closed);
+        assert!(obstacles(&game).is_empty());
+        assert_eq!(game.field.gates[0].switches, 5 + 2 * GATE_PERIOD);
+    }
+
+    #[test]
+    fn a_gate_waits_until_the_apple_is_out_of_it() {
+        let mut game = game();
+        game.field.gates = vec![Gate {
+            cells: vec![(2, 4), (3, 4)],
+            closed: false,
+            switches: 1,
+        }];
+        game.apples[0].position = (3, 4);
+        game.update();
+        game.update();
+        assert!(!game.field.gates[0].closed);
+        assert_eq!(game.field.gates[0].switches, 3);
+        game.apples[0].position = (0, 0);
+        game.update();
+        assert!(game.field.gates[0].closed);
+    }
+
+    #[test]
+    fn an_obstacle_grows_for_every_few_points() {
+        let mut game = game().with_growing_obstacles(2);
+        for (points, grown) in [(1, 0), (2, 1), (3, 1), (6, 3)] {
+            game.players[0].points = points;
+            game.update();
+            assert_eq!(obstacles(&game).len(), grown, "at {} points", points);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// pickups that show up next to the apple now and then and change the game for a while
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUpKind {
+    // the game runs at half the speed
+    SlowMotion,
+    // the game runs at twice the speed
+    Speed,
+    // the snake can cross its own body
+    Ghost,
+    // the tail gets shorter right away, the score stays
+    Shrink,
+    // every apple counts more
+    Multiplier,
+    // apples close by move towards the head
+    Magnet,
+}
+
+// picking up the same kind again adds to the time that is left, up to this many times its duration
+const MAX_STACKED_DURATIONS: u64 = 3;
+// the multiplier goes up by one every time it is picked up while it lasts
+const MAX_MULTIPLIER: u32 = 4;
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::SlowMotion,
+        PowerUpKind::Speed,
+        PowerUpKind::Ghost,
+        PowerUpKind::Shrink,
+        PowerUpKind::Multiplier,
+        PowerUpKind::Magnet,
+    ];
+
+    // in ticks, shrinking happens once so it doesn't last
+    pub fn duration(self) -> u64 {
+        match self {
+            PowerUpKind::SlowMotion => 60,
+            PowerUpKind::Speed => 60,
+            PowerUpKind::Ghost => 40,
+            PowerUpKind::Shrink => 0,
+            PowerUpKind::Multiplier => 100,
+            PowerUpKind::Magnet => 80,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::SlowMotion => "slow-mo",
+            PowerUpKind::Speed => "speed",
+            PowerUpKind::Ghost => "ghost",
+            PowerUpKind::Shrink => "shrink",
+            PowerUpKind::Multiplier => "multiplier",
+            PowerUpKind::Magnet => "magnet",
+        }
+    }
+}
+
+// one lying on the field
+#[derive(Clone, Copy, Debug)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub position: (u32, u32),
+    // the tick it disappears on when nobody picked it up
+    pub expires: u64,
+}
+
+// a power-up that was picked up and still goes on
+#[derive(Clone, Copy, Debug)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    // in ticks
+    pub remaining: u64,
+    // how often it was picked up while it lasted
+    pub stacks: u32,
+}
+
+// adds a picked up power-up to the effects of a snake, slow-mo and speed cancel each other out
+pub fn stack_effect(effects: &mut Vec<Effect>, kind: PowerUpKind) {
+    if kind.duration() == 0 {
+        return;
+    }
+    let opposite = match kind {
+        PowerUpKind::SlowMotion => Some(PowerUpKind::Speed),
+        PowerUpKind::Speed => Some(PowerUpKind::SlowMotion),
+        _ => None,
+    };
+    effects.retain(|effect| Some(effect.kind) != opposite);
+    match effects.iter_mut().find(|effect| effect.kind == kind) {
+        Some(effect) => {
+            effect.remaining =
+                (effect.remaining + kind.duration()).min(kind.duration() * MAX_STACKED_DURATIONS);
+            effect.stacks = (effect.stacks + 1).min(MAX_MULTIPLIER - 1);
+        }
+        None => effects.push(Effect {
+            kind,
+            remaining: kind.duration(),
+            stacks: 1,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{AppleKind, Direction};
+    use crate::{GameEvent, GameState, SnakeGame};
+
+    // a snake in the middle of an empty field heading to bigger x, the apple out of its way
+    fn game() -> SnakeGame {
+        let mut game = SnakeGame::from_seed(21, 21, false, (false, 0), 1);
+        game.apples[0].position = (0, 0);
+        game.resume();
+        game
+    }
+
+    // the cell the head moves onto next
+    fn ahead(game: &SnakeGame) -> (u32, u32) {
+        let (width, height) = game.field.size;
+        let head = game.snake().body[0].get().position;
+        game.snake().head_direction().step(head, width, height)
+    }
+
+    fn pick_up(game: &mut SnakeGame, kind: PowerUpKind) {
+        let position = ahead(game);
+        game.power_ups.push(PowerUp {
+            kind,
+            position,
+            expires: game.ticks + 100,
+        });
+        game.update();
+    }
+
+    #[test]
+    fn picking_up_the_same_again_adds_to_its_time() {
+        let mut effects = vec![];
+        stack_effect(&mut effects, PowerUpKind::Ghost);
+        assert_eq!((effects[0].remaining, effects[0].stacks), (40, 1));
+        effects[0].remaining = 15;
+        stack_effect(&mut effects, PowerUpKind::Ghost);
+        assert_eq!((effects[0].remaining, effects[0].stacks), (55, 2));
+        for _ in 0..5 {
+            stack_effect(&mut effects, PowerUpKind::Ghost);
+        }
+        assert_eq!(effects.len(), 1);
+        assert_eq!(effects[0].remaining, 40 * MAX_STACKED_DURATIONS);
+        assert_eq!(effects[0].stacks, MAX_MULTIPLIER - 1);
+    }
+
+    #[test]
+    fn slow_mo_and_speed_cancel_each_other_out() {
+        let mut effects = vec![];
+        stack_effect(&mut effects, PowerUpKind::Magnet);
+        stack_effect(&mut effects, PowerUpKind::SlowMotion);
+        stack_effect(&mut effects, PowerUpKind::Speed);
+        let kinds: Vec<PowerUpKind> = effects.iter().map(|effect| effect.kind).collect();
+        assert_eq!(kinds, [PowerUpKind::Magnet, PowerUpKind::Speed]);
+        // shrinking happens right away and doesn't last
+        stack_effect(&mut effects, PowerUpKind::Shrink);
+        assert_eq!(effects.len(), 2);
+    }
+
+    #[test]
+    fn effects_wear_off_and_change_the_pace() {
+        let mut game = game();
+        pick_up(&mut game, PowerUpKind::SlowMotion);
+        assert!(game.drain_events().contains(&GameEvent::PoweredUp));
+        assert!(game.power_ups.is_empty());
+        assert_eq!(game.pace(), 2.0);
+        // the tick it was picked up on counts
+        for _ in 1..PowerUpKind::SlowMotion.duration() {
+            assert!(game.players[0].effect(PowerUpKind::SlowMotion).is_some());
+            game.update();
+        }
+        assert!(game.players[0].effects.is_empty());
+        assert_eq!(game.pace(), 1.0);
+
+        pick_up(&mut game, PowerUpKind::Speed);
+        assert_eq!(game.pace(), 0.5);
+    }
+
+    #[test]
+    fn a_power_up_nobody_picks_up_goes_away() {
+        let mut game = game();
+        game.power_ups.push(PowerUp {
+            kind: PowerUpKind::Ghost,
+            position: (0, 20),
+            expires: 3,
+        });
+        game.update();
+        game.update();
+        assert_eq!(game.power_ups.len(), 1);
+        game.update();
+        assert!(game.power_ups.is_empty());
+    }
+
+    #[test]
+    fn the_multiplier_makes_apples_worth_more() {
+        let mut game = game();
+        for (stacks, points) in [(1, 2), (2, 5), (3, 9), (3, 13)] {
+            pick_up(&mut game, PowerUpKind::Multiplier);
+            assert_eq!(
+                game.players[0]
+                    .effect(PowerUpKind::Multiplier)
+                    .unwrap()
+                    .stacks,
+                stacks
+            );
+            game.apples[0].position = ahead(&game);
+            game.apples[0].kind = AppleKind::Normal;
+            game.update();
+            assert_eq!(game.score(), points);
+            game.apples[0].position = (0, 0);
+        }
+    }
+
+    #[test]
+    fn shrinking_takes_parts_off_but_not_points() {
+        let mut game = game();
+        for _ in 0..5 {
+            game.apples[0].position = ahead(&game);
+            game.update();
+        }
+        game.apples[0].position = (0, 0);
+        assert_eq!((game.snake().body.len(), game.score()), (7, 5));
+        pick_up(&mut game, PowerUpKind::Shrink);
+        assert_eq!((game.snake().body.len(), game.score()), (4, 5));
+        assert!(game.players[0].effects.is_empty());
+    }
+
+    #[test]
+    fn a_ghost_crosses_its_own_body() {
+        for ghost in [true, false] {
+            let mut game = game();
+            for _ in 0..4 {
+                game.apples[0].position = ahead(&game);
+                game.update();
+            }
+            game.apples[0].position = (0, 0);
+            if ghost {
+                pick_up(&mut game, PowerUpKind::Ghost);
+            }
+            // a tight loop runs the head into the body
+            for direction in [Direction::UP, Direction::RIGHT, Direction::DOWN] {
+                game.change_head_direction(direction);
+                game.update();
+            }
+            assert_eq!(game.players[0].alive, ghost);
+            assert_eq!(game.state == GameState::Running, ghost);
+        }
+    }
+}
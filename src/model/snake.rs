@@ -1,6 +1,8 @@
 use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Direction {
     UP,
     DOWN,
@@ -8,6 +10,61 @@ pub enum Direction {
     RIGHT,
 }
 
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::UP,
+        Direction::DOWN,
+        Direction::LEFT,
+        Direction::RIGHT,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+            Direction::LEFT => Direction::RIGHT,
+            Direction::RIGHT => Direction::LEFT,
+        }
+    }
+
+    // where the head ends up after one move, the same rules as move_snake (left goes towards
+    // bigger x and the field wraps around)
+    pub fn step(self, position: (u32, u32), width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Direction::UP => (position.0, (position.1 + height - 1) % height),
+            Direction::DOWN => (position.0, (position.1 + 1) % height),
+            Direction::LEFT => ((position.0 + 1) % width, position.1),
+            Direction::RIGHT => ((position.0 + width - 1) % width, position.1),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Direction::UP => "UP",
+            Direction::DOWN => "DOWN",
+            Direction::LEFT => "LEFT",
+            Direction::RIGHT => "RIGHT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "UP" => Ok(Direction::UP),
+            "DOWN" => Ok(Direction::DOWN),
+            "LEFT" => Ok(Direction::LEFT),
+            "RIGHT" => Ok(Direction::RIGHT),
+            _ => Err(format!("unknown direction \"{}\"", s)),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct BodyProperties {
     // the position in x and y coordinates
@@ -15,8 +72,11 @@ pub struct BodyProperties {
     direction: Direction,
 }
 
+#[derive(Clone)]
 pub struct Snake {
     pub body: Vec<Cell<BodyProperties>>,
+    // where the tail was before the last move, that is where the snake grows into
+    vacated: Option<BodyProperties>,
 }
 
 impl Snake {
@@ -33,6 +93,7 @@ impl Snake {
 
         Snake {
             body: Vec::from([Cell::new(head), Cell::new(tail)]),
+            vacated: None,
         }
     }
 
@@ -119,6 +180,25 @@ impl Snake {
             *body_values = sub_1.to_owned();
             sub_1 = sub_2.clone();
         }
+        // after the loop this holds the old tail
+        self.vacated = Some(sub_1.get());
+    }
+
+    // moves the head somewhere else without turning it, used by portals
+    pub fn teleport_head(&mut self, position: (u32, u32)) {
+        let head = self.body[0].get();
+        self.body[0].set(BodyProperties { position, ..head });
+    }
+
+    // takes parts off the tail, the head and one part always stay
+    pub fn shrink(&mut self, parts: usize) {
+        let length = self.body.len().saturating_sub(parts).max(2);
+        self.body.truncate(length);
+        self.vacated = None;
+    }
+
+    pub fn head_direction(&self) -> Direction {
+        self.body[0].get().direction
     }
 
     pub fn set_direction(&mut self, direction: Direction) {
@@ -144,6 +224,12 @@ impl Snake {
 
     // need to optimize this so it can conform to the wrapping feature as its crashing
     pub fn grow_snake(&mut self, width: u32, height: u32) {
+        // guessing from the direction of the tail goes wrong right after the tail turned,
+        // so the cell it just left is used whenever there is one
+        if let Some(tail) = self.vacated.take() {
+            self.body.push(Cell::new(tail));
+            return;
+        }
         self.body.push(Cell::new(BodyProperties {
             position: match self.body[self.body.len() - 1].get().direction {
                 Direction::LEFT => {
@@ -0,0 +1,1332 @@
+// playing over the network: one server runs the game and everyone else joins it
+//
+// every message is one line of json, like the bot protocol. a client starts with
+//   {"type":"hello","name":"ana"} or {"type":"hello","name":"ana","token":"..."} to get its seat back
+// and gets {"type":"welcome","player":0,"token":"...","width":21,...} or {"type":"refused","reason":"..."}.
+// spectators say {"type":"hello","watch":true} instead, get a welcome with no player and then
+// everything the players get, but anything else they send besides pings is ignored.
+// after that the client sends {"type":"ready","ready":true}, {"type":"turn","direction":"UP"} and
+// {"type":"ping","id":3}, the server sends:
+//   {"type":"lobby","players":[{"name":"ana","ready":true,"connected":true}]}
+//   {"type":"start","seed":7,"width":21,"height":21,"border":false,"obstacles":0,
+//    "power_ups":false,"apples":1,"apple_kinds":false,"edges":"wrap,wrap,wrap,wrap","portals":0,
+//    "patrols":0,"gates":0,"growing_obstacles":0,
+//    "shrinking_arena":0,"mode":"classic","players":2,"tick":0,"moves":[[[4,"UP"]],[]]}
+//   {"type":"tick","tick":12,"turns":[[1,"DOWN"]]}
+//   {"type":"waiting","players":["bob"]}
+//   {"type":"pong","id":3}
+// the server is the only one deciding anything, the clients play the same game from the seed and
+// the turns of every tick so nothing but the turns has to be sent. a client that comes back in
+// the middle of a game gets the turns so far with the start message and catches up
+use crate::controller::{HumanController, ReplayController};
+use crate::mode::Mode;
+use crate::model::{Direction, Edges};
+use crate::{GameState, SnakeGame, MAX_SIDE, MIN_SIDE};
+use serde_json::{json, Value};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// one snake per color
+pub const MAX_PLAYERS: usize = 4;
+// how long the game waits for a player that lost the connection before going on without them
+const RECONNECT_TIME: Duration = Duration::from_secs(15);
+// the longest message is the start of a game joined halfway with every turn so far, a peer that
+// sends more than this without a line break isn't speaking the protocol and gets dropped
+const MAX_LINE: usize = 1024 * 1024;
+// a client that lets this much pile up isn't reading and gets dropped, it can join again to catch
+// up. the catch up itself has to fit with the ticks that come in behind it or it never gets back
+const MAX_BACKLOG: usize = 2 * MAX_LINE;
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+// a socket that never blocks, the lines are collected as they come in and sent as they fit
+struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    // messages that came in with the welcome and still have to be looked at
+    unread: Vec<Value>,
+    backlog: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        // the turns are tiny and have to arrive right away
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            received: vec![],
+            unread: vec![],
+            backlog: vec![],
+            closed: false,
+        })
+    }
+
+    fn send(&mut self, message: &Value) {
+        self.backlog
+            .extend_from_slice(format!("{}\n", message).as_bytes());
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while !self.closed && !self.backlog.is_empty() {
+            match self.stream.write(&self.backlog) {
+                Ok(0) => self.closed = true,
+                Ok(written) => {
+                    self.backlog.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        if self.backlog.len() > MAX_BACKLOG {
+            self.closed = true;
+        }
+    }
+
+    // the messages that arrived since the last call, lines that aren't json close the connection
+    fn receive(&mut self) -> Vec<Value> {
+        let mut messages = std::mem::take(&mut self.unread);
+        let mut buffer = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => {
+                    self.received.extend_from_slice(&buffer[..read]);
+                    self.read_lines(&mut messages);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        messages
+    }
+
+    // takes the complete lines out of what was received, the rest waits for its line break
+    fn read_lines(&mut self, messages: &mut Vec<Value>) {
+        while let Some(end) = self.received.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            match serde_json::from_slice(&line) {
+                Ok(message) => messages.push(message),
+                Err(_) => self.closed = true,
+            }
+        }
+        if self.received.len() > MAX_LINE {
+            self.received = vec![];
+            self.closed = true;
+        }
+    }
+}
+
+fn kind(message: &Value) -> &str {
+    message.get("type").and_then(Value::as_str).unwrap_or("")
+}
+
+fn direction_of(value: Option<&Value>) -> Option<Direction> {
+    value?.as_str()?.parse().ok()
+}
+
+fn moves_to_json(moves: &[(u64, Direction)]) -> Value {
+    moves
+        .iter()
+        .map(|(tick, direction)| json!([tick, direction.to_string()]))
+        .collect()
+}
+
+fn moves_from_json(value: &Value) -> Vec<(u64, Direction)> {
+    let Some(moves) = value.as_array() else {
+        return vec![];
+    };
+    moves
+        .iter()
+        .filter_map(|entry| Some((entry.get(0)?.as_u64()?, direction_of(entry.get(1))?)))
+        .collect()
+}
+
+// what every game on a server is played on
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Board {
+    pub width: u32,
+    pub height: u32,
+    pub border: bool,
+    pub obstacles: u32,
+    pub power_ups: bool,
+    pub apples: u32,
+    pub apple_kinds: bool,
+    pub edges: Edges,
+    pub portals: u32,
+    pub patrols: u32,
+    pub gates: u32,
+    pub growing_obstacles: u32,
+    pub shrinking_arena: u32,
+    pub mode: Mode,
+}
+
+impl Board {
+    fn of(game: &SnakeGame) -> Self {
+        Self {
+            width: game.field.size.0,
+            height: game.field.size.1,
+            border: game.field.has_border(),
+            obstacles: game.field.num_obstacles(),
+            power_ups: game.spawns_power_ups,
+            apples: game.num_apples,
+            apple_kinds: game.apple_kinds,
+            edges: game.field.edges,
+            portals: game.field.num_portals,
+            patrols: game.field.num_patrols,
+            gates: game.field.num_gates,
+            growing_obstacles: game.field.growing_obstacles,
+            shrinking_arena: game.field.shrinking_arena,
+            mode: game.mode,
+        }
+    }
+
+    // adds the board to a welcome or start message, board_of reads it back
+    fn describe(&self, message: &mut Value) {
+        message["width"] = json!(self.width);
+        message["height"] = json!(self.height);
+        message["border"] = json!(self.border);
+        message["obstacles"] = json!(self.obstacles);
+        message["power_ups"] = json!(self.power_ups);
+        message["apples"] = json!(self.apples);
+        message["apple_kinds"] = json!(self.apple_kinds);
+        message["edges"] = json!(self.edges.to_string());
+        message["portals"] = json!(self.portals);
+        message["patrols"] = json!(self.patrols);
+        message["gates"] = json!(self.gates);
+        message["growing_obstacles"] = json!(self.growing_obstacles);
+        message["shrinking_arena"] = json!(self.shrinking_arena);
+        message["mode"] = json!(self.mode.to_string());
+    }
+
+    fn new_game(&self, seed: u64, players: usize) -> SnakeGame {
+        SnakeGame::from_seed(
+            self.width,
+            self.height,
+            self.border,
+            (self.obstacles > 0, self.obstacles),
+            seed,
+        )
+        .with_players(players)
+        .with_power_ups(self.power_ups)
+        .with_apples(self.apples as usize, self.apple_kinds)
+        .with_edges(self.edges)
+        .with_portals(self.portals as usize)
+        .with_moving_obstacles(self.patrols as usize, self.gates as usize)
+        .with_growing_obstacles(self.growing_obstacles)
+        .with_shrinking_arena(self.shrinking_arena)
+        .with_mode(self.mode)
+    }
+}
+
+pub struct ServerConfig {
+    pub board: Board,
+    pub tick: Duration,
+    // every game starts from this seed when set, a random one otherwise
+    pub seed: Option<u64>,
+}
+
+// someone who joined, their place in the list is their player number in the game
+struct Seat {
+    name: String,
+    // lets the same person take the seat back after losing the connection
+    token: String,
+    connection: Option<Connection>,
+    disconnected_at: Option<Instant>,
+    ready: bool,
+    pilot: HumanController,
+}
+
+// read only connections, they hear everything about the game but can't change anything
+#[derive(Default)]
+struct Spectators {
+    connections: Vec<Connection>,
+}
+
+impl Spectators {
+    fn add(&mut self, mut connection: Connection, board: Board, game: Option<&SnakeGame>) {
+        let mut welcome = json!({"type": "welcome", "player": null});
+        board.describe(&mut welcome);
+        connection.send(&welcome);
+        if let Some(game) = game {
+            connection.send(&start_message(game));
+        }
+        self.connections.push(connection);
+    }
+
+    fn send(&mut self, message: &Value) {
+        for connection in &mut self.connections {
+            connection.send(message);
+        }
+    }
+
+    // answers the pings and forgets whoever left
+    fn poll(&mut self) {
+        for connection in &mut self.connections {
+            for message in connection.receive() {
+                if kind(&message) == "ping" {
+                    connection.send(&pong(&message));
+                }
+            }
+            connection.flush();
+        }
+        self.connections.retain(|connection| !connection.closed);
+    }
+}
+
+fn is_watching(hello: &Value) -> bool {
+    hello.get("watch").and_then(Value::as_bool) == Some(true)
+}
+
+fn pong(ping: &Value) -> Value {
+    json!({"type": "pong", "id": ping.get("id").cloned().unwrap_or(Value::Null)})
+}
+
+// the connections that haven't said hello yet, with the hellos of the ones that did
+fn greet(listener: &TcpListener, newcomers: &mut Vec<Connection>) -> Vec<(Connection, Value)> {
+    while let Ok((stream, _)) = listener.accept() {
+        if let Ok(connection) = Connection::new(stream) {
+            newcomers.push(connection);
+        }
+    }
+    let mut greeted = vec![];
+    for mut connection in std::mem::take(newcomers) {
+        let mut messages = connection.receive();
+        match messages.iter().position(|message| kind(message) == "hello") {
+            Some(hello) => {
+                // whatever came right after the hello is handled once they are in
+                connection.unread = messages.split_off(hello + 1);
+                greeted.push((connection, messages.swap_remove(hello)));
+            }
+            None if !connection.closed => newcomers.push(connection),
+            None => {}
+        }
+    }
+    greeted
+}
+
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    // connections that haven't said hello yet
+    newcomers: Vec<Connection>,
+    seats: Vec<Seat>,
+    spectators: Spectators,
+    game: Option<SnakeGame>,
+    next_tick: Instant,
+}
+
+impl Server {
+    pub fn bind(port: u16, config: ServerConfig) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        Ok(Self {
+            listener,
+            config,
+            newcomers: vec![],
+            seats: vec![],
+            spectators: Spectators::default(),
+            game: None,
+            next_tick: Instant::now(),
+        })
+    }
+
+    // serves games until the process ends
+    pub fn run(mut self) {
+        loop {
+            self.poll();
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub fn spawn(self) {
+        thread::spawn(move || self.run());
+    }
+
+    fn poll(&mut self) {
+        for (connection, hello) in greet(&self.listener, &mut self.newcomers) {
+            if is_watching(&hello) {
+                self.spectators
+                    .add(connection, self.config.board, self.game.as_ref());
+                self.broadcast_seats();
+                self.broadcast_waiting();
+            } else {
+                self.seat(connection, &hello);
+            }
+        }
+        self.spectators.poll();
+
+        let mut changed = false;
+        for index in 0..self.seats.len() {
+            let Some(connection) = &mut self.seats[index].connection else {
+                continue;
+            };
+            for message in connection.receive() {
+                changed |= self.handle(index, &message);
+            }
+        }
+        changed |= self.drop_lost_connections();
+
+        if self.game.is_none() && self.everyone_ready() {
+            self.start();
+        } else if self.game.is_some() && Instant::now() >= self.next_tick {
+            self.tick();
+        } else if changed {
+            self.broadcast_seats();
+        }
+
+        for seat in &mut self.seats {
+            if let Some(connection) = &mut seat.connection {
+                connection.flush();
+            }
+        }
+    }
+
+    fn seat(&mut self, mut connection: Connection, hello: &Value) {
+        let name = hello
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("Player")
+            .to_string();
+        let token = hello.get("token").and_then(Value::as_str);
+        let returning = self
+            .seats
+            .iter()
+            .position(|seat| seat.connection.is_none() && Some(seat.token.as_str()) == token);
+        let index = match returning {
+            Some(index) => index,
+            None if self.game.is_some() => {
+                connection.send(&json!({
+                    "type": "refused",
+                    "reason": "a game is running, join again once it is over",
+                }));
+                return;
+            }
+            None if self.seats.len() >= MAX_PLAYERS => {
+                connection.send(&json!({"type": "refused", "reason": "the game is full"}));
+                return;
+            }
+            None => {
+                self.seats.push(Seat {
+                    name,
+                    token: format!("{:016x}", rand::random::<u64>()),
+                    connection: None,
+                    disconnected_at: None,
+                    ready: false,
+                    pilot: HumanController::new(),
+                });
+                self.seats.len() - 1
+            }
+        };
+
+        let board = self.config.board;
+        let seat = &mut self.seats[index];
+        let mut welcome = json!({
+            "type": "welcome",
+            "player": index,
+            "token": seat.token,
+            "tick_ms": self.config.tick.as_millis() as u64,
+        });
+        board.describe(&mut welcome);
+        connection.send(&welcome);
+        seat.connection = Some(connection);
+        seat.disconnected_at = None;
+        if let Some(game) = &self.game {
+            // the game went on without them, the turns so far let them catch up
+            let start = start_message(game);
+            self.seats[index].connection.as_mut().unwrap().send(&start);
+        }
+        self.broadcast_seats();
+        self.broadcast_waiting();
+    }
+
+    // true when the lobby has to hear about it
+    fn handle(&mut self, index: usize, message: &Value) -> bool {
+        let seat = &mut self.seats[index];
+        match kind(message) {
+            "ready" if self.game.is_none() => {
+                seat.ready = message.get("ready").and_then(Value::as_bool) == Some(true);
+                true
+            }
+            "turn" => {
+                if let (Some(direction), Some(_)) =
+                    (direction_of(message.get("direction")), &self.game)
+                {
+                    seat.pilot.push(direction);
+                }
+                false
+            }
+            "ping" => {
+                if let Some(connection) = &mut seat.connection {
+                    connection.send(&pong(message));
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // in the lobby a seat is given up with the connection, in a game it is kept for coming back
+    fn drop_lost_connections(&mut self) -> bool {
+        let mut changed = false;
+        for seat in &mut self.seats {
+            if seat
+                .connection
+                .as_ref()
+                .is_some_and(|connection| connection.closed)
+            {
+                seat.connection = None;
+                seat.disconnected_at = Some(Instant::now());
+                changed = true;
+            }
+        }
+        if self.game.is_none() {
+            self.seats.retain(|seat| seat.connection.is_some());
+        }
+        if changed {
+            self.broadcast_waiting();
+        }
+        changed
+    }
+
+    fn everyone_ready(&self) -> bool {
+        !self.seats.is_empty() && self.seats.iter().all(|seat| seat.ready)
+    }
+
+    fn start(&mut self) {
+        let seed = self.config.seed.unwrap_or_else(rand::random);
+        let mut game = self.config.board.new_game(seed, self.seats.len());
+        game.resume();
+        for seat in &mut self.seats {
+            seat.pilot.clear();
+        }
+        self.broadcast(&start_message(&game));
+        self.game = Some(game);
+        self.next_tick = Instant::now() + self.config.tick;
+    }
+
+    // the players that lost the connection but may still come back
+    fn waiting_for(&self) -> Vec<&str> {
+        if self.game.is_none() {
+            return vec![];
+        }
+        self.seats
+            .iter()
+            .filter(|seat| {
+                seat.disconnected_at
+                    .is_some_and(|since| since.elapsed() < RECONNECT_TIME)
+            })
+            .map(|seat| seat.name.as_str())
+            .collect()
+    }
+
+    fn tick(&mut self) {
+        // slow connections don't hold the game up, only lost ones for a while
+        if !self.waiting_for().is_empty() {
+            self.next_tick = Instant::now() + self.config.tick;
+            return;
+        }
+        let game = self.game.as_mut().unwrap();
+        let tick = game.ticks;
+        let mut turns = vec![];
+        for (index, seat) in self.seats.iter_mut().enumerate() {
+            let taken = game.players[index].moves.len();
+            game.drive_player(index, &mut seat.pilot);
+            if let Some((_, direction)) = game.players[index].moves.get(taken) {
+                turns.push(json!([index, direction.to_string()]));
+            }
+        }
+        game.update();
+        game.drain_events();
+        let is_over = game.state == GameState::GameOver;
+        // slow-mo and speed power-ups change the pace for everyone
+        let pace = game.pace();
+        self.broadcast(&json!({"type": "tick", "tick": tick, "turns": turns}));
+        self.next_tick += self.config.tick.mul_f64(pace);
+
+        if is_over {
+            // back to the lobby for another round, whoever left by now is gone for good
+            self.game = None;
+            self.seats.retain(|seat| seat.connection.is_some());
+            for seat in &mut self.seats {
+                seat.ready = false;
+                seat.disconnected_at = None;
+            }
+            self.broadcast_seats();
+        }
+    }
+
+    fn broadcast(&mut self, message: &Value) {
+        for seat in &mut self.seats {
+            if let Some(connection) = &mut seat.connection {
+                connection.send(message);
+            }
+        }
+        self.spectators.send(message);
+    }
+
+    fn broadcast_seats(&mut self) {
+        let players: Vec<Value> = self
+            .seats
+            .iter()
+            .map(|seat| {
+                json!({
+                    "name": seat.name,
+                    "ready": seat.ready,
+                    "connected": seat.connection.is_some(),
+                })
+            })
+            .collect();
+        self.broadcast(&json!({"type": "lobby", "players": players}));
+    }
+
+    fn broadcast_waiting(&mut self) {
+        let message = json!({"type": "waiting", "players": self.waiting_for()});
+        self.broadcast(&message);
+    }
+}
+
+fn start_message(game: &SnakeGame) -> Value {
+    let moves: Vec<Value> = game
+        .players
+        .iter()
+        .map(|player| moves_to_json(&player.moves))
+        .collect();
+    let mut start = json!({
+        "type": "start",
+        "seed": game.seed,
+        "players": game.players.len(),
+        "tick": game.ticks,
+        "moves": moves,
+    });
+    Board::of(game).describe(&mut start);
+    start
+}
+
+// streams a game played on this computer to spectators, the frontend hands it the game every tick
+pub struct Broadcaster {
+    listener: TcpListener,
+    newcomers: Vec<Connection>,
+    spectators: Spectators,
+    // what the spectators already know: the seed, the number of players and the ticks
+    sent: Option<(u64, usize, u64)>,
+}
+
+impl Broadcaster {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        Ok(Self {
+            listener,
+            newcomers: vec![],
+            spectators: Spectators::default(),
+            sent: None,
+        })
+    }
+
+    pub fn publish(&mut self, game: &SnakeGame) {
+        let same_game = self.sent.is_some_and(|(seed, players, ticks)| {
+            seed == game.seed && players == game.players.len() && ticks <= game.ticks
+        });
+        if !same_game {
+            self.spectators.send(&start_message(game));
+        } else if let Some((_, _, sent_ticks)) = self.sent {
+            for tick in sent_ticks..game.ticks {
+                let mut turns = vec![];
+                for (index, player) in game.players.iter().enumerate() {
+                    // the newest turns are at the end
+                    let recent = player.moves.iter().rev().take_while(|(at, _)| *at >= tick);
+                    let mut taken: Vec<Value> = recent
+                        .filter(|(at, _)| *at == tick)
+                        .map(|(_, direction)| json!([index, direction.to_string()]))
+                        .collect();
+                    taken.reverse();
+                    turns.extend(taken);
+                }
+                self.spectators
+                    .send(&json!({"type": "tick", "tick": tick, "turns": turns}));
+            }
+        }
+        self.sent = Some((game.seed, game.players.len(), game.ticks));
+
+        // the ones coming in now start from the game as it is
+        for (mut connection, hello) in greet(&self.listener, &mut self.newcomers) {
+            if is_watching(&hello) {
+                self.spectators.add(connection, Board::of(game), Some(game));
+            } else {
+                connection.send(&json!({
+                    "type": "refused",
+                    "reason": "this game can only be watched",
+                }));
+                connection.flush();
+            }
+        }
+        self.spectators.poll();
+    }
+}
+
+// a seat as the lobby shows it
+#[derive(Clone, Debug)]
+pub struct LobbySeat {
+    pub name: String,
+    pub ready: bool,
+    pub connected: bool,
+}
+
+pub struct NetClient {
+    address: String,
+    name: String,
+    token: String,
+    watching: bool,
+    // the snake this client steers, none for spectators
+    pub player: Option<usize>,
+    pub board: Board,
+    pub tick: Duration,
+    connection: Option<Connection>,
+    last_attempt: Instant,
+    // why the last try to get back in failed
+    problem: Option<String>,
+    pub lobby: Vec<LobbySeat>,
+    pub waiting_for: Vec<String>,
+    ready: bool,
+    playing: bool,
+    ping_sent: Option<(u64, Instant)>,
+    pings: u64,
+    pub latency: Option<Duration>,
+}
+
+impl NetClient {
+    // waits until the server lets us in
+    pub fn join(address: &str, name: &str) -> Result<Self, String> {
+        Self::connect(address, name, false)
+    }
+
+    // only looks at the game, works with hosted games and broadcasts alike
+    pub fn watch(address: &str) -> Result<Self, String> {
+        Self::connect(address, "spectator", true)
+    }
+
+    fn connect(address: &str, name: &str, watching: bool) -> Result<Self, String> {
+        let mut client = Self {
+            address: address.to_string(),
+            name: name.to_string(),
+            token: String::new(),
+            watching,
+            player: None,
+            board: Board::default(),
+            tick: Duration::ZERO,
+            connection: None,
+            last_attempt: Instant::now(),
+            problem: None,
+            lobby: vec![],
+            waiting_for: vec![],
+            ready: false,
+            playing: false,
+            ping_sent: None,
+            pings: 0,
+            latency: None,
+        };
+        let (connection, welcome) = connect(address, &client.hello())?;
+        client.welcome(&welcome)?;
+        client.connection = Some(connection);
+        Ok(client)
+    }
+
+    fn hello(&self) -> Value {
+        let mut hello = json!({"type": "hello", "name": self.name});
+        if self.watching {
+            hello["watch"] = json!(true);
+        } else if !self.token.is_empty() {
+            hello["token"] = json!(self.token);
+        }
+        hello
+    }
+
+    // a welcome with a board that can't be played leaves everything as it was
+    fn welcome(&mut self, welcome: &Value) -> Result<(), String> {
+        let board = board_of(welcome)?;
+        let number = |key: &str| welcome.get(key).and_then(Value::as_u64).unwrap_or(0);
+        self.token = welcome
+            .get("token")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string();
+        self.player = welcome
+            .get("player")
+            .and_then(Value::as_u64)
+            .map(|player| player as usize);
+        self.board = board;
+        self.tick = Duration::from_millis(number("tick_ms"));
+        // a new seat in the lobby starts out not ready
+        self.ready = false;
+        Ok(())
+    }
+
+    // something to show until the first game starts
+    pub fn new_game(&self) -> SnakeGame {
+        self.board.new_game(0, 1)
+    }
+
+    pub fn turn(&mut self, direction: Direction) {
+        if self.player.is_some() {
+            self.send(&json!({"type": "turn", "direction": direction.to_string()}));
+        }
+    }
+
+    pub fn toggle_ready(&mut self) {
+        if self.playing || self.player.is_none() {
+            return;
+        }
+        self.ready = !self.ready;
+        let ready = self.ready;
+        self.send(&json!({"type": "ready", "ready": ready}));
+    }
+
+    fn send(&mut self, message: &Value) {
+        if let Some(connection) = &mut self.connection {
+            connection.send(message);
+        }
+    }
+
+    // brings the game up to date with the server, a new game replaces the old one
+    pub fn sync(&mut self, game: &mut SnakeGame) {
+        self.reconnect();
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        let messages = connection.receive();
+        for message in &messages {
+            self.handle(message, game);
+        }
+
+        let ping_due = match self.ping_sent {
+            Some((_, sent)) => sent.elapsed() >= PING_INTERVAL,
+            None => true,
+        };
+        if ping_due {
+            self.pings += 1;
+            let id = self.pings;
+            self.ping_sent = Some((id, Instant::now()));
+            self.send(&json!({"type": "ping", "id": id}));
+        }
+
+        if let Some(connection) = &mut self.connection {
+            connection.flush();
+            if connection.closed {
+                self.connection = None;
+                self.last_attempt = Instant::now();
+                self.latency = None;
+            }
+        }
+    }
+
+    fn handle(&mut self, message: &Value, game: &mut SnakeGame) {
+        match kind(message) {
+            "lobby" => {
+                let players = message.get("players").and_then(Value::as_array);
+                self.lobby = players
+                    .into_iter()
+                    .flatten()
+                    .map(|seat| LobbySeat {
+                        name: seat
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .unwrap_or("")
+                            .to_string(),
+                        ready: seat.get("ready").and_then(Value::as_bool) == Some(true),
+                        connected: seat.get("connected").and_then(Value::as_bool) == Some(true),
+                    })
+                    .collect();
+                if let Some(seat) = self.player.and_then(|player| self.lobby.get(player)) {
+                    self.ready = seat.ready;
+                }
+            }
+            "waiting" => {
+                let players = message.get("players").and_then(Value::as_array);
+                self.waiting_for = players
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|name| name.as_str().map(str::to_string))
+                    .collect();
+            }
+            "start" => match self.catch_up(message) {
+                Ok(started) => {
+                    *game = started;
+                    self.playing = game.state != GameState::GameOver;
+                }
+                // a server that sends a game that can't be played isn't one to stay with
+                Err(e) => {
+                    self.problem = Some(e);
+                    if let Some(connection) = &mut self.connection {
+                        connection.closed = true;
+                    }
+                }
+            },
+            "tick" => {
+                let tick = message.get("tick").and_then(Value::as_u64);
+                if !self.playing || tick != Some(game.ticks) {
+                    return;
+                }
+                let turns = message.get("turns").and_then(Value::as_array);
+                for turn in turns.into_iter().flatten() {
+                    let player = turn
+                        .get(0)
+                        .and_then(Value::as_u64)
+                        .map(|player| player as usize);
+                    if let (Some(player), Some(direction)) = (player, direction_of(turn.get(1))) {
+                        if player < game.players.len() {
+                            game.turn(player, direction);
+                        }
+                    }
+                }
+                game.update();
+                if game.state == GameState::GameOver {
+                    self.playing = false;
+                }
+            }
+            "pong" => {
+                if let Some((id, sent)) = self.ping_sent {
+                    if message.get("id").and_then(Value::as_u64) == Some(id) {
+                        self.latency = Some(sent.elapsed());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // the game the server is playing, with every turn up to now played out. a broadcast can
+    // switch boards between games so the start brings the board along
+    fn catch_up(&mut self, start: &Value) -> Result<SnakeGame, String> {
+        let board = board_of(start)?;
+        let seed = start.get("seed").and_then(Value::as_u64).unwrap_or(0);
+        let players = start.get("players").and_then(Value::as_u64).unwrap_or(1);
+        if !(1..=MAX_PLAYERS as u64).contains(&players) {
+            return Err(format!("the server started a game for {} players", players));
+        }
+        let players = players as usize;
+        let tick = start.get("tick").and_then(Value::as_u64).unwrap_or(0);
+        self.board = board;
+        let mut game = self.board.new_game(seed, players);
+        let mut replays: Vec<ReplayController> = (0..players)
+            .map(|player| {
+                let moves = start.get("moves").and_then(|moves| moves.get(player));
+                ReplayController::from(&moves.map(moves_from_json).unwrap_or_default())
+            })
+            .collect();
+        game.resume();
+        while game.ticks < tick && game.state == GameState::Running {
+            for (player, replay) in replays.iter_mut().enumerate() {
+                game.drive_player(player, replay);
+            }
+            game.update();
+        }
+        // the sounds of the turns that were missed would all play at once
+        game.drain_events();
+        Ok(game)
+    }
+
+    // tries to get the seat back once a second after losing the connection
+    fn reconnect(&mut self) {
+        if self.connection.is_some() || self.last_attempt.elapsed() < RETRY_INTERVAL {
+            return;
+        }
+        self.last_attempt = Instant::now();
+        match connect(&self.address, &self.hello()) {
+            Ok((connection, welcome)) => match self.welcome(&welcome) {
+                Ok(()) => {
+                    self.connection = Some(connection);
+                    self.problem = None;
+                    self.ping_sent = None;
+                }
+                Err(e) => self.problem = Some(e),
+            },
+            Err(e) => self.problem = Some(e),
+        }
+    }
+
+    // one line about where things stand, for the frontends to show
+    pub fn status(&self) -> String {
+        if self.connection.is_none() {
+            return match &self.problem {
+                Some(problem) => format!("Connection lost, reconnecting ({})", problem),
+                None => "Connection lost, reconnecting".to_string(),
+            };
+        }
+        let ping = match self.latency {
+            Some(latency) => format!(" (ping {} ms)", latency.as_millis()),
+            None => String::new(),
+        };
+        if !self.waiting_for.is_empty() {
+            return format!(
+                "Waiting for {} to come back{}",
+                self.waiting_for.join(", "),
+                ping
+            );
+        }
+        let Some(player) = self.player else {
+            // a broadcast has no lobby to show
+            return match self.playing || self.lobby.is_empty() {
+                true => format!("Watching{}", ping),
+                false => format!("Watching the lobby: {}{}", self.seats(None), ping),
+            };
+        };
+        if self.playing {
+            return format!("Playing as player {}{}", player + 1, ping);
+        }
+        let hint = if self.ready {
+            "waiting for the others"
+        } else {
+            "press <R> when ready"
+        };
+        format!("Lobby: {}, {}{}", self.seats(Some(player)), hint, ping)
+    }
+
+    fn seats(&self, player: Option<usize>) -> String {
+        let seats: Vec<String> = self
+            .lobby
+            .iter()
+            .enumerate()
+            .map(|(index, seat)| {
+                let ready = if seat.ready { " (ready)" } else { "" };
+                let you = if Some(index) == player { " (you)" } else { "" };
+                format!("{}{}{}", seat.name, you, ready)
+            })
+            .collect();
+        seats.join(", ")
+    }
+}
+
+// the board of a welcome or a start, one the game can't be started on is refused
+fn board_of(message: &Value) -> Result<Board, String> {
+    let number = |key: &str| message.get(key).and_then(Value::as_u64).unwrap_or(0) as u32;
+    let side = |key: &str| {
+        let side = message.get(key).and_then(Value::as_u64).unwrap_or(0);
+        u32::try_from(side)
+            .ok()
+            .filter(|side| (MIN_SIDE..=MAX_SIDE).contains(side))
+            .ok_or(format!(
+                "the server sent a board with a {} of {}",
+                key, side
+            ))
+    };
+    Ok(Board {
+        width: side("width")?,
+        height: side("height")?,
+        border: message.get("border").and_then(Value::as_bool) == Some(true),
+        obstacles: number("obstacles"),
+        power_ups: message.get("power_ups").and_then(Value::as_bool) == Some(true),
+        apples: number("apples"),
+        apple_kinds: message.get("apple_kinds").and_then(Value::as_bool) == Some(true),
+        edges: message
+            .get("edges")
+            .and_then(Value::as_str)
+            .and_then(|edges| edges.parse().ok())
+            .unwrap_or_default(),
+        portals: number("portals"),
+        patrols: number("patrols"),
+        gates: number("gates"),
+        growing_obstacles: number("growing_obstacles"),
+        shrinking_arena: number("shrinking_arena"),
+        mode: message
+            .get("mode")
+            .and_then(Value::as_str)
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or_default(),
+    })
+}
+
+// opens a connection and says hello, the welcome comes back with it
+fn connect(address: &str, hello: &Value) -> Result<(Connection, Value), String> {
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to find \"{}\": {}", address, e))?;
+    let mut last_error = format!("\"{}\" has no address", address);
+    for socket_address in addresses {
+        let stream = match TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT) {
+            Ok(stream) => stream,
+            Err(e) => {
+                last_error = format!("Failed to connect to {}: {}", address, e);
+                continue;
+            }
+        };
+        let mut connection = Connection::new(stream).map_err(|e| e.to_string())?;
+        connection.send(hello);
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        // whatever comes in before the answer is kept for later like what comes after it
+        let mut messages = vec![];
+        while Instant::now() < deadline && !connection.closed {
+            messages.extend(connection.receive());
+            let Some(answer) = messages
+                .iter()
+                .position(|message| matches!(kind(message), "welcome" | "refused"))
+            else {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+            let message = messages.remove(answer);
+            connection.unread = messages;
+            if kind(&message) == "refused" {
+                let reason = message.get("reason").and_then(Value::as_str);
+                return Err(format!(
+                    "The server refused to let us in: {}",
+                    reason.unwrap_or("no reason given")
+                ));
+            }
+            return Ok((connection, message));
+        }
+        return Err(format!("{} didn't answer like a snake server", address));
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::tests::snapshot;
+    use std::io::{BufRead, BufReader};
+
+    // two ends of a connection on this computer
+    fn pair() -> (Connection, Connection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (
+            Connection::new(server).unwrap(),
+            Connection::new(client).unwrap(),
+        )
+    }
+
+    fn receive_until(connection: &mut Connection, count: usize) -> Vec<Value> {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut messages = vec![];
+        while messages.len() < count && !connection.closed && Instant::now() < deadline {
+            messages.extend(connection.receive());
+            thread::sleep(POLL_INTERVAL);
+        }
+        messages
+    }
+
+    #[test]
+    fn the_longest_line_gets_through_to_a_slow_reader() {
+        let (mut server, mut client) = pair();
+        // the ticks fill up what the system buffers until the rest has to wait in the backlog
+        let tick = json!({"type": "tick", "tick": 0, "turns": "x".repeat(4000)});
+        let mut ticks = 0;
+        while server.backlog.is_empty() {
+            server.send(&tick);
+            ticks += 1;
+        }
+        let start = json!({"type": "start", "moves": "x".repeat(MAX_LINE - 100)});
+        server.send(&start);
+        server.send(&tick);
+        assert!(!server.closed);
+        assert!(server.backlog.len() > MAX_LINE / 2);
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut messages = vec![];
+        while messages.len() < ticks + 2 && !client.closed && Instant::now() < deadline {
+            messages.extend(client.receive());
+            server.flush();
+        }
+        assert!(!server.closed && !client.closed);
+        assert_eq!(messages.len(), ticks + 2);
+        assert_eq!(messages[ticks], start);
+    }
+
+    #[test]
+    fn a_line_longer_than_any_message_closes_the_connection() {
+        let (server, mut client) = pair();
+        let mut stream = server.stream;
+        stream.set_nonblocking(false).unwrap();
+        let writer = thread::spawn(move || {
+            let _ = stream.write_all(&vec![b'x'; MAX_LINE + 4096]);
+        });
+        receive_until(&mut client, 1);
+        assert!(client.closed);
+        assert!(client.received.is_empty());
+        drop(client);
+        writer.join().unwrap();
+    }
+
+    // a server that answers every hello with these lines
+    fn fake_server(lines: Vec<Value>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut hello = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut hello)
+                .unwrap();
+            for line in lines {
+                writeln!(stream, "{}", line).unwrap();
+            }
+            // keeps the connection open until the client is done
+            let _ = stream.read(&mut [0; 1]);
+        });
+        address
+    }
+
+    fn welcome(width: u32, height: u32) -> Value {
+        let mut welcome = json!({"type": "welcome", "player": 0, "token": "t", "tick_ms": 50});
+        Board {
+            width,
+            height,
+            ..Board::default()
+        }
+        .describe(&mut welcome);
+        welcome
+    }
+
+    #[test]
+    fn what_comes_before_the_welcome_isnt_lost() {
+        let lobby = json!({"type": "lobby", "players": [{"name": "ana", "ready": true, "connected": true}]});
+        let mut client =
+            NetClient::join(&fake_server(vec![lobby, welcome(30, 25)]), "bob").unwrap();
+        assert_eq!((client.board.width, client.board.height), (30, 25));
+        let mut game = client.new_game();
+        client.sync(&mut game);
+        assert_eq!(client.lobby.len(), 1);
+        assert!(client.lobby[0].ready);
+    }
+
+    #[test]
+    fn a_board_that_cant_be_played_is_refused() {
+        for (width, height) in [(0, 0), (21, 3), (21, MAX_SIDE + 1)] {
+            let result = NetClient::join(&fake_server(vec![welcome(width, height)]), "bob");
+            assert!(result.is_err(), "{}x{} was played", width, height);
+        }
+        let address = fake_server(vec![
+            welcome(21, 21),
+            json!({"type": "start", "seed": 1, "players": 1, "tick": 0, "width": 0, "height": 0}),
+        ]);
+        let mut client = NetClient::join(&address, "bob").unwrap();
+        let mut game = client.new_game();
+        client.sync(&mut game);
+        assert!(client.connection.is_none());
+        assert!(
+            client.status().contains("width of 0"),
+            "{}",
+            client.status()
+        );
+    }
+
+    fn serve() -> String {
+        let config = ServerConfig {
+            board: Board {
+                width: 40,
+                height: 30,
+                apples: 1,
+                ..Board::default()
+            },
+            tick: Duration::from_millis(10),
+            seed: Some(7),
+        };
+        let server = Server::bind(0, config).unwrap();
+        let port = server.listener.local_addr().unwrap().port();
+        server.spawn();
+        format!("127.0.0.1:{}", port)
+    }
+
+    // syncs everyone until it is true or it takes too long
+    fn sync_until(
+        clients: &mut [&mut NetClient],
+        games: &mut [&mut SnakeGame],
+        done: impl Fn(&[&mut NetClient], &[&mut SnakeGame]) -> bool,
+    ) {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        while !done(clients, games) {
+            assert!(Instant::now() < deadline, "it took too long");
+            for (client, game) in clients.iter_mut().zip(games.iter_mut()) {
+                client.sync(game);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn players_join_play_the_same_game_and_get_back_in() {
+        let address = serve();
+        let mut ana = NetClient::join(&address, "ana").unwrap();
+        let mut bob = NetClient::join(&address, "bob").unwrap();
+        assert_eq!((ana.player, bob.player), (Some(0), Some(1)));
+        assert_eq!((bob.board.width, bob.board.height), (40, 30));
+        let (mut ana_game, mut bob_game) = (ana.new_game(), bob.new_game());
+        let (clients, games) = (
+            &mut [&mut ana, &mut bob],
+            &mut [&mut ana_game, &mut bob_game],
+        );
+        sync_until(clients, games, |clients, _| {
+            clients.iter().all(|client| client.lobby.len() == 2)
+        });
+
+        for client in clients.iter_mut() {
+            client.toggle_ready();
+        }
+        sync_until(clients, games, |clients, _| {
+            clients.iter().all(|client| client.playing)
+        });
+        // side by side so they never run into each other
+        clients[0].turn(Direction::UP);
+        clients[1].turn(Direction::UP);
+        sync_until(clients, games, |_, games| {
+            games[0].ticks >= 20 && games[0].ticks == games[1].ticks
+        });
+        assert_eq!(snapshot(games[0]), snapshot(games[1]));
+        assert_eq!(
+            games[0].players[0].moves.first().map(|turn| turn.1),
+            Some(Direction::UP)
+        );
+        assert_eq!(
+            games[0].players[1].moves.first().map(|turn| turn.1),
+            Some(Direction::UP)
+        );
+
+        // bob loses the connection, the game waits for him and he catches up when he is back
+        clients[1].connection = None;
+        clients[1].last_attempt = Instant::now() - RETRY_INTERVAL;
+        sync_until(&mut clients[..1], &mut games[..1], |clients, _| {
+            clients[0].waiting_for == ["bob"]
+        });
+        let paused_at = games[0].ticks;
+        sync_until(clients, games, |clients, games| {
+            clients[1].connection.is_some()
+                && clients[0].waiting_for.is_empty()
+                && games[0].ticks > paused_at + 10
+                && games[0].ticks == games[1].ticks
+        });
+        assert_eq!(clients[1].player, Some(1));
+        assert_eq!(snapshot(games[0]), snapshot(games[1]));
+    }
+
+    #[test]
+    fn nobody_gets_in_while_a_game_is_running() {
+        let address = serve();
+        let mut ana = NetClient::join(&address, "ana").unwrap();
+        let mut game = ana.new_game();
+        ana.toggle_ready();
+        sync_until(&mut [&mut ana], &mut [&mut game], |clients, _| {
+            clients[0].playing
+        });
+        let refused = NetClient::join(&address, "bob").err().unwrap_or_default();
+        assert!(refused.contains("a game is running"), "{}", refused);
+        let watching = NetClient::watch(&address).unwrap();
+        assert_eq!(watching.player, None);
+    }
+}
@@ -0,0 +1,166 @@
+// the colors every frontend draws with, kept free of sdl so the terminal can use them too
+use crate::model::AppleKind;
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub name: &'static str,
+    pub background: Rgb,
+    pub snake: Rgb,
+    // the snakes of the second, third and fourth player
+    pub rivals: [Rgb; 3],
+    pub apple: Rgb,
+    // the other kinds of apples
+    pub golden: Rgb,
+    pub poison: Rgb,
+    pub rotten: Rgb,
+    pub obstacle: Rgb,
+    pub power_up: Rgb,
+    pub portal: Rgb,
+    // used for things drawn on top of the field like the camera frame in the minimap
+    pub highlight: Rgb,
+    // draw the entities with different shapes so they can be told apart without color
+    pub shape_cues: bool,
+}
+
+pub const PALETTES: [Palette; 6] = [
+    Palette {
+        name: "default",
+        background: (0, 0, 0),
+        snake: (0, 255, 0),
+        rivals: [(0, 128, 255), (255, 128, 0), (255, 0, 255)],
+        apple: (255, 0, 0),
+        golden: (255, 215, 0),
+        poison: (170, 0, 255),
+        rotten: (140, 90, 40),
+        obstacle: (255, 255, 255),
+        power_up: (0, 255, 255),
+        portal: (255, 0, 255),
+        highlight: (255, 255, 0),
+        shape_cues: false,
+    },
+    // blue against orange stays apart for people missing the green cones
+    Palette {
+        name: "deuteranopia",
+        background: (0, 0, 0),
+        snake: (0, 114, 178),
+        rivals: [(204, 121, 167), (255, 255, 255), (86, 180, 233)],
+        apple: (230, 159, 0),
+        golden: (240, 228, 66),
+        poison: (120, 60, 200),
+        rotten: (150, 110, 60),
+        obstacle: (200, 200, 200),
+        power_up: (0, 158, 115),
+        portal: (86, 180, 233),
+        highlight: (240, 228, 66),
+        shape_cues: false,
+    },
+    // red looks dark without the red cones so the apple is a bright yellow instead
+    Palette {
+        name: "protanopia",
+        background: (0, 0, 0),
+        snake: (86, 180, 233),
+        rivals: [(0, 158, 115), (204, 121, 167), (255, 255, 255)],
+        apple: (240, 228, 66),
+        golden: (255, 255, 255),
+        poison: (0, 90, 200),
+        rotten: (150, 110, 60),
+        obstacle: (200, 200, 200),
+        power_up: (213, 94, 0),
+        portal: (0, 158, 115),
+        highlight: (230, 159, 0),
+        shape_cues: false,
+    },
+    // blue and yellow get confused without the blue cones, red against teal doesn't
+    Palette {
+        name: "tritanopia",
+        background: (0, 0, 0),
+        snake: (0, 170, 170),
+        rivals: [(255, 255, 255), (180, 120, 60), (120, 120, 255)],
+        apple: (230, 60, 60),
+        golden: (255, 200, 0),
+        poison: (150, 0, 150),
+        rotten: (120, 90, 60),
+        obstacle: (200, 200, 200),
+        power_up: (255, 220, 0),
+        portal: (255, 120, 200),
+        highlight: (255, 160, 200),
+        shape_cues: false,
+    },
+    Palette {
+        name: "high-contrast",
+        background: (0, 0, 0),
+        snake: (255, 255, 0),
+        rivals: [(0, 255, 0), (255, 255, 255), (255, 128, 0)],
+        apple: (255, 0, 255),
+        golden: (255, 255, 255),
+        poison: (255, 0, 0),
+        rotten: (0, 255, 0),
+        obstacle: (255, 255, 255),
+        power_up: (0, 128, 255),
+        portal: (255, 128, 0),
+        highlight: (0, 255, 255),
+        shape_cues: true,
+    },
+    Palette {
+        name: "monochrome",
+        background: (0, 0, 0),
+        snake: (255, 255, 255),
+        rivals: [(200, 200, 200), (140, 140, 140), (90, 90, 90)],
+        apple: (255, 255, 255),
+        golden: (255, 255, 255),
+        poison: (200, 200, 200),
+        rotten: (140, 140, 140),
+        obstacle: (160, 160, 160),
+        power_up: (255, 255, 255),
+        portal: (200, 200, 200),
+        highlight: (255, 255, 255),
+        shape_cues: true,
+    },
+];
+
+impl Palette {
+    pub fn by_name(name: &str) -> Option<Self> {
+        PALETTES
+            .iter()
+            .find(|palette| palette.name == name)
+            .copied()
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        PALETTES.iter().map(|palette| palette.name).collect()
+    }
+
+    pub fn apple_color(&self, kind: AppleKind) -> Rgb {
+        match kind {
+            AppleKind::Normal => self.apple,
+            AppleKind::Golden => self.golden,
+            AppleKind::Poison => self.poison,
+            AppleKind::Rotten => self.rotten,
+        }
+    }
+
+    // the color of a player's snake, there are colors for four
+    pub fn snake_color(&self, player: usize) -> Rgb {
+        match player {
+            0 => self.snake,
+            _ => self.rivals[(player - 1) % self.rivals.len()],
+        }
+    }
+
+    // the palette after this one, wrapping around to the first
+    pub fn next(&self) -> Self {
+        let index = PALETTES
+            .iter()
+            .position(|palette| palette.name == self.name)
+            .unwrap_or(0);
+        PALETTES[(index + 1) % PALETTES.len()]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0]
+    }
+}
@@ -0,0 +1,514 @@
+// everything that draws a game, shared by the window and the offscreen captures
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+use snake_game_sdl::camera::Camera;
+use snake_game_sdl::model::{AppleKind, Direction, Edge, Snake, RING_WARNING};
+use snake_game_sdl::palette::{Palette, Rgb};
+use snake_game_sdl::*;
+
+pub const SQUARE_SIZE: u32 = 16;
+const PIXEL_PADDING: u32 = 3;
+const MINIMAP_SIZE: u32 = 160;
+const MINIMAP_MARGIN: i32 = 8;
+// how much of the ghost shows, out of 255
+const GHOST_ALPHA: u8 = 90;
+
+// the most snakes that get their own color
+const SNAKE_COLORS: usize = 4;
+
+pub struct Sprites<'a> {
+    // one for every player
+    pub snakes: Vec<Texture<'a>>,
+    // one for every kind, in the order of AppleKind::ALL
+    pub apples: Vec<Texture<'a>>,
+    pub power_up: Texture<'a>,
+    pub portal: Texture<'a>,
+    pub collision: Texture<'a>,
+    pub palette: Palette,
+}
+
+fn is_within_padded_area(x: u32, y: u32, small: bool) -> bool {
+    let mut padding = PIXEL_PADDING;
+    if small {
+        padding = PIXEL_PADDING + 2;
+    }
+    is_within_padding(x, y, padding)
+}
+
+fn is_within_padding(x: u32, y: u32, padding: u32) -> bool {
+    let padded_start = padding;
+    let padded_end = SQUARE_SIZE - padding;
+
+    (x >= padded_start && x <= padded_end) && (y >= padded_start && y <= padded_end)
+}
+
+pub fn dummy_texture<'a, T: RenderTarget, C>(
+    canvas: &mut Canvas<T>,
+    texture_creator: &'a TextureCreator<C>,
+    palette: &Palette,
+) -> Result<Sprites<'a>, String> {
+    enum TextureKind {
+        Snake(usize),
+        Apple(AppleKind),
+        PowerUp,
+        Portal,
+        Obstacle,
+    }
+
+    let mut snakes = (0..SNAKE_COLORS)
+        .map(|_| {
+            texture_creator
+                .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<Texture>, String>>()?;
+    let mut apples = AppleKind::ALL
+        .iter()
+        .map(|_| {
+            texture_creator
+                .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<Texture>, String>>()?;
+    let mut power_up = texture_creator
+        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
+        .map_err(|e| e.to_string())?;
+    let mut portal = texture_creator
+        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
+        .map_err(|e| e.to_string())?;
+    let mut collision = texture_creator
+        .create_texture_target(None, SQUARE_SIZE, SQUARE_SIZE)
+        .map_err(|e| e.to_string())?;
+
+    {
+        let mut textures: Vec<(&mut Texture, TextureKind)> = snakes
+            .iter_mut()
+            .enumerate()
+            .map(|(player, texture)| (texture, TextureKind::Snake(player)))
+            .collect();
+        for (texture, kind) in apples.iter_mut().zip(AppleKind::ALL) {
+            textures.push((texture, TextureKind::Apple(kind)));
+        }
+        textures.push((&mut power_up, TextureKind::PowerUp));
+        textures.push((&mut portal, TextureKind::Portal));
+        textures.push((&mut collision, TextureKind::Obstacle));
+
+        // to maybe add some margins to make it prettier
+        canvas
+            .with_multiple_texture_canvas(textures.iter(), |texture_canvas, user_context| {
+                texture_canvas.set_draw_color(color(palette.background));
+                texture_canvas.clear();
+                match *user_context {
+                    TextureKind::Snake(player) => {
+                        // we draw pixel by pixel the snake body (it's just a rectangle)
+                        let small = false;
+                        for y in 0..SQUARE_SIZE {
+                            for x in 0..SQUARE_SIZE {
+                                texture_canvas.set_draw_color(color(palette.snake_color(player)));
+                                if is_within_padded_area(x, y, small) {
+                                    texture_canvas
+                                        .draw_point(Point::new(x as i32, y as i32))
+                                        .expect("failed to draw point");
+                                }
+                            }
+                        }
+                    }
+
+                    TextureKind::Apple(kind) => {
+                        // we draw pixel by pixel the apple (it's just a rectangle, or a ring
+                        // when the palette relies on shapes), the other kinds get their own
+                        // shapes: a cross for golden, an x for poison and holes for rotten
+                        let small = true;
+                        let middle = SQUARE_SIZE / 2;
+                        for y in 0..SQUARE_SIZE {
+                            for x in 0..SQUARE_SIZE {
+                                texture_canvas.set_draw_color(color(palette.apple_color(kind)));
+                                let is_drawn = match kind {
+                                    AppleKind::Normal => {
+                                        let hollow = palette.shape_cues
+                                            && is_within_padding(x, y, PIXEL_PADDING + 4);
+                                        is_within_padded_area(x, y, small) && !hollow
+                                    }
+                                    AppleKind::Golden => {
+                                        is_within_padded_area(x, y, false)
+                                            && (x.abs_diff(middle) <= 2 || y.abs_diff(middle) <= 2)
+                                    }
+                                    AppleKind::Poison => {
+                                        is_within_padded_area(x, y, false)
+                                            && (x.abs_diff(y) <= 1
+                                                || x.abs_diff(SQUARE_SIZE - 1 - y) <= 1)
+                                    }
+                                    AppleKind::Rotten => {
+                                        is_within_padded_area(x, y, small)
+                                            && (x / 2 + y / 2) % 2 == 0
+                                    }
+                                };
+                                if is_drawn {
+                                    texture_canvas
+                                        .draw_point(Point::new(x as i32, y as i32))
+                                        .expect("failed to draw point");
+                                }
+                            }
+                        }
+                    }
+
+                    TextureKind::PowerUp => {
+                        // a diamond so it doesn't look like an apple in any palette
+                        let middle = SQUARE_SIZE / 2;
+                        for y in 0..SQUARE_SIZE {
+                            for x in 0..SQUARE_SIZE {
+                                texture_canvas.set_draw_color(color(palette.power_up));
+                                if x.abs_diff(middle) + y.abs_diff(middle) <= middle - PIXEL_PADDING
+                                {
+                                    texture_canvas
+                                        .draw_point(Point::new(x as i32, y as i32))
+                                        .expect("failed to draw point");
+                                }
+                            }
+                        }
+                    }
+
+                    TextureKind::Portal => {
+                        // a ring around the whole cell
+                        let middle = SQUARE_SIZE as f32 / 2.0 - 0.5;
+                        for y in 0..SQUARE_SIZE {
+                            for x in 0..SQUARE_SIZE {
+                                texture_canvas.set_draw_color(color(palette.portal));
+                                let distance = (x as f32 - middle).hypot(y as f32 - middle);
+                                if (4.0..=7.0).contains(&distance) {
+                                    texture_canvas
+                                        .draw_point(Point::new(x as i32, y as i32))
+                                        .expect("failed to draw point");
+                                }
+                            }
+                        }
+                    }
+
+                    TextureKind::Obstacle => {
+                        // we draw pixel by pixel the collition (it's just a rectangle, hatched
+                        // when the palette relies on shapes)
+                        for y in 0..SQUARE_SIZE {
+                            for x in 0..SQUARE_SIZE {
+                                texture_canvas.set_draw_color(color(palette.obstacle));
+                                if !palette.shape_cues || (x + y) % 4 < 2 {
+                                    texture_canvas
+                                        .draw_point(Point::new(x as i32, y as i32))
+                                        .expect("failed to draw point");
+                                }
+                            }
+                        }
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(Sprites {
+        snakes,
+        apples,
+        power_up,
+        portal,
+        collision,
+        palette: *palette,
+    })
+}
+
+pub fn color(rgb: Rgb) -> Color {
+    Color::RGB(rgb.0, rgb.1, rgb.2)
+}
+
+fn draw_cell<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    texture: &Texture,
+    camera: &Camera,
+    zoom: u32,
+    position: (u32, u32),
+) {
+    if let Some((x, y)) = camera.to_view(position) {
+        let _ = canvas.copy(
+            texture,
+            None,
+            Rect::new(x as i32 * zoom as i32, y as i32 * zoom as i32, zoom, zoom),
+        );
+    }
+}
+
+// a small overview of the whole field in the top right corner
+fn draw_minimap<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    game: &SnakeGame,
+    camera: &Camera,
+    palette: &Palette,
+) {
+    let (field_width, field_height) = game.field.size;
+    let scale = MINIMAP_SIZE as f32 / field_width.max(field_height) as f32;
+    let map_width = (field_width as f32 * scale).ceil() as u32;
+    let map_height = (field_height as f32 * scale).ceil() as u32;
+    let Ok((window_width, _)) = canvas.output_size() else {
+        return;
+    };
+    let map_x = window_width as i32 - map_width as i32 - MINIMAP_MARGIN;
+    let map_y = MINIMAP_MARGIN;
+
+    // every entity is at least one pixel so it doesn't vanish on big boards
+    let cell_rect = |position: (u32, u32), min_size: u32| {
+        Rect::new(
+            map_x + (position.0 as f32 * scale) as i32,
+            map_y + (position.1 as f32 * scale) as i32,
+            (scale.ceil() as u32).max(min_size),
+            (scale.ceil() as u32).max(min_size),
+        )
+    };
+
+    canvas.set_draw_color(color(palette.background));
+    let _ = canvas.fill_rect(Rect::new(map_x, map_y, map_width, map_height));
+
+    if let Some(obstacles) = &game.field.obstacles {
+        canvas.set_draw_color(color(palette.obstacle));
+        for obstacle_part in obstacles {
+            let _ = canvas.fill_rect(cell_rect(*obstacle_part, 1));
+        }
+    }
+
+    for (player, snake) in game.visible_snakes() {
+        canvas.set_draw_color(color(palette.snake_color(player)));
+        for snake_part in snake.body.iter() {
+            let _ = canvas.fill_rect(cell_rect(snake_part.get().position, 1));
+        }
+    }
+
+    for apple in &game.apples {
+        canvas.set_draw_color(color(palette.apple_color(apple.kind)));
+        let _ = canvas.fill_rect(cell_rect(apple.position, 3));
+    }
+    canvas.set_draw_color(color(palette.power_up));
+    for item in &game.power_ups {
+        let _ = canvas.fill_rect(cell_rect(item.position, 3));
+    }
+    canvas.set_draw_color(color(palette.portal));
+    for (first, second) in &game.field.portals {
+        let _ = canvas.fill_rect(cell_rect(*first, 3));
+        let _ = canvas.fill_rect(cell_rect(*second, 3));
+    }
+
+    // the part of the field the camera currently shows
+    canvas.set_draw_color(color(palette.highlight));
+    let _ = canvas.draw_rect(Rect::new(
+        map_x + (camera.origin.0 as f32 * scale) as i32,
+        map_y + (camera.origin.1 as f32 * scale) as i32,
+        ((camera.view.0 as f32 * scale) as u32).max(1),
+        ((camera.view.1 as f32 * scale) as u32).max(1),
+    ));
+
+    canvas.set_draw_color(color(palette.obstacle));
+    let _ = canvas.draw_rect(Rect::new(map_x, map_y, map_width, map_height));
+}
+
+// a thin line along the sides that don't wrap around, walls look like obstacles and the bouncy
+// ones are highlighted
+fn draw_edges<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    game: &SnakeGame,
+    camera: &Camera,
+    zoom: u32,
+    palette: &Palette,
+) {
+    let (width, height) = game.field.size;
+    let edges = game.field.edges;
+    let thickness = (zoom / 6).max(1);
+    let sides = [
+        (edges.top, Direction::UP),
+        (edges.bottom, Direction::DOWN),
+        (edges.right, Direction::LEFT),
+        (edges.left, Direction::RIGHT),
+    ];
+    for (edge, side) in sides {
+        canvas.set_draw_color(color(match edge {
+            Edge::Wrap => continue,
+            Edge::Wall => palette.obstacle,
+            Edge::Bounce => palette.highlight,
+        }));
+        let cells: Vec<(u32, u32)> = match side {
+            Direction::UP => (0..width).map(|x| (x, 0)).collect(),
+            Direction::DOWN => (0..width).map(|x| (x, height - 1)).collect(),
+            Direction::LEFT => (0..height).map(|y| (width - 1, y)).collect(),
+            Direction::RIGHT => (0..height).map(|y| (0, y)).collect(),
+        };
+        for cell in cells {
+            let Some((x, y)) = camera.to_view(cell) else {
+                continue;
+            };
+            let (x, y) = (x as i32 * zoom as i32, y as i32 * zoom as i32);
+            let far = zoom as i32 - thickness as i32;
+            let _ = canvas.fill_rect(match side {
+                Direction::UP => Rect::new(x, y, zoom, thickness),
+                Direction::DOWN => Rect::new(x, y + far, zoom, thickness),
+                Direction::LEFT => Rect::new(x + far, y, thickness, zoom),
+                Direction::RIGHT => Rect::new(x, y, thickness, zoom),
+            });
+        }
+    }
+}
+
+// open gates are only outlined, they flash in the highlight color for a while before closing
+fn draw_open_gates<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    game: &SnakeGame,
+    camera: &Camera,
+    zoom: u32,
+    palette: &Palette,
+) {
+    for gate in game.field.gates.iter().filter(|gate| !gate.closed) {
+        let flashing = gate.is_closing(game.ticks) && game.ticks % 4 < 2;
+        canvas.set_draw_color(color(match flashing {
+            true => palette.highlight,
+            false => palette.obstacle,
+        }));
+        for cell in &gate.cells {
+            let Some((x, y)) = camera.to_view(*cell) else {
+                continue;
+            };
+            let _ = canvas.draw_rect(Rect::new(
+                x as i32 * zoom as i32,
+                y as i32 * zoom as i32,
+                zoom,
+                zoom,
+            ));
+        }
+    }
+}
+
+// the best run so far in the color of the first snake but see-through, under everything else
+fn draw_ghost<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    ghost: &Snake,
+    camera: &Camera,
+    zoom: u32,
+    palette: &Palette,
+) {
+    let (r, g, b) = palette.snake_color(0);
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(r, g, b, GHOST_ALPHA));
+    let padding = PIXEL_PADDING * zoom / SQUARE_SIZE;
+    for body_part in ghost.body.iter() {
+        let Some((x, y)) = camera.to_view(body_part.get().position) else {
+            continue;
+        };
+        let _ = canvas.fill_rect(Rect::new(
+            (x * zoom + padding) as i32,
+            (y * zoom + padding) as i32,
+            zoom - padding * 2,
+            zoom - padding * 2,
+        ));
+    }
+    canvas.set_blend_mode(BlendMode::None);
+}
+
+// the exit of a campaign level is a square in the highlight color with a gap around it
+fn draw_exit<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    game: &SnakeGame,
+    camera: &Camera,
+    zoom: u32,
+    palette: &Palette,
+) {
+    let Some((x, y)) = game.exit().and_then(|exit| camera.to_view(exit)) else {
+        return;
+    };
+    let gap = zoom / 4;
+    canvas.set_draw_color(color(palette.highlight));
+    let _ = canvas.draw_rect(Rect::new(
+        x as i32 * zoom as i32,
+        y as i32 * zoom as i32,
+        zoom,
+        zoom,
+    ));
+    let _ = canvas.fill_rect(Rect::new(
+        (x * zoom + gap) as i32,
+        (y * zoom + gap) as i32,
+        zoom - gap * 2,
+        zoom - gap * 2,
+    ));
+}
+
+// the ring of walls closing next flashes for a while before it does
+fn draw_closing_ring<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    game: &SnakeGame,
+    camera: &Camera,
+    zoom: u32,
+    palette: &Palette,
+) {
+    let (Some(ring), Some(closes_in)) = (
+        game.field.next_ring(),
+        game.field.ring_closes_in(game.ticks),
+    ) else {
+        return;
+    };
+    if closes_in > RING_WARNING || game.ticks % 4 >= 2 {
+        return;
+    }
+    canvas.set_draw_color(color(palette.highlight));
+    for cell in game.field.ring(ring) {
+        let Some((x, y)) = camera.to_view(cell) else {
+            continue;
+        };
+        let _ = canvas.draw_rect(Rect::new(
+            x as i32 * zoom as i32,
+            y as i32 * zoom as i32,
+            zoom,
+            zoom,
+        ));
+    }
+}
+
+// clears the canvas and draws whatever the camera is looking at
+pub fn draw_game<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    sprites: &Sprites,
+    game: &SnakeGame,
+    camera: &Camera,
+    zoom: u32,
+    show_minimap: bool,
+    ghost: Option<&Snake>,
+) {
+    canvas.set_draw_color(color(sprites.palette.background));
+    canvas.clear();
+    draw_exit(canvas, game, camera, zoom, &sprites.palette);
+    if let Some(ghost) = ghost {
+        draw_ghost(canvas, ghost, camera, zoom, &sprites.palette);
+    }
+
+    // use the game provider to draw the entities
+    for apple in &game.apples {
+        let texture = &sprites.apples[apple.kind as usize];
+        draw_cell(canvas, texture, camera, zoom, apple.position);
+    }
+    for item in &game.power_ups {
+        draw_cell(canvas, &sprites.power_up, camera, zoom, item.position);
+    }
+    for (first, second) in &game.field.portals {
+        draw_cell(canvas, &sprites.portal, camera, zoom, *first);
+        draw_cell(canvas, &sprites.portal, camera, zoom, *second);
+    }
+
+    for (player, snake) in game.visible_snakes() {
+        let texture = &sprites.snakes[player % sprites.snakes.len()];
+        for snake_part in snake.body.iter() {
+            draw_cell(canvas, texture, camera, zoom, snake_part.get().position);
+        }
+    }
+    if let Some(obstacles) = &game.field.obstacles {
+        for obstacle_part in obstacles {
+            draw_cell(canvas, &sprites.collision, camera, zoom, *obstacle_part);
+        }
+    }
+
+    draw_open_gates(canvas, game, camera, zoom, &sprites.palette);
+    draw_closing_ring(canvas, game, camera, zoom, &sprites.palette);
+    draw_edges(canvas, game, camera, zoom, &sprites.palette);
+
+    if show_minimap && camera.is_scrolling() {
+        draw_minimap(canvas, game, camera, &sprites.palette);
+    }
+}
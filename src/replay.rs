@@ -0,0 +1,476 @@
+// a finished game saved as its settings, its seed and the turns that were taken
+//
+// the file is plain text, the first line holds the settings and every other line one turn or a
+// rewind, when the game was stepped back from one tick to an earlier one and played on from there:
+//   seed width height border obstacles players flags
+//   tick direction player
+//   rewind from to
+// the players are left out for single player games, which is how the first replays were written,
+// the flags are words for the rules that are turned on like power-ups or apples=3 for settings,
+// a campaign level is level=<id> and brings its own board and rules
+use crate::campaign;
+use crate::mode::Mode;
+use crate::model::{Direction, Edges};
+use crate::{SnakeGame, MAX_SIDE, MIN_SIDE};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    pub has_border: bool,
+    pub obstacles: u32,
+    pub power_ups: bool,
+    // how many apples are on the field at once
+    pub apples: u32,
+    pub apple_kinds: bool,
+    pub edges: Edges,
+    // how many pairs of portals there are
+    pub portals: u32,
+    pub patrols: u32,
+    pub gates: u32,
+    pub growing_obstacles: u32,
+    pub shrinking_arena: u32,
+    pub mode: Mode,
+    // the campaign level that was played, the other settings don't matter then
+    pub level: Option<String>,
+    // the turns of every player
+    pub moves: Vec<Vec<(u64, Direction)>>,
+    // the turns that were taken back aren't kept, only that they were
+    pub rewinds: Vec<(u64, u64)>,
+}
+
+impl Replay {
+    pub fn from_game(game: &SnakeGame) -> Self {
+        Self {
+            seed: game.seed,
+            width: game.field.size.0,
+            height: game.field.size.1,
+            has_border: game.field.has_border(),
+            obstacles: game.field.num_obstacles(),
+            power_ups: game.spawns_power_ups,
+            apples: game.num_apples,
+            apple_kinds: game.apple_kinds,
+            edges: game.field.edges,
+            portals: game.field.num_portals,
+            patrols: game.field.num_patrols,
+            gates: game.field.num_gates,
+            growing_obstacles: game.field.growing_obstacles,
+            shrinking_arena: game.field.shrinking_arena,
+            mode: game.mode,
+            level: game.level.clone(),
+            moves: game
+                .players
+                .iter()
+                .map(|player| player.moves.clone())
+                .collect(),
+            rewinds: game.rewinds.clone(),
+        }
+    }
+
+    // a fresh game that plays out exactly like the recorded one when given the same turns
+    pub fn new_game(&self) -> SnakeGame {
+        let mut game = self.board();
+        game.rewinds = self.rewinds.clone();
+        game
+    }
+
+    fn board(&self) -> SnakeGame {
+        // load made sure the level is there
+        if let Some(level) = self.level.as_deref().and_then(campaign::level_by_id) {
+            return level.new_game(self.seed);
+        }
+        SnakeGame::from_seed(
+            self.width,
+            self.height,
+            self.has_border,
+            (self.obstacles > 0, self.obstacles),
+            self.seed,
+        )
+        .with_players(self.moves.len())
+        .with_power_ups(self.power_ups)
+        .with_apples(self.apples as usize, self.apple_kinds)
+        .with_edges(self.edges)
+        .with_portals(self.portals as usize)
+        .with_moving_obstacles(self.patrols as usize, self.gates as usize)
+        .with_growing_obstacles(self.growing_obstacles)
+        .with_shrinking_arena(self.shrinking_arena)
+        .with_mode(self.mode)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| e.to_string())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{} {} {} {} {}",
+            self.seed, self.width, self.height, self.has_border, self.obstacles
+        );
+        let is_multiplayer = self.moves.len() > 1;
+        if is_multiplayer {
+            text.push_str(&format!(" {}", self.moves.len()));
+        }
+        for flag in self.flags() {
+            text.push_str(&format!(" {}", flag));
+        }
+        if self.mode != Mode::Classic {
+            text.push_str(&format!(" mode={}", self.mode));
+        }
+        if let Some(level) = &self.level {
+            text.push_str(&format!(" level={}", level));
+        }
+        text.push('\n');
+        for (player, moves) in self.moves.iter().enumerate() {
+            for (tick, direction) in moves {
+                match is_multiplayer {
+                    true => text.push_str(&format!("{} {} {}\n", tick, direction, player)),
+                    false => text.push_str(&format!("{} {}\n", tick, direction)),
+                }
+            }
+        }
+        for (from, to) in &self.rewinds {
+            text.push_str(&format!("rewind {} {}\n", from, to));
+        }
+        text
+    }
+
+    // the words for the rules that are turned on, in the order they are written
+    fn flags(&self) -> Vec<String> {
+        let mut flags = vec![];
+        if self.power_ups {
+            flags.push("power-ups".to_string());
+        }
+        if self.apples > 1 {
+            flags.push(format!("apples={}", self.apples));
+        }
+        if self.apple_kinds {
+            flags.push("apple-kinds".to_string());
+        }
+        if self.edges != Edges::TORUS {
+            flags.push(format!("edges={}", self.edges));
+        }
+        if self.portals > 0 {
+            flags.push(format!("portals={}", self.portals));
+        }
+        if self.patrols > 0 {
+            flags.push(format!("patrols={}", self.patrols));
+        }
+        if self.gates > 0 {
+            flags.push(format!("gates={}", self.gates));
+        }
+        if self.growing_obstacles > 0 {
+            flags.push(format!("growing-obstacles={}", self.growing_obstacles));
+        }
+        if self.shrinking_arena > 0 {
+            flags.push(format!("shrinking-arena={}", self.shrinking_arena));
+        }
+        flags
+    }
+
+    // every rule that changes how the game plays apart from the seed, the size and the mode, like
+    // +border+obstacles=5+apples=3, empty for a plain board
+    pub fn rules(&self) -> String {
+        let mut rules = vec![];
+        if self.has_border {
+            rules.push("border".to_string());
+        }
+        if self.obstacles > 0 {
+            rules.push(format!("obstacles={}", self.obstacles));
+        }
+        rules.extend(self.flags());
+        rules.iter().map(|rule| format!("+{}", rule)).collect()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Self::parse(&fs::read_to_string(path).map_err(|e| e.to_string())?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or("the replay is empty")?
+            .split_whitespace()
+            .collect();
+        if header.len() < 5 {
+            return Err("the first line of the replay must hold at least 5 settings".to_string());
+        }
+        let number = |value: &str| value.parse::<u64>().map_err(|e| e.to_string());
+        // a side the game couldn't be started on would crash it
+        let side = |value: &str| {
+            u32::try_from(number(value)?)
+                .ok()
+                .filter(|side| (MIN_SIDE..=MAX_SIDE).contains(side))
+                .ok_or(format!(
+                    "a side of the board must be {} to {} cells, not {}",
+                    MIN_SIDE, MAX_SIDE, value
+                ))
+        };
+        let mut players = 1;
+        let mut power_ups = false;
+        let mut apples = 1;
+        let mut apple_kinds = false;
+        let mut edges = Edges::TORUS;
+        let mut portals = 0;
+        let mut patrols = 0;
+        let mut gates = 0;
+        let mut growing_obstacles = 0;
+        let mut shrinking_arena = 0;
+        let mut mode = Mode::Classic;
+        let mut level = None;
+        for setting in &header[5..] {
+            match setting.split_once('=') {
+                Some(("apples", count)) => apples = number(count)?.max(1) as u32,
+                Some(("edges", sides)) => edges = sides.parse()?,
+                Some(("portals", pairs)) => portals = number(pairs)? as u32,
+                Some(("patrols", count)) => patrols = number(count)? as u32,
+                Some(("gates", count)) => gates = number(count)? as u32,
+                Some(("growing-obstacles", points)) => growing_obstacles = number(points)? as u32,
+                Some(("shrinking-arena", ticks)) => shrinking_arena = number(ticks)? as u32,
+                Some(("mode", name)) => mode = name.parse()?,
+                Some(("level", id)) => match campaign::level_by_id(id) {
+                    Some(_) => level = Some(id.to_string()),
+                    None => return Err(format!("there is no level \"{}\" in the campaign", id)),
+                },
+                Some(_) => return Err(format!("unknown setting \"{}\"", setting)),
+                None if *setting == "power-ups" => power_ups = true,
+                None if *setting == "apple-kinds" => apple_kinds = true,
+                None => players = number(setting)?.max(1) as usize,
+            }
+        }
+
+        let mut moves = vec![vec![]; players];
+        let mut rewinds = vec![];
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (tick, direction, player) = match parts[..] {
+                ["rewind", from, to] => {
+                    rewinds.push((number(from)?, number(to)?));
+                    continue;
+                }
+                [tick, direction] => (tick, direction, 0),
+                [tick, direction, player] => (tick, direction, number(player)? as usize),
+                _ => return Err(format!("malformed turn \"{}\"", line)),
+            };
+            moves
+                .get_mut(player)
+                .ok_or(format!("there is no player {} in the replay", player))?
+                .push((number(tick)?, direction.parse()?));
+        }
+
+        Ok(Self {
+            seed: number(header[0])?,
+            width: side(header[1])?,
+            height: side(header[2])?,
+            has_border: header[3]
+                .parse()
+                .map_err(|_| "border must be true or false")?,
+            obstacles: number(header[4])? as u32,
+            power_ups,
+            apples,
+            apple_kinds,
+            edges,
+            portals,
+            patrols,
+            gates,
+            growing_obstacles,
+            shrinking_arena,
+            mode,
+            level,
+            moves,
+            rewinds,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ai::AStarController;
+    use crate::controller::{Controller, ReplayController};
+    use crate::GameState;
+
+    // every rule turned on at once, the rules that need a single player are left to the modes
+    pub(crate) fn busy_game(seed: u64, players: usize, mode: Mode) -> SnakeGame {
+        SnakeGame::from_seed(24, 18, false, (true, 6), seed)
+            .with_players(players)
+            .with_power_ups(true)
+            .with_apples(3, true)
+            .with_edges("wall,wrap,bounce,wrap".parse().unwrap())
+            .with_portals(2)
+            .with_moving_obstacles(2, 2)
+            .with_growing_obstacles(5)
+            .with_shrinking_arena(150)
+            .with_mode(mode)
+    }
+
+    // lets the controllers play until the game is over or the ticks are reached
+    pub(crate) fn play(game: &mut SnakeGame, controllers: &mut [Box<dyn Controller>], ticks: u64) {
+        if game.state == GameState::Paused {
+            game.resume();
+        }
+        while game.state == GameState::Running && game.ticks < ticks {
+            for (player, controller) in controllers.iter_mut().enumerate() {
+                game.drive_player(player, controller.as_mut());
+            }
+            game.update();
+        }
+    }
+
+    pub(crate) fn bots(game: &SnakeGame) -> Vec<Box<dyn Controller>> {
+        (0..game.players.len())
+            .map(|_| Box::new(AStarController) as Box<dyn Controller>)
+            .collect()
+    }
+
+    pub(crate) fn replayers(replay: &Replay) -> Vec<Box<dyn Controller>> {
+        replay
+            .moves
+            .iter()
+            .map(|moves| Box::new(ReplayController::from(moves)) as Box<dyn Controller>)
+            .collect()
+    }
+
+    // everything on the field that two games playing out the same have in common
+    pub(crate) fn snapshot(game: &SnakeGame) -> String {
+        let players: Vec<String> = game
+            .players
+            .iter()
+            .map(|player| {
+                let body: Vec<(u32, u32)> = player
+                    .snake
+                    .body
+                    .iter()
+                    .map(|body_part| body_part.get().position)
+                    .collect();
+                format!("{} {} {:?}", player.alive, player.points, body)
+            })
+            .collect();
+        format!(
+            "{} {:?} {:?} {:?} {:?} {:?} {:?}",
+            game.ticks,
+            players,
+            game.apples,
+            game.field.obstacles,
+            game.field.patrols,
+            game.field.gates,
+            game.field.closed_rings
+        )
+    }
+
+    #[test]
+    fn the_text_reads_back_the_same() {
+        let games = [
+            busy_game(1, 1, Mode::Classic),
+            busy_game(2, 2, Mode::Classic),
+            busy_game(3, 1, Mode::AppleSprint { apples: 5 }),
+            busy_game(4, 3, Mode::TimeAttack { ticks: 200 }),
+            SnakeGame::from_seed(21, 21, true, (false, 0), 5),
+            campaign::levels()[4].new_game(6),
+        ];
+        for mut game in games {
+            let mut controllers = bots(&game);
+            play(&mut game, &mut controllers, 300);
+            game.rewinds = vec![(120, 80), (200, 150)];
+            let replay = Replay::from_game(&game);
+            assert_eq!(Replay::parse(&replay.to_text()).unwrap(), replay);
+        }
+    }
+
+    #[test]
+    fn broken_headers_are_refused() {
+        for text in [
+            "",
+            "1 21 21 false\n",
+            "seed 21 21 false 0\n",
+            "1 21 21 maybe 0\n",
+            "1 21 21 false 0 apples=many\n",
+            "1 21 21 false 0 wings=2\n",
+            "1 21 21 false 0 edges=sideways\n",
+            "1 21 21 false 0 mode=golf\n",
+            "1 21 21 false 0 level=no-such-level\n",
+            "1 21 21 false 0\n3 SIDEWAYS\n",
+            "1 21 21 false 0\n3 UP 1\n",
+            "1 21 21 false 0\nrewind 5\n",
+        ] {
+            assert!(Replay::parse(text).is_err(), "{:?} was read", text);
+        }
+    }
+
+    #[test]
+    fn boards_that_cant_be_played_are_refused() {
+        for text in [
+            "1 0 0 false 0\n",
+            "1 3 3 false 0\n",
+            "1 21 1001 false 0\n",
+            // wraps around to 21 when it is cut down to 32 bits
+            "1 4294967317 21 false 0\n",
+            "1 -21 21 false 0\n",
+        ] {
+            assert!(Replay::parse(text).is_err(), "{:?} was read", text);
+        }
+        let replay = Replay::parse(&format!("1 {} {} true 0\n", MIN_SIDE, MIN_SIDE)).unwrap();
+        let mut game = replay.new_game();
+        let mut controllers = bots(&game);
+        play(&mut game, &mut controllers, 50);
+    }
+
+    // not every patrol fits, the rebuilt board has to try as often as the first one did
+    #[test]
+    fn a_crowded_board_is_rebuilt_the_same() {
+        for seed in 0..4 {
+            let mut game = SnakeGame::from_seed(10, 8, true, (true, 8), seed)
+                .with_apples(2, false)
+                .with_moving_obstacles(30, 4);
+            assert!(game.field.patrols.len() < 30);
+            let mut controllers = bots(&game);
+            play(&mut game, &mut controllers, 300);
+
+            let replay = Replay::parse(&Replay::from_game(&game).to_text()).unwrap();
+            let mut rebuilt = replay.new_game();
+            play(&mut rebuilt, &mut replayers(&replay), game.ticks);
+            assert_eq!(snapshot(&rebuilt), snapshot(&game), "seed {}", seed);
+        }
+    }
+
+    // the same goes for apples that didn't fit
+    #[test]
+    fn a_board_full_of_apples_is_rebuilt_the_same() {
+        for seed in 0..4 {
+            let mut game = SnakeGame::from_seed(10, 8, true, (true, 8), seed).with_apples(60, true);
+            assert!(game.apples.len() < 60);
+            let mut controllers = bots(&game);
+            play(&mut game, &mut controllers, 300);
+
+            let replay = Replay::parse(&Replay::from_game(&game).to_text()).unwrap();
+            assert_eq!(replay.apples, 60);
+            let mut rebuilt = replay.new_game();
+            play(&mut rebuilt, &mut replayers(&replay), game.ticks);
+            assert_eq!(snapshot(&rebuilt), snapshot(&game), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn a_rebuilt_game_ends_the_same() {
+        for seed in 0..4 {
+            for (players, mode) in [
+                (1, Mode::Classic),
+                (2, Mode::Classic),
+                (1, Mode::AppleSprint { apples: 8 }),
+            ] {
+                let mut game = busy_game(seed, players, mode);
+                let mut controllers = bots(&game);
+                play(&mut game, &mut controllers, 2000);
+
+                let replay = Replay::parse(&Replay::from_game(&game).to_text()).unwrap();
+                let mut rebuilt = replay.new_game();
+                play(&mut rebuilt, &mut replayers(&replay), game.ticks);
+                assert_eq!(rebuilt.state, game.state);
+                assert_eq!(snapshot(&rebuilt), snapshot(&game), "seed {}", seed);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// stepping a game back and forth through the last ticks, for practice and for trying out levels
+//
+// the game is copied before every tick so going back is putting an older copy in its place, the
+// ticks that were stepped back over stay around to step forward again until the game goes on from
+// the earlier tick. then the rewind is written down in the game so the replay shows it happened
+use crate::{GameState, SnakeGame};
+use std::collections::VecDeque;
+
+// how many ticks it can go back
+pub const REWIND_TICKS: usize = 300;
+
+#[derive(Default)]
+pub struct Rewind {
+    // the oldest first
+    past: VecDeque<SnakeGame>,
+    // the ticks stepped back over, the closest one last
+    ahead: Vec<SnakeGame>,
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // keeps a copy of the game right before it moves on
+    pub fn record(&mut self, game: &mut SnakeGame) {
+        if let Some(furthest) = self.ahead.first() {
+            game.rewinds.push((furthest.ticks, game.ticks));
+            self.ahead.clear();
+        }
+        if self.past.len() == REWIND_TICKS {
+            self.past.pop_front();
+        }
+        self.past.push_back(game.clone());
+    }
+
+    // one tick back, false when there is nothing left to go back to
+    pub fn back(&mut self, game: &mut SnakeGame) -> bool {
+        let Some(earlier) = self.past.pop_back() else {
+            return false;
+        };
+        self.ahead.push(swap_in(game, earlier));
+        true
+    }
+
+    // one tick forward again, only over ticks that were stepped back over
+    pub fn forward(&mut self, game: &mut SnakeGame) -> bool {
+        let Some(later) = self.ahead.pop() else {
+            return false;
+        };
+        self.past.push_back(swap_in(game, later));
+        true
+    }
+
+    // a new game has nothing to go back to
+    pub fn clear(&mut self) {
+        self.past.clear();
+        self.ahead.clear();
+    }
+}
+
+// puts a copy in place of the game and hands back the game, the copy takes over the rewinds so far
+// since it was made before some of them. it waits for the player after every step
+fn swap_in(game: &mut SnakeGame, mut copy: SnakeGame) -> SnakeGame {
+    copy.rewinds = std::mem::take(&mut game.rewinds);
+    copy.drain_events();
+    if copy.state == GameState::Running {
+        copy.state = GameState::Paused;
+    }
+    std::mem::replace(game, copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::GreedyController;
+    use crate::controller::Controller;
+    use crate::mode::Mode;
+    use crate::replay::tests::{bots, busy_game, play, replayers, snapshot};
+    use crate::replay::Replay;
+
+    // lots of room so the snake lives long enough
+    fn open_game(seed: u64) -> SnakeGame {
+        SnakeGame::from_seed(40, 40, false, (false, 0), seed)
+    }
+
+    // plays like the window does, keeping a copy before every tick
+    fn play_recorded(
+        game: &mut SnakeGame,
+        rewind: &mut Rewind,
+        controllers: &mut [Box<dyn Controller>],
+        ticks: u64,
+    ) {
+        if game.state == GameState::Paused {
+            game.resume();
+        }
+        while game.state == GameState::Running && game.ticks < ticks {
+            rewind.record(game);
+            for (player, controller) in controllers.iter_mut().enumerate() {
+                game.drive_player(player, controller.as_mut());
+            }
+            game.update();
+        }
+    }
+
+    #[test]
+    fn steps_back_and_forward_again() {
+        let mut game = busy_game(1, 1, Mode::Classic);
+        let mut rewind = Rewind::new();
+        let mut controllers = bots(&game);
+        let mut snapshots = vec![];
+        for ticks in 1..=20 {
+            snapshots.push(snapshot(&game));
+            play_recorded(&mut game, &mut rewind, &mut controllers, ticks);
+        }
+        let now = snapshot(&game);
+        assert!(!rewind.forward(&mut game));
+
+        for ticks in (15..20).rev() {
+            assert!(rewind.back(&mut game));
+            assert_eq!(game.ticks, ticks);
+            assert_eq!(game.state, GameState::Paused);
+            assert_eq!(snapshot(&game), snapshots[ticks as usize]);
+        }
+        while rewind.forward(&mut game) {}
+        assert_eq!(snapshot(&game), now);
+        assert!(game.rewinds.is_empty());
+
+        while rewind.back(&mut game) {}
+        assert_eq!(game.ticks, 0);
+        assert_eq!(snapshot(&game), snapshots[0]);
+    }
+
+    #[test]
+    fn goes_back_no_further_than_it_keeps() {
+        let mut game = open_game(2).with_mode(Mode::Endless);
+        let mut rewind = Rewind::new();
+        let mut controllers = bots(&game);
+        let ticks = REWIND_TICKS as u64 + 50;
+        play_recorded(&mut game, &mut rewind, &mut controllers, ticks);
+        assert_eq!(
+            game.ticks, ticks,
+            "the snake crashed too early for the test"
+        );
+        let mut steps = 0;
+        while rewind.back(&mut game) {
+            steps += 1;
+        }
+        assert_eq!(steps, REWIND_TICKS);
+        assert_eq!(game.ticks, 50);
+    }
+
+    #[test]
+    fn playing_on_after_going_back_is_written_down_and_replays_the_same() {
+        let mut game = open_game(3);
+        let mut rewind = Rewind::new();
+        let mut controllers = bots(&game);
+        play_recorded(&mut game, &mut rewind, &mut controllers, 120);
+        for _ in 0..40 {
+            rewind.back(&mut game);
+        }
+        // another bot plays on from there so the turns after it change
+        let mut greedy: Vec<Box<dyn Controller>> = vec![Box::new(GreedyController)];
+        play_recorded(&mut game, &mut rewind, &mut greedy, 200);
+        assert_eq!(game.rewinds, [(120, 80)]);
+        // the ticks stepped back over are gone once it played on
+        assert!(!rewind.forward(&mut game));
+
+        let replay = Replay::parse(&Replay::from_game(&game).to_text()).unwrap();
+        assert_eq!(replay.rewinds, game.rewinds);
+        let mut rebuilt = replay.new_game();
+        play(&mut rebuilt, &mut replayers(&replay), game.ticks);
+        assert_eq!(snapshot(&rebuilt), snapshot(&game));
+        assert_eq!(rebuilt.rewinds, game.rewinds);
+    }
+}
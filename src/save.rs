@@ -0,0 +1,138 @@
+// a game that was left halfway so it can be picked up again later
+//
+// games play out the same way from the same seed and turns, so the save is the replay of the game
+// so far with a line in front telling how far it got:
+//   save ticks tickspeed
+//   seed width height border obstacles players flags
+//   tick direction player
+// playing the turns again up to that tick brings back everything, the field, the snakes, the
+// apples, the timers and where the random numbers were
+use crate::controller::ReplayController;
+use crate::replay::Replay;
+use crate::{GameState, SnakeGame};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Save {
+    pub replay: Replay,
+    pub ticks: u64,
+    // in milliseconds, the clocks of the modes go by it
+    pub tickspeed: u32,
+}
+
+impl Save {
+    pub fn from_game(game: &SnakeGame, tickspeed: u32) -> Self {
+        Self {
+            replay: Replay::from_game(game),
+            ticks: game.ticks,
+            tickspeed,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "save {} {}\n{}",
+            self.ticks,
+            self.tickspeed,
+            self.replay.to_text()
+        )
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (first, replay) = text.split_once('\n').unwrap_or((text, ""));
+        let parts: Vec<&str> = first.split_whitespace().collect();
+        let ["save", ticks, tickspeed] = parts[..] else {
+            return Err("this is no saved game".to_string());
+        };
+        Ok(Self {
+            replay: Replay::parse(replay)?,
+            ticks: ticks
+                .parse()
+                .map_err(|_| "the ticks of the save are broken")?,
+            tickspeed: tickspeed
+                .parse()
+                .map_err(|_| "the tickspeed of the save is broken")?,
+        })
+    }
+
+    // the game right where it was left, paused so nobody crashes before they are ready
+    pub fn new_game(&self) -> SnakeGame {
+        let mut game = self.replay.new_game();
+        let mut controllers: Vec<ReplayController> = self
+            .replay
+            .moves
+            .iter()
+            .map(|moves| ReplayController::from(moves))
+            .collect();
+        game.resume();
+        while game.ticks < self.ticks && game.state == GameState::Running {
+            for (player, controller) in controllers.iter_mut().enumerate() {
+                game.drive_player(player, controller);
+            }
+            game.update();
+        }
+        game.toggle_pause();
+        // the sounds of everything that happened so far were already heard
+        game.drain_events();
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::replay::tests::{bots, busy_game, play, snapshot};
+
+    #[test]
+    fn a_save_picks_up_where_it_was_left() {
+        for (seed, players) in [(0, 1), (1, 2), (2, 1)] {
+            let mut game = busy_game(seed, players, Mode::Classic);
+            let mut controllers = bots(&game);
+            play(&mut game, &mut controllers, 150);
+
+            let save = Save::from_game(&game, 120);
+            let loaded = Save::parse(&save.to_text()).unwrap();
+            assert_eq!(loaded, save);
+            let resumed = loaded.new_game();
+            if game.state == GameState::Running {
+                assert_eq!(resumed.state, GameState::Paused);
+            }
+            assert_eq!(snapshot(&resumed), snapshot(&game));
+
+            // and it goes on the same way from there
+            let mut resumed = resumed;
+            let mut resumed_controllers = bots(&resumed);
+            play(&mut game, &mut controllers, 400);
+            play(&mut resumed, &mut resumed_controllers, 400);
+            assert_eq!(snapshot(&resumed), snapshot(&game));
+        }
+    }
+
+    #[test]
+    fn broken_saves_are_refused() {
+        let replay = "1 21 21 false 0\n";
+        for text in [
+            "".to_string(),
+            replay.to_string(),
+            format!("save 10\n{}", replay),
+            format!("save ten 150\n{}", replay),
+            format!("save 10 fast\n{}", replay),
+            "save 10 150\n".to_string(),
+        ] {
+            assert!(Save::parse(&text).is_err(), "{:?} was read", text);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// plays games to the end without drawing anything, for comparing bots
+use crate::bot::ExternalController;
+use crate::controller::Controller;
+use crate::model::{Edge, Field};
+use crate::{ai, GameState, SnakeGame, MAX_SIDE, MIN_SIDE};
+use std::fmt;
+use std::time::Duration;
+
+// a built in bot or a command for an outside one
+pub enum Player {
+    Ai(String),
+    Bot(String, Duration),
+}
+
+impl Player {
+    pub fn controller(&self, field: &Field) -> Result<Box<dyn Controller>, String> {
+        match self {
+            Player::Ai(name) => ai::by_name(name, field),
+            Player::Bot(command, time_limit) => {
+                Ok(Box::new(ExternalController::spawn(command, *time_limit)?))
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Player::Ai(name) => name,
+            Player::Bot(command, _) => command,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Ending {
+    Won,
+    Wall,
+    Obstacle,
+    // ran into its own body
+    Body,
+    // still alive when the tick limit was reached
+    Timeout,
+    // the bot couldn't play this board at all
+    Unplayable,
+    // the bot broke the rules of the bot protocol
+    Disqualified,
+}
+
+impl Ending {
+    pub const ALL: [Ending; 7] = [
+        Ending::Won,
+        Ending::Wall,
+        Ending::Obstacle,
+        Ending::Body,
+        Ending::Timeout,
+        Ending::Unplayable,
+        Ending::Disqualified,
+    ];
+
+    // how the game ended, only meaningful once it is over
+    pub fn of(game: &SnakeGame) -> Self {
+        if game.state != GameState::GameOver {
+            return Ending::Timeout;
+        }
+        if game.players[0].disqualified.is_some() {
+            return Ending::Disqualified;
+        }
+        if game.has_won() {
+            return Ending::Won;
+        }
+        let head = game.snake().body[0].get().position;
+        let (width, height) = game.field.size;
+        // a shrinking arena can close on any part of the snake
+        let hit_obstacle = game.field.obstacles.as_ref().is_some_and(|obstacles| {
+            game.snake()
+                .body
+                .iter()
+                .any(|body_part| obstacles.contains(&body_part.get().position))
+        });
+        let on_edge = head.0 == 0 || head.1 == 0 || head.0 == width - 1 || head.1 == height - 1;
+        // a snake that ran into a wall edge stopped right in front of it
+        let hit_edge =
+            game.field.edge_ahead(head, game.snake().head_direction()) == Some(Edge::Wall);
+        match hit_obstacle {
+            false if hit_edge => Ending::Wall,
+            true if game.field.has_border() && on_edge => Ending::Wall,
+            true => Ending::Obstacle,
+            false => Ending::Body,
+        }
+    }
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Ending::Won => "won",
+            Ending::Wall => "wall",
+            Ending::Obstacle => "obstacle",
+            Ending::Body => "body",
+            Ending::Timeout => "timeout",
+            Ending::Unplayable => "unplayable",
+            Ending::Disqualified => "disqualified",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Outcome {
+    pub ending: Ending,
+    pub score: usize,
+    pub ticks: u64,
+}
+
+// lets the controller play until the game is over or max_ticks passed, 0 means no limit
+pub fn play(game: &mut SnakeGame, controller: &mut dyn Controller, max_ticks: u64) -> Outcome {
+    play_watched(game, controller, max_ticks, &mut |_| {})
+}
+
+// the same as play, on_tick sees the game after every tick
+pub fn play_watched(
+    game: &mut SnakeGame,
+    controller: &mut dyn Controller,
+    max_ticks: u64,
+    on_tick: &mut dyn FnMut(&SnakeGame),
+) -> Outcome {
+    game.resume();
+    while game.state == GameState::Running && (max_ticks == 0 || game.ticks < max_ticks) {
+        game.drive(controller);
+        game.update();
+        // nobody listens to the events here
+        game.drain_events();
+        on_tick(game);
+    }
+    Outcome {
+        ending: Ending::of(game),
+        score: game.score(),
+        ticks: game.ticks,
+    }
+}
+
+// for the games a bot couldn't even start
+pub fn unplayable() -> Outcome {
+    Outcome {
+        ending: Ending::Unplayable,
+        score: 0,
+        ticks: 0,
+    }
+}
+
+// a board size given as WIDTHxHEIGHT on the command line
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or(format!("\"{}\" is not a size like 21x21", value))?;
+    let parse = |side: &str| {
+        side.parse::<u32>()
+            .ok()
+            .filter(|side| (MIN_SIDE..=MAX_SIDE).contains(side))
+            .ok_or(format!(
+                "\"{}\" is not a side of {} to {} cells",
+                side, MIN_SIDE, MAX_SIDE
+            ))
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+// one line of a csv file, cells with a comma, a quote or a line break in them are quoted and
+// their quotes doubled
+pub fn csv_line(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// every column as wide as its widest cell, numbers aligned to the right
+pub fn format_table(lines: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..lines[0].len())
+        .map(|column| lines.iter().map(|line| line[column].len()).max().unwrap())
+        .collect();
+    let mut table = String::new();
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+            .collect();
+        table.push_str(&cells.join("  "));
+        table.push('\n');
+    }
+    table
+}
@@ -0,0 +1,564 @@
+// bots playing against each other in brackets, with ratings for who is better
+//
+// a match is a pairing playing the same seeds one after the other, either together on one board
+// (the arena) or each alone on the same board (solo, the better score wins). every game moves the
+// ratings, the match as a whole gives the points the swiss pairings go by
+use crate::controller::Controller;
+use crate::model::Field;
+use crate::sim::{self, Ending, Outcome, Player};
+use crate::{GameState, SnakeGame};
+use std::cmp::Ordering;
+
+pub const ELO_START: f64 = 1500.0;
+// how far one game moves the elo
+const ELO_K: f64 = 32.0;
+
+// the usual trueskill defaults
+pub const TRUESKILL_MU: f64 = 25.0;
+pub const TRUESKILL_SIGMA: f64 = TRUESKILL_MU / 3.0;
+const TRUESKILL_BETA: f64 = TRUESKILL_SIGMA / 2.0;
+const TRUESKILL_TAU: f64 = TRUESKILL_SIGMA / 100.0;
+// the performance gap that still counts as a draw, for a draw chance of 10%
+const TRUESKILL_DRAW_MARGIN: f64 = 0.7404;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum GameResult {
+    FirstWon,
+    SecondWon,
+    Draw,
+}
+
+impl GameResult {
+    // the points of the first one, a draw is worth half
+    pub fn score(self) -> f64 {
+        match self {
+            GameResult::FirstWon => 1.0,
+            GameResult::SecondWon => 0.0,
+            GameResult::Draw => 0.5,
+        }
+    }
+
+    fn flipped(self) -> Self {
+        match self {
+            GameResult::FirstWon => GameResult::SecondWon,
+            GameResult::SecondWon => GameResult::FirstWon,
+            GameResult::Draw => GameResult::Draw,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Mode {
+    // both bots on the same board, the last one alive wins
+    Arena,
+    // every bot alone on the same board, the better run wins
+    Solo,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rating {
+    pub elo: f64,
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            elo: ELO_START,
+            mu: TRUESKILL_MU,
+            sigma: TRUESKILL_SIGMA,
+        }
+    }
+}
+
+impl Rating {
+    // the trueskill everyone can be fairly sure the bot has, used for sorting
+    pub fn conservative(&self) -> f64 {
+        self.mu - 3.0 * self.sigma
+    }
+}
+
+// moves both ratings after one game
+pub fn rate(first: &mut Rating, second: &mut Rating, result: GameResult) {
+    let expected = 1.0 / (1.0 + 10f64.powf((second.elo - first.elo) / 400.0));
+    let change = ELO_K * (result.score() - expected);
+    first.elo += change;
+    second.elo -= change;
+
+    // trueskill works from the winner's side, a draw is the same both ways
+    let (winner, loser) = match result {
+        GameResult::SecondWon => (second, first),
+        _ => (first, second),
+    };
+    let winner_variance = winner.sigma.powi(2) + TRUESKILL_TAU.powi(2);
+    let loser_variance = loser.sigma.powi(2) + TRUESKILL_TAU.powi(2);
+    let c = (2.0 * TRUESKILL_BETA.powi(2) + winner_variance + loser_variance).sqrt();
+    let t = (winner.mu - loser.mu) / c;
+    let margin = TRUESKILL_DRAW_MARGIN / c;
+    let (v, w) = match result {
+        GameResult::Draw => draw_factors(t, margin),
+        _ => win_factors(t, margin),
+    };
+    winner.mu += winner_variance / c * v;
+    loser.mu -= loser_variance / c * v;
+    winner.sigma = (winner_variance * (1.0 - winner_variance / c.powi(2) * w)).sqrt();
+    loser.sigma = (loser_variance * (1.0 - loser_variance / c.powi(2) * w)).sqrt();
+}
+
+fn win_factors(t: f64, margin: f64) -> (f64, f64) {
+    let x = t - margin;
+    // far behind the numbers get too small to divide, the limits take over there
+    let cdf = normal_cdf(x);
+    let v = if cdf < 1e-12 { -x } else { normal_pdf(x) / cdf };
+    (v, v * (v + x))
+}
+
+fn draw_factors(t: f64, margin: f64) -> (f64, f64) {
+    let (low, high) = (-margin - t, margin - t);
+    let chance = normal_cdf(high) - normal_cdf(low);
+    if chance < 1e-12 {
+        return (0.0, 1.0);
+    }
+    let v = (normal_pdf(low) - normal_pdf(high)) / chance;
+    let w = v * v + (high * normal_pdf(high) - low * normal_pdf(low)) / chance;
+    (v, w)
+}
+
+fn normal_pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+// the complementary error function from numerical recipes, good to about 1e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * polynomial.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+// every pairing once, in rounds where nobody plays twice (the circle method)
+pub fn round_robin(entrants: usize) -> Vec<Vec<(usize, usize)>> {
+    // an odd number gets a made up entrant, whoever meets it sits the round out
+    let count = entrants + entrants % 2;
+    let mut circle: Vec<usize> = (0..count).collect();
+    let mut rounds = vec![];
+    for _ in 1..count {
+        let round = (0..count / 2)
+            .map(|index| (circle[index], circle[count - 1 - index]))
+            .filter(|(first, second)| *first < entrants && *second < entrants)
+            .collect();
+        rounds.push(round);
+        // the first one stays, the others turn one place
+        circle[1..].rotate_right(1);
+    }
+    rounds
+}
+
+// the standing of an entrant, for the swiss pairings and the leaderboard
+#[derive(Debug, Clone, Default)]
+pub struct Standing {
+    // one for every match won, half for a draw, the games themselves count for the ratings
+    pub points: f64,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub rating: Rating,
+    // the ones already played, swiss tries not to pair them again
+    pub opponents: Vec<usize>,
+    pub had_bye: bool,
+}
+
+impl Standing {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+}
+
+// the entrants from the best to the worst: points, then rating
+pub fn ranking(standings: &[Standing]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (&standings[*a], &standings[*b]);
+        b.points
+            .partial_cmp(&a.points)
+            .unwrap_or(Ordering::Equal)
+            .then(
+                b.rating
+                    .conservative()
+                    .partial_cmp(&a.rating.conservative())
+                    .unwrap_or(Ordering::Equal),
+            )
+    });
+    order
+}
+
+// the next swiss round: neighbours in the ranking play, rematches only when nothing else is left.
+// with an odd number the lowest one that hasn't had a bye sits out and gets the point
+pub fn swiss_round(standings: &[Standing]) -> (Vec<(usize, usize)>, Option<usize>) {
+    let mut order = ranking(standings);
+    let mut bye = None;
+    if order.len() % 2 == 1 {
+        let sits_out = order
+            .iter()
+            .rposition(|entrant| !standings[*entrant].had_bye)
+            .unwrap_or(order.len() - 1);
+        bye = Some(order.remove(sits_out));
+    }
+    if let Some(pairs) = pair_without_rematches(&order, standings) {
+        return (pairs, bye);
+    }
+    let mut pairs = vec![];
+    while !order.is_empty() {
+        let first = order.remove(0);
+        let opponent = order
+            .iter()
+            .position(|other| !standings[first].opponents.contains(other))
+            .unwrap_or(0);
+        pairs.push((first, order.remove(opponent)));
+    }
+    (pairs, bye)
+}
+
+// the best one left plays the next best it hasn't met yet, backing up when that leaves two at the
+// end who already played. none when every way of pairing them has a rematch
+fn pair_without_rematches(order: &[usize], standings: &[Standing]) -> Option<Vec<(usize, usize)>> {
+    let Some((first, rest)) = order.split_first() else {
+        return Some(vec![]);
+    };
+    for (index, other) in rest.iter().enumerate() {
+        if standings[*first].opponents.contains(other) {
+            continue;
+        }
+        let mut others = rest.to_vec();
+        others.remove(index);
+        if let Some(mut pairs) = pair_without_rematches(&others, standings) {
+            pairs.insert(0, (*first, *other));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+// what the games are played on
+#[derive(Debug, Clone, Copy)]
+pub struct Arena {
+    pub width: u32,
+    pub height: u32,
+    pub border: bool,
+    pub obstacles: u32,
+    pub max_ticks: u64,
+}
+
+impl Arena {
+    fn new_game(&self, seed: u64, players: usize) -> SnakeGame {
+        SnakeGame::from_seed(
+            self.width,
+            self.height,
+            self.border,
+            (self.obstacles > 0, self.obstacles),
+            seed,
+        )
+        .with_players(players)
+    }
+}
+
+// one game between two bots, with the games to save as replays
+pub struct PlayedGame {
+    pub result: GameResult,
+    pub games: Vec<SnakeGame>,
+}
+
+// the first player starts on the upper row, the sides are swapped on odd seeds so both get
+// every start as often. on_tick sees every tick, for watching the games
+pub fn play_game(
+    mode: Mode,
+    arena: &Arena,
+    players: (&Player, &Player),
+    seed: u64,
+    on_tick: &mut dyn FnMut(&SnakeGame),
+) -> PlayedGame {
+    match mode {
+        Mode::Arena if seed % 2 == 1 => {
+            let played = play_arena(arena, (players.1, players.0), seed, on_tick);
+            PlayedGame {
+                result: played.result.flipped(),
+                games: played.games,
+            }
+        }
+        Mode::Arena => play_arena(arena, players, seed, on_tick),
+        Mode::Solo => {
+            let (first_game, first) = play_solo(arena, players.0, seed, on_tick);
+            let (second_game, second) = play_solo(arena, players.1, seed, on_tick);
+            let result = match compare_runs(&first, &second) {
+                Ordering::Greater => GameResult::FirstWon,
+                Ordering::Less => GameResult::SecondWon,
+                Ordering::Equal => GameResult::Draw,
+            };
+            PlayedGame {
+                result,
+                games: vec![first_game, second_game],
+            }
+        }
+    }
+}
+
+fn controller_for(player: &Player, field: &Field) -> Option<Box<dyn Controller>> {
+    player.controller(field).ok()
+}
+
+fn play_arena(
+    arena: &Arena,
+    players: (&Player, &Player),
+    seed: u64,
+    on_tick: &mut dyn FnMut(&SnakeGame),
+) -> PlayedGame {
+    let mut game = arena.new_game(seed, 2);
+    let mut controllers = [
+        controller_for(players.0, &game.field),
+        controller_for(players.1, &game.field),
+    ];
+    // a bot that can't play this board at all loses it
+    let result = match (&controllers[0], &controllers[1]) {
+        (None, None) => Some(GameResult::Draw),
+        (None, Some(_)) => Some(GameResult::SecondWon),
+        (Some(_), None) => Some(GameResult::FirstWon),
+        _ => None,
+    };
+    if let Some(result) = result {
+        return PlayedGame {
+            result,
+            games: vec![game],
+        };
+    }
+
+    game.resume();
+    while game.state == GameState::Running && game.ticks < arena.max_ticks {
+        for (player, controller) in controllers.iter_mut().enumerate() {
+            if let Some(controller) = controller {
+                game.drive_player(player, controller.as_mut());
+            }
+        }
+        game.update();
+        game.drain_events();
+        on_tick(&game);
+    }
+
+    let result = match game.winner() {
+        Some(0) => GameResult::FirstWon,
+        Some(_) => GameResult::SecondWon,
+        // both still going when time ran out, the longer snake wins
+        None if game.state == GameState::Running => {
+            match game.players[0].score().cmp(&game.players[1].score()) {
+                Ordering::Greater => GameResult::FirstWon,
+                Ordering::Less => GameResult::SecondWon,
+                Ordering::Equal => GameResult::Draw,
+            }
+        }
+        None => GameResult::Draw,
+    };
+    PlayedGame {
+        result,
+        games: vec![game],
+    }
+}
+
+fn play_solo(
+    arena: &Arena,
+    player: &Player,
+    seed: u64,
+    on_tick: &mut dyn FnMut(&SnakeGame),
+) -> (SnakeGame, Outcome) {
+    let mut game = arena.new_game(seed, 1);
+    let outcome = match controller_for(player, &game.field) {
+        Some(mut controller) => {
+            sim::play_watched(&mut game, controller.as_mut(), arena.max_ticks, on_tick)
+        }
+        None => sim::unplayable(),
+    };
+    (game, outcome)
+}
+
+// clearing the board beats everything, the faster the better, otherwise the score counts
+fn compare_runs(first: &Outcome, second: &Outcome) -> Ordering {
+    let won = |outcome: &Outcome| outcome.ending == Ending::Won;
+    match (won(first), won(second)) {
+        (true, true) => second.ticks.cmp(&first.ticks),
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => first.score.cmp(&second.score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        for entrants in 2..=9 {
+            let rounds = round_robin(entrants);
+            assert_eq!(rounds.len(), entrants + entrants % 2 - 1);
+            let mut met = vec![vec![0; entrants]; entrants];
+            for round in &rounds {
+                let mut playing = vec![false; entrants];
+                for (first, second) in round {
+                    assert!(!playing[*first] && !playing[*second], "twice in a round");
+                    playing[*first] = true;
+                    playing[*second] = true;
+                    met[*first][*second] += 1;
+                    met[*second][*first] += 1;
+                }
+            }
+            for (first, counts) in met.iter().enumerate() {
+                for (second, count) in counts.iter().enumerate() {
+                    let expected = if first == second { 0 } else { 1 };
+                    assert_eq!(*count, expected, "{} and {}", first, second);
+                }
+            }
+        }
+    }
+
+    // whether the entrants can still be paired up without anyone playing the same one again
+    fn can_avoid_rematches(entrants: &[usize], standings: &[Standing]) -> bool {
+        let Some((first, rest)) = entrants.split_first() else {
+            return true;
+        };
+        rest.iter().enumerate().any(|(index, other)| {
+            let mut others = rest.to_vec();
+            others.remove(index);
+            !standings[*first].opponents.contains(other) && can_avoid_rematches(&others, standings)
+        })
+    }
+
+    #[test]
+    fn swiss_avoids_rematches_when_it_can() {
+        // a small generator so the results are all over the place but the same every run
+        let mut state: u64 = 1;
+        for entrants in 4..=9 {
+            for _ in 0..50 {
+                let mut standings = vec![Standing::default(); entrants];
+                for _ in 1..entrants {
+                    let (pairs, bye) = swiss_round(&standings);
+                    let playing: Vec<usize> = (0..entrants)
+                        .filter(|entrant| Some(*entrant) != bye)
+                        .collect();
+                    assert_eq!(pairs.len() * 2, playing.len());
+                    if can_avoid_rematches(&playing, &standings) {
+                        for (first, second) in &pairs {
+                            assert!(!standings[*first].opponents.contains(second), "a rematch");
+                        }
+                    }
+
+                    if let Some(bye) = bye {
+                        assert!(!standings[bye].had_bye || standings.iter().all(|s| s.had_bye));
+                        standings[bye].had_bye = true;
+                        standings[bye].points += 1.0;
+                    }
+                    for (first, second) in pairs {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        let winner = if (state >> 33) & 1 == 0 {
+                            first
+                        } else {
+                            second
+                        };
+                        standings[winner].points += 1.0;
+                        standings[first].opponents.push(second);
+                        standings[second].opponents.push(first);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_pairs_neighbours_in_the_ranking() {
+        let mut standings = vec![Standing::default(); 4];
+        for (entrant, points) in [3.0, 2.0, 1.0, 0.0].into_iter().enumerate() {
+            standings[entrant].points = points;
+        }
+        assert_eq!(swiss_round(&standings), (vec![(0, 1), (2, 3)], None));
+
+        standings[0].opponents = vec![1];
+        standings[1].opponents = vec![0];
+        assert_eq!(swiss_round(&standings), (vec![(0, 2), (1, 3)], None));
+
+        // the lowest one without a bye sits out
+        standings.push(Standing::default());
+        standings[4].had_bye = true;
+        assert_eq!(swiss_round(&standings).1, Some(3));
+    }
+
+    #[test]
+    fn elo_moves_by_how_unexpected_the_result_was() {
+        let (mut first, mut second) = (Rating::default(), Rating::default());
+        rate(&mut first, &mut second, GameResult::FirstWon);
+        assert_close(first.elo, 1516.0);
+        assert_close(second.elo, 1484.0);
+
+        let mut favourite = Rating {
+            elo: 1600.0,
+            ..Rating::default()
+        };
+        let mut underdog = Rating {
+            elo: 1400.0,
+            ..Rating::default()
+        };
+        rate(&mut favourite, &mut underdog, GameResult::SecondWon);
+        assert_close(favourite.elo, 1575.688);
+        assert_close(underdog.elo, 1424.312);
+
+        let (mut first, mut second) = (Rating::default(), Rating::default());
+        rate(&mut first, &mut second, GameResult::Draw);
+        assert_close(first.elo, 1500.0);
+    }
+
+    // the same numbers the reference trueskill implementation gives for one game
+    #[test]
+    fn trueskill_matches_the_reference() {
+        let (mut first, mut second) = (Rating::default(), Rating::default());
+        rate(&mut first, &mut second, GameResult::FirstWon);
+        assert_close(first.mu, 29.396);
+        assert_close(first.sigma, 7.171);
+        assert_close(second.mu, 20.604);
+        assert_close(second.sigma, 7.171);
+
+        let (mut first, mut second) = (Rating::default(), Rating::default());
+        rate(&mut first, &mut second, GameResult::SecondWon);
+        assert_close(first.mu, 20.604);
+        assert_close(second.mu, 29.396);
+
+        let (mut first, mut second) = (Rating::default(), Rating::default());
+        rate(&mut first, &mut second, GameResult::Draw);
+        assert_close(first.mu, 25.0);
+        assert_close(first.sigma, 6.458);
+        assert_close(second.sigma, 6.458);
+    }
+}
@@ -0,0 +1,500 @@
+// plays the game inside the terminal, every grid square is two characters wide so it looks square
+use crate::{
+    clock, effects, game_over_message, leading_head, scores, Campaign, GameSettings, Session,
+};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use snake_game_sdl::camera::Camera;
+use snake_game_sdl::model::{AppleKind, Direction, PowerUpKind, Snake, RING_WARNING};
+use snake_game_sdl::net::NetClient;
+use snake_game_sdl::palette::{Palette, Rgb};
+use snake_game_sdl::rewind::Rewind;
+use snake_game_sdl::*;
+use std::io::{self, Stdout, Write};
+use std::time::Instant;
+
+const CELL_WIDTH: u16 = 2;
+// the ends of the same portal look the same
+const PORTAL_GLYPHS: [&str; 9] = ["◎1", "◎2", "◎3", "◎4", "◎5", "◎6", "◎7", "◎8", "◎9"];
+// the lines under the field used for the score and the controls
+const STATUS_LINES: u16 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    // the player the snake belongs to
+    SnakeHead(usize),
+    SnakeBody(usize),
+    Apple(AppleKind),
+    PowerUp(PowerUpKind),
+    // the number of the pair it belongs to
+    Portal(usize),
+    Obstacle,
+    // a gate that is open, flashing when true
+    OpenGate(bool),
+    // part of the ring of walls closing next
+    ClosingRing,
+    Exit,
+    // the best run so far, see-through in the window
+    Ghost,
+}
+
+impl Cell {
+    // every cell has its own glyph so the palettes without colors still work
+    fn glyph(self, palette: &Palette) -> (&'static str, Color) {
+        match self {
+            Cell::Empty => ("  ", color(palette.background)),
+            Cell::SnakeHead(player) => ("██", color(palette.snake_color(player))),
+            Cell::SnakeBody(player) => ("▓▓", color(palette.snake_color(player))),
+            Cell::Apple(kind) => (apple_glyph(kind), color(palette.apple_color(kind))),
+            Cell::PowerUp(kind) => (power_up_glyph(kind), color(palette.power_up)),
+            Cell::Portal(pair) => (
+                PORTAL_GLYPHS[pair % PORTAL_GLYPHS.len()],
+                color(palette.portal),
+            ),
+            Cell::Obstacle => ("▒▒", color(palette.obstacle)),
+            Cell::OpenGate(true) => ("▓▓", color(palette.highlight)),
+            Cell::OpenGate(false) => ("░░", color(palette.obstacle)),
+            Cell::ClosingRing => ("░░", color(palette.highlight)),
+            Cell::Exit => ("[]", color(palette.highlight)),
+            Cell::Ghost => ("◌◌", color(palette.snake_color(0))),
+        }
+    }
+}
+
+fn apple_glyph(kind: AppleKind) -> &'static str {
+    match kind {
+        AppleKind::Normal => "● ",
+        AppleKind::Golden => "★ ",
+        AppleKind::Poison => "✖ ",
+        AppleKind::Rotten => "○ ",
+    }
+}
+
+// the first letter tells them apart, speed and shrink would share it
+fn power_up_glyph(kind: PowerUpKind) -> &'static str {
+    match kind {
+        PowerUpKind::SlowMotion => "◆s",
+        PowerUpKind::Speed => "◆f",
+        PowerUpKind::Ghost => "◆g",
+        PowerUpKind::Shrink => "◆-",
+        PowerUpKind::Multiplier => "◆x",
+        PowerUpKind::Magnet => "◆m",
+    }
+}
+
+fn color(rgb: Rgb) -> Color {
+    Color::Rgb {
+        r: rgb.0,
+        g: rgb.1,
+        b: rgb.2,
+    }
+}
+
+// puts the terminal back the way it was even if the game panics
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter(stdout: &mut Stdout) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn view_in_cells() -> (u32, u32) {
+    let (columns, rows) = terminal::size().unwrap_or((80, 24));
+    (
+        (columns / CELL_WIDTH).max(1) as u32,
+        rows.saturating_sub(STATUS_LINES).max(1) as u32,
+    )
+}
+
+fn draw(
+    stdout: &mut Stdout,
+    game: &SnakeGame,
+    camera: &Camera,
+    palette: &Palette,
+    ghost: Option<&Snake>,
+    status: &str,
+) -> io::Result<()> {
+    let (view_width, view_height) = camera.view;
+    let mut cells = vec![Cell::Empty; (view_width * view_height) as usize];
+    let mut put = |position: (u32, u32), cell: Cell| {
+        if let Some((x, y)) = camera.to_view(position) {
+            cells[(y * view_width + x) as usize] = cell;
+        }
+    };
+
+    if let Some(obstacles) = &game.field.obstacles {
+        for obstacle_part in obstacles {
+            put(*obstacle_part, Cell::Obstacle);
+        }
+    }
+    for gate in game.field.gates.iter().filter(|gate| !gate.closed) {
+        let flashing = gate.is_closing(game.ticks) && game.ticks % 4 < 2;
+        for cell in &gate.cells {
+            put(*cell, Cell::OpenGate(flashing));
+        }
+    }
+    if let (Some(ring), Some(closes_in)) = (
+        game.field.next_ring(),
+        game.field.ring_closes_in(game.ticks),
+    ) {
+        if closes_in <= RING_WARNING && game.ticks % 4 < 2 {
+            for cell in game.field.ring(ring) {
+                put(cell, Cell::ClosingRing);
+            }
+        }
+    }
+    if let Some(exit) = game.exit() {
+        put(exit, Cell::Exit);
+    }
+    for apple in &game.apples {
+        put(apple.position, Cell::Apple(apple.kind));
+    }
+    for item in &game.power_ups {
+        put(item.position, Cell::PowerUp(item.kind));
+    }
+    for (pair, (first, second)) in game.field.portals.iter().enumerate() {
+        put(*first, Cell::Portal(pair));
+        put(*second, Cell::Portal(pair));
+    }
+    // under the snakes, it can't run into them anyway
+    if let Some(ghost) = ghost {
+        for snake_part in &ghost.body {
+            put(snake_part.get().position, Cell::Ghost);
+        }
+    }
+    for (player, snake) in game.visible_snakes() {
+        for (index, snake_part) in snake.body.iter().enumerate() {
+            let cell = if index == 0 {
+                Cell::SnakeHead(player)
+            } else {
+                Cell::SnakeBody(player)
+            };
+            put(snake_part.get().position, cell);
+        }
+    }
+
+    // only switch colors when they change, it keeps the output small over ssh
+    for (y, row) in cells.chunks(view_width as usize).enumerate() {
+        queue!(stdout, MoveTo(0, y as u16))?;
+        let mut current_color = None;
+        for cell in row {
+            let (glyph, foreground) = cell.glyph(palette);
+            if current_color != Some(foreground) {
+                queue!(
+                    stdout,
+                    SetForegroundColor(foreground),
+                    SetBackgroundColor(color(palette.background))
+                )?;
+                current_color = Some(foreground);
+            }
+            queue!(stdout, Print(glyph))?;
+        }
+    }
+
+    queue!(
+        stdout,
+        ResetColor,
+        MoveTo(0, view_height as u16),
+        terminal::Clear(terminal::ClearType::FromCursorDown),
+        Print(status),
+        MoveTo(0, view_height as u16 + 1),
+        Print("<W/A/S/D> move  <P> pause  <R> restart  <,/.> rewind  <F5> save  <Q> quit"),
+    )?;
+    stdout.flush()
+}
+
+// the score, the clock, the power-ups and what is going on, for the line under the field
+fn status_line(
+    game: &SnakeGame,
+    session: &Session,
+    settings: &GameSettings,
+    // how the game did on the leaderboard or in the campaign, or that it was saved
+    message: Option<&str>,
+) -> String {
+    let tick = session.base_tick(settings);
+    let status = match (game.state, session.status()) {
+        (GameState::GameOver, Some(online)) => {
+            format!("{} {}", game_over_message(game, tick), online)
+        }
+        (_, Some(online)) => online,
+        (GameState::GameOver, None) => format!(
+            "{} {}",
+            game_over_message(game, tick),
+            message.unwrap_or("Press <R> to restart!")
+        ),
+        (GameState::Paused, None) => match message {
+            Some(message) => format!("{} Paused, press <W/A/S/D> to play", message),
+            None => "Paused, press <W/A/S/D> to play".to_string(),
+        },
+        (GameState::Running, None) => String::new(),
+    };
+    format!(
+        "Score: {}  {}  {}  {}",
+        scores(game),
+        clock(game, tick),
+        effects(game, session.tick_length(settings, game)),
+        status
+    )
+}
+
+// the level select takes the place of the status lines, the picked level is shown above it
+fn draw_level_select(stdout: &mut Stdout, campaign: &Campaign, row: u16) -> io::Result<()> {
+    let locked = match campaign.is_unlocked() {
+        true => "",
+        false => "  win the one before it first",
+    };
+    queue!(
+        stdout,
+        ResetColor,
+        MoveTo(0, row),
+        terminal::Clear(terminal::ClearType::FromCursorDown),
+        Print(format!(
+            "Level {}/{} {}{}",
+            campaign.picked + 1,
+            campaign.levels.len(),
+            campaign.describe(campaign.picked),
+            locked
+        )),
+        MoveTo(0, row + 1),
+        Print("<W/S> pick a level  <Enter> play  <Q> quit"),
+    )?;
+    stdout.flush()
+}
+
+pub fn run(
+    settings: &mut GameSettings,
+    online: Option<NetClient>,
+    mut campaign: Option<Campaign>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout();
+    let _guard = TerminalGuard::enter(&mut stdout)?;
+    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+
+    // only the audio subsystem of sdl is used here, without a device or sdl it stays silent
+    #[cfg(feature = "sdl")]
+    let sdl_context = sdl2::init()?;
+    #[cfg(feature = "sdl")]
+    let mut audio = settings.open_audio(&sdl_context);
+
+    let (mut game, mut session) = settings.new_session(online)?;
+    let mut ghost = settings.new_ghost(&game);
+    let mut palette = settings.palette;
+    let mut camera = Camera::from(game.field.size, view_in_cells(), settings.deadzone);
+    camera.center_on(leading_head(&game));
+    // recording goes through the offscreen renderer so it works without a display too
+    #[cfg(feature = "sdl")]
+    let mut recorder = settings.new_recorder(&game)?;
+    let mut message: Option<String> = None;
+    let can_rewind = settings.can_rewind(&session);
+    let mut rewind = Rewind::new();
+
+    'running: loop {
+        let tick_end = Instant::now() + session.tick_time(settings, &game);
+        loop {
+            let remaining = tick_end.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !event::poll(remaining)? {
+                break;
+            }
+            // the arrow keys are a second layout for the second player, alone they steer the first
+            let arrow_player = 1.min(game.players.len() - 1);
+            let event = event::read()?;
+            // the level select shows the picked level until it is started
+            if let Some(campaign) = campaign.as_mut().filter(|campaign| campaign.selecting) {
+                let Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) = event
+                else {
+                    continue;
+                };
+                let difference = match code {
+                    KeyCode::Esc | KeyCode::Char('q') => break 'running,
+                    KeyCode::Char('w') | KeyCode::Up => -1,
+                    KeyCode::Char('s') | KeyCode::Down => 1,
+                    KeyCode::Enter | KeyCode::Char(' ') if campaign.is_unlocked() => {
+                        campaign.selecting = false;
+                        session.restart(settings, &mut game)?;
+                        ghost = settings.new_ghost(&game);
+                        rewind.clear();
+                        camera.center_on(leading_head(&game));
+                        execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if campaign.move_pick(difference) {
+                    settings.pick_level(campaign.level());
+                    game = settings.new_game();
+                    ghost = settings.new_ghost(&game);
+                    camera.center_on(leading_head(&game));
+                    #[cfg(feature = "sdl")]
+                    audio.play_navigate();
+                }
+                continue;
+            }
+            match event {
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => match code {
+                    KeyCode::Esc | KeyCode::Char('q') => break 'running,
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        break 'running
+                    }
+                    KeyCode::Char('r') => {
+                        if let Some(campaign) = &mut campaign {
+                            campaign.restart(&game);
+                            settings.pick_level(campaign.level());
+                        }
+                        message = None;
+                        session.restart(settings, &mut game)?;
+                        ghost = settings.new_ghost(&game);
+                        rewind.clear();
+                        #[cfg(feature = "sdl")]
+                        if let Some(recorder) = &mut recorder {
+                            recorder.record_tick(&game, &palette)?;
+                        }
+                        camera.center_on(leading_head(&game));
+                        execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                    }
+                    KeyCode::Char('l') => {
+                        if let Some(campaign) = &mut campaign {
+                            settings.save_unfinished_replay(&game);
+                            campaign.selecting = true;
+                            rewind.clear();
+                            message = None;
+                            game = settings.new_game();
+                            ghost = settings.new_ghost(&game);
+                            camera.center_on(leading_head(&game));
+                        }
+                    }
+                    KeyCode::Char(',' | '.') if !can_rewind => {
+                        message = Some("Rewinding is off in this game.".to_string());
+                    }
+                    KeyCode::Char(step @ (',' | '.')) => {
+                        let stepped = match step {
+                            ',' => rewind.back(&mut game),
+                            _ => rewind.forward(&mut game),
+                        };
+                        if stepped {
+                            session.clear_turns();
+                            message = Some(format!("Tick {}.", game.ticks));
+                        }
+                    }
+                    KeyCode::F(5) if session.is_local() => {
+                        if game.state == GameState::Running {
+                            session.toggle_pause(&mut game);
+                        }
+                        message = Some(settings.save_game(&game));
+                    }
+                    KeyCode::Char('p') => session.toggle_pause(&mut game),
+                    KeyCode::Char('c') => palette = palette.next(),
+                    #[cfg(feature = "sdl")]
+                    KeyCode::Char('n') => audio.toggle_mute(),
+                    #[cfg(feature = "sdl")]
+                    KeyCode::Char('[') => audio.change_volume(-10),
+                    #[cfg(feature = "sdl")]
+                    KeyCode::Char(']') => audio.change_volume(10),
+                    // the same layouts as the sdl frontend, there are no gamepads in the terminal
+                    KeyCode::Char('w') => session.steer(&mut game, 0, Direction::UP),
+                    KeyCode::Char('a') => session.steer(&mut game, 0, Direction::RIGHT),
+                    KeyCode::Char('s') => session.steer(&mut game, 0, Direction::DOWN),
+                    KeyCode::Char('d') => session.steer(&mut game, 0, Direction::LEFT),
+                    KeyCode::Up => session.steer(&mut game, arrow_player, Direction::UP),
+                    KeyCode::Left => session.steer(&mut game, arrow_player, Direction::RIGHT),
+                    KeyCode::Down => session.steer(&mut game, arrow_player, Direction::DOWN),
+                    KeyCode::Right => session.steer(&mut game, arrow_player, Direction::LEFT),
+                    _ => {}
+                },
+                Event::Resize(..) => {
+                    camera.resize(view_in_cells());
+                    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                }
+                _ => {}
+            }
+        }
+
+        let was_over = game.state == GameState::GameOver;
+        if can_rewind && game.state == GameState::Running {
+            rewind.record(&mut game);
+        }
+        session.advance(&mut game);
+        if let Some(ghost) = &mut ghost {
+            ghost.follow(game.ticks);
+        }
+        // games watched over the network can change the board between games
+        if camera.field() != game.field.size {
+            camera = Camera::from(game.field.size, view_in_cells(), settings.deadzone);
+            camera.center_on(leading_head(&game));
+        }
+        // a note about the save is gone once the game goes on
+        if game.state == GameState::Running {
+            message = None;
+        }
+        if !was_over && game.state == GameState::GameOver {
+            settings.save_replay(&game);
+            message = match &mut campaign {
+                Some(campaign) => Some(campaign.finish(&game)),
+                None => settings
+                    .record_result(&game)
+                    .map(|result| format!("{} Press <R> to restart!", result)),
+            };
+            if let Some(best) = settings.offer_ghost(&game) {
+                message = Some(match message {
+                    Some(message) => format!("{} {}", best, message),
+                    None => format!("{} Press <R> to restart!", best),
+                });
+            }
+        }
+        #[cfg(feature = "sdl")]
+        if let Some(recorder) = &mut recorder {
+            recorder.record_tick(&game, &palette)?;
+        }
+        #[cfg(feature = "sdl")]
+        {
+            audio.play_events(&game.drain_events());
+            audio.sync_music(game.state);
+        }
+        // nothing plays the events without sdl but they still pile up
+        #[cfg(not(feature = "sdl"))]
+        game.drain_events();
+        camera.follow(leading_head(&game));
+        draw(
+            &mut stdout,
+            &game,
+            &camera,
+            &palette,
+            ghost.as_ref().and_then(|ghost| ghost.snake()),
+            &status_line(&game, &session, settings, message.as_deref()),
+        )?;
+        if let Some(campaign) = campaign.as_ref().filter(|campaign| campaign.selecting) {
+            draw_level_select(&mut stdout, campaign, camera.view.1 as u16)?;
+        }
+    }
+
+    settings.save_unfinished_replay(&game);
+    let saved = settings.save_unfinished_game(&game, &session);
+    // back on the normal screen so the note stays around
+    drop(_guard);
+    if let Some(saved) = saved {
+        println!("{}", saved);
+    }
+    Ok(())
+}